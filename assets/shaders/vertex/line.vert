@@ -0,0 +1,17 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(push_constant) uniform PushConstants {
+    mat4 view_projection;
+    vec4 color;
+    vec2 from;
+    vec2 to;
+} push_constants;
+
+layout(location = 0) out vec4 vertex_color;
+
+void main() {
+    vec2 pos = gl_VertexIndex == 0 ? push_constants.from : push_constants.to;
+    vertex_color = push_constants.color;
+    gl_Position = push_constants.view_projection * vec4(pos, 0.0, 1.0);
+}
\ No newline at end of file
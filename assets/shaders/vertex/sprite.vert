@@ -0,0 +1,30 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(push_constant) uniform PushConstants {
+    mat4 view_projection;
+    vec4 color;
+    vec2 pos;
+    vec2 scale;
+    vec4 uv;
+} push_constants;
+
+layout(location = 0) out vec4 vertex_color;
+layout(location = 1) out vec2 vertex_uv;
+
+vec2 corners[6] = vec2[](
+    vec2(0.0, 0.0),
+    vec2(0.0, 1.0),
+    vec2(1.0, 1.0),
+    vec2(0.0, 0.0),
+    vec2(1.0, 1.0),
+    vec2(1.0, 0.0)
+);
+
+void main() {
+    vec2 corner = corners[gl_VertexIndex];
+    vertex_color = push_constants.color;
+    vertex_uv = mix(push_constants.uv.xy, push_constants.uv.zw, corner);
+    vec2 position = push_constants.pos + corner * push_constants.scale;
+    gl_Position = push_constants.view_projection * vec4(position, 0.0, 1.0);
+}
\ No newline at end of file
@@ -2,6 +2,7 @@
 #extension GL_ARB_separate_shader_objects : enable
 
 layout(push_constant) uniform PushConstants {
+    mat4 view_projection;
     vec4 color;
     vec2 pos;
     vec2 scale;
@@ -18,5 +19,5 @@ vec2 positions[3] = vec2[](
 void main() {
     vec2 pos = positions[gl_VertexIndex] * push_constants.scale;
     vertex_color = push_constants.color;
-    gl_Position = vec4((pos + push_constants.pos), 0.0, 1.0);
+    gl_Position = push_constants.view_projection * vec4((pos + push_constants.pos), 0.0, 1.0);
 }
\ No newline at end of file
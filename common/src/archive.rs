@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+// the layout, all numbers little endian:
+//   header  MAGIC, VERSION: u32, entry count: u32, index offset: u64
+//   data    the contents of every entry, one after the other
+//   index   per entry: path length: u16, path, offset: u64, stored size: u64, size: u64,
+//           compression: u8, crc32 of the contents: u32
+// paths are relative to the packed directory, separated by `/`
+const MAGIC: &[u8; 4] = b"GPAK";
+const VERSION: u32 = 1;
+const HEADER_SIZE: u64 = 4 + 4 + 4 + 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    Stored = 0,
+    Deflate = 1,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub offset: u64,
+    // in the archive
+    pub stored_size: u64,
+    pub size: u64,
+    pub compression: Compression,
+    pub hash: u32,
+}
+
+pub fn hash(contents: &[u8]) -> u32 {
+    crc32fast::hash(contents)
+}
+
+// an archive opened for reading, files are read from it on demand
+#[derive(Debug)]
+pub struct Archive {
+    entries: HashMap<String, Entry>,
+    file: Mutex<File>,
+}
+
+impl Archive {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let mut file = File::open(path).map_err(|error| error.to_string())?;
+        let mut header = [0; HEADER_SIZE as usize];
+        file.read_exact(&mut header)
+            .map_err(|_| "not an asset archive")?;
+        if &header[0..4] != MAGIC {
+            return Err("not an asset archive".to_string());
+        }
+        let version = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        if version != VERSION {
+            return Err(format!("archive version {}, expected {}", version, VERSION));
+        }
+        let count = u32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+        let mut index_offset = [0; 8];
+        index_offset.copy_from_slice(&header[12..20]);
+        let index_offset = u64::from_le_bytes(index_offset);
+        let length = file.metadata().map_err(|error| error.to_string())?.len();
+        if index_offset < HEADER_SIZE || index_offset > length {
+            return Err("truncated archive".to_string());
+        }
+        file.seek(SeekFrom::Start(index_offset))
+            .map_err(|error| error.to_string())?;
+        let mut index = vec![];
+        file.read_to_end(&mut index)
+            .map_err(|error| error.to_string())?;
+        let entries = read_index(&index, count, index_offset).ok_or("corrupt index")?;
+        Ok(Self {
+            entries,
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &Entry)> {
+        self.entries
+            .iter()
+            .map(|(path, entry)| (path.as_str(), entry))
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.entries.contains_key(path)
+    }
+
+    // None when there is no such file, the contents are checked against their hash
+    pub fn read(&self, path: &str) -> Option<Result<Vec<u8>, String>> {
+        let entry = self.entries.get(path)?;
+        Some(self.read_entry(entry))
+    }
+
+    fn read_entry(&self, entry: &Entry) -> Result<Vec<u8>, String> {
+        let mut stored = vec![0; entry.stored_size as usize];
+        {
+            let mut file = self.file.lock().unwrap();
+            file.seek(SeekFrom::Start(entry.offset))
+                .and_then(|_| file.read_exact(&mut stored))
+                .map_err(|error| error.to_string())?;
+        }
+        // never inflated much past the size the index gives. The inflater only sees the end of
+        // the data with room left over, one byte more is caught by the size check below
+        let contents = match entry.compression {
+            Compression::Stored => stored,
+            Compression::Deflate => miniz_oxide::inflate::decompress_to_vec_with_limit(
+                &stored,
+                entry.size.saturating_add(1) as usize,
+            )
+            .map_err(|error| format!("corrupt compressed data, {:?}", error))?,
+        };
+        if contents.len() as u64 != entry.size || hash(&contents) != entry.hash {
+            return Err("contents don't match their hash".to_string());
+        }
+        Ok(contents)
+    }
+}
+
+// every entry has to be in the data, between the header and `data_end`, so that nothing read
+// for it is more than the file holds
+fn read_index(mut index: &[u8], count: u32, data_end: u64) -> Option<HashMap<String, Entry>> {
+    fn take<'a>(index: &mut &'a [u8], length: usize) -> Option<&'a [u8]> {
+        if index.len() < length {
+            return None;
+        }
+        let (taken, rest) = index.split_at(length);
+        *index = rest;
+        Some(taken)
+    }
+    fn u64_from(bytes: &[u8]) -> u64 {
+        let mut array = [0; 8];
+        array.copy_from_slice(bytes);
+        u64::from_le_bytes(array)
+    }
+    let mut entries = HashMap::new();
+    for _ in 0..count {
+        let length = take(&mut index, 2)?;
+        let length = u16::from_le_bytes([length[0], length[1]]) as usize;
+        let path = String::from_utf8(take(&mut index, length)?.to_vec()).ok()?;
+        let fields = take(&mut index, 8 + 8 + 8 + 1 + 4)?;
+        let compression = match fields[24] {
+            0 => Compression::Stored,
+            1 => Compression::Deflate,
+            _ => return None,
+        };
+        let entry = Entry {
+            offset: u64_from(&fields[0..8]),
+            stored_size: u64_from(&fields[8..16]),
+            size: u64_from(&fields[16..24]),
+            compression,
+            hash: u32::from_le_bytes([fields[25], fields[26], fields[27], fields[28]]),
+        };
+        let end = entry.offset.checked_add(entry.stored_size)?;
+        if entry.offset < HEADER_SIZE || end > data_end {
+            return None;
+        }
+        entries.insert(path, entry);
+    }
+    Some(entries)
+}
+
+// collects files and writes them out as an archive
+#[derive(Debug, Default)]
+pub struct ArchiveWriter {
+    files: Vec<(String, Vec<u8>)>,
+}
+
+impl ArchiveWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, path: impl Into<String>, contents: Vec<u8>) {
+        self.files.push((path.into(), contents));
+    }
+
+    // files that don't get smaller are stored as they are
+    pub fn write(&self, out: &mut impl Write, compress: bool) -> std::io::Result<Vec<Entry>> {
+        let mut data = vec![];
+        let mut entries = vec![];
+        for (_, contents) in &self.files {
+            let compressed = if compress {
+                Some(miniz_oxide::deflate::compress_to_vec(contents, 6))
+                    .filter(|compressed| compressed.len() < contents.len())
+            } else {
+                None
+            };
+            let (stored, compression) = match &compressed {
+                Some(compressed) => (compressed, Compression::Deflate),
+                None => (contents, Compression::Stored),
+            };
+            entries.push(Entry {
+                offset: HEADER_SIZE + data.len() as u64,
+                stored_size: stored.len() as u64,
+                size: contents.len() as u64,
+                compression,
+                hash: hash(contents),
+            });
+            data.extend_from_slice(stored);
+        }
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&(entries.len() as u32).to_le_bytes())?;
+        out.write_all(&(HEADER_SIZE + data.len() as u64).to_le_bytes())?;
+        out.write_all(&data)?;
+        for ((path, _), entry) in self.files.iter().zip(&entries) {
+            out.write_all(&(path.len() as u16).to_le_bytes())?;
+            out.write_all(path.as_bytes())?;
+            out.write_all(&entry.offset.to_le_bytes())?;
+            out.write_all(&entry.stored_size.to_le_bytes())?;
+            out.write_all(&entry.size.to_le_bytes())?;
+            out.write_all(&[entry.compression as u8])?;
+            out.write_all(&entry.hash.to_le_bytes())?;
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packed(name: &str, compress: bool) -> std::path::PathBuf {
+        let mut writer = ArchiveWriter::new();
+        writer.add("levels/one.toml", b"name = \"one\"\n".repeat(20));
+        writer.add("noise.bin", vec![7, 1, 200]);
+        let path = std::env::temp_dir().join(format!(
+            "archive-{}-{}-{}.pak",
+            name,
+            std::process::id(),
+            compress
+        ));
+        let mut file = File::create(&path).unwrap();
+        let entries = writer.write(&mut file, compress).unwrap();
+        let expected = if compress {
+            Compression::Deflate
+        } else {
+            Compression::Stored
+        };
+        assert_eq!(entries[0].compression, expected);
+        // too small to get any smaller
+        assert_eq!(entries[1].compression, Compression::Stored);
+        path
+    }
+
+    #[test]
+    fn reads_back_packed_files() {
+        for &compress in &[false, true] {
+            let path = packed("round-trip", compress);
+            let archive = Archive::open(&path).unwrap();
+            assert_eq!(archive.entries().count(), 2);
+            assert_eq!(
+                archive.read("levels/one.toml").unwrap().unwrap(),
+                b"name = \"one\"\n".repeat(20)
+            );
+            assert_eq!(archive.read("noise.bin").unwrap().unwrap(), [7, 1, 200]);
+            assert!(archive.read("missing").is_none());
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn detects_corruption() {
+        let path = packed("corrupt", false);
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[HEADER_SIZE as usize] ^= 1;
+        std::fs::write(&path, &bytes).unwrap();
+        let archive = Archive::open(&path).unwrap();
+        assert!(archive.read("levels/one.toml").unwrap().is_err());
+        assert!(archive.read("noise.bin").unwrap().is_ok());
+        std::fs::write(&path, b"PK\x03\x04").unwrap();
+        assert!(Archive::open(&path).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rejects_entries_past_the_data() {
+        let path = packed("oversized", true);
+        let bytes = std::fs::read(&path).unwrap();
+        let index = u64_from_header(&bytes) as usize;
+        // the first entry's stored size, after its path length, path and offset
+        let stored_size = index + 2 + "levels/one.toml".len() + 8;
+        let mut oversized = bytes.clone();
+        oversized[stored_size..stored_size + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        std::fs::write(&path, &oversized).unwrap();
+        assert_eq!(Archive::open(&path).unwrap_err(), "corrupt index");
+        // claims to be smaller than it inflates to
+        let mut understated = bytes;
+        let size = stored_size + 8;
+        understated[size..size + 8].copy_from_slice(&4u64.to_le_bytes());
+        std::fs::write(&path, &understated).unwrap();
+        let archive = Archive::open(&path).unwrap();
+        assert!(archive.read("levels/one.toml").unwrap().is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    fn u64_from_header(bytes: &[u8]) -> u64 {
+        let mut array = [0; 8];
+        array.copy_from_slice(&bytes[12..20]);
+        u64::from_le_bytes(array)
+    }
+}
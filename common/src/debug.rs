@@ -0,0 +1,177 @@
+use crate::render::{Color, RenderCommand, RenderCommands};
+#[cfg(debug_assertions)]
+use crate::render::{Layer, Line, Space, Text};
+
+// line height of debug text, 16 pixels at the default zoom
+#[cfg(debug_assertions)]
+const TEXT_SIZE: f32 = 1.0 / 16.0;
+const CIRCLE_SEGMENTS: usize = 24;
+// arrow heads, as a fraction of the arrow length
+const HEAD_LENGTH: f32 = 0.2;
+
+// immediate mode shapes in world units, drawn over everything else. Primitives are collected
+// until `flush` hands them to the renderer, only while enabled. Release builds keep the API
+// but record nothing
+#[derive(Debug, Default)]
+pub struct DebugDraw {
+    pub enabled: bool,
+    commands: Vec<RenderCommand>,
+}
+
+impl DebugDraw {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+    }
+
+    #[cfg(debug_assertions)]
+    pub fn line(&mut self, from: [f32; 2], to: [f32; 2], color: Color) {
+        if self.enabled {
+            self.commands.push(RenderCommand::Line(Line {
+                from,
+                to,
+                color,
+                space: Space::World,
+                layer: Layer::Ui,
+                z: i16::MAX,
+            }));
+        }
+    }
+
+    #[cfg(not(debug_assertions))]
+    #[inline(always)]
+    pub fn line(&mut self, _from: [f32; 2], _to: [f32; 2], _color: Color) {}
+
+    // outline with `position` as the top left corner
+    pub fn rect(&mut self, position: [f32; 2], size: [f32; 2], color: Color) {
+        let [x, y] = position;
+        let [right, bottom] = [x + size[0], y + size[1]];
+        self.line([x, y], [right, y], color);
+        self.line([right, y], [right, bottom], color);
+        self.line([right, bottom], [x, bottom], color);
+        self.line([x, bottom], [x, y], color);
+    }
+
+    pub fn circle(&mut self, center: [f32; 2], radius: f32, color: Color) {
+        let point = |segment: usize| {
+            let angle = segment as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::PI * 2.0;
+            [
+                center[0] + radius * angle.cos(),
+                center[1] + radius * angle.sin(),
+            ]
+        };
+        for segment in 0..CIRCLE_SEGMENTS {
+            self.line(point(segment), point(segment + 1), color);
+        }
+    }
+
+    pub fn arrow(&mut self, from: [f32; 2], to: [f32; 2], color: Color) {
+        self.line(from, to, color);
+        let back = [
+            (from[0] - to[0]) * HEAD_LENGTH,
+            (from[1] - to[1]) * HEAD_LENGTH,
+        ];
+        // the shaft direction turned 30 degrees either way
+        let (sin, cos) = (0.5, 0.75f32.sqrt());
+        for sin in [sin, -sin].iter() {
+            let head = [
+                to[0] + back[0] * cos - back[1] * sin,
+                to[1] + back[0] * sin + back[1] * cos,
+            ];
+            self.line(to, head, color);
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    pub fn text(&mut self, content: impl Into<String>, position: [f32; 2], color: Color) {
+        if self.enabled {
+            self.commands.push(RenderCommand::Text(Text {
+                color,
+                space: Space::World,
+                z: i16::MAX,
+                ..Text::new(content, position, TEXT_SIZE)
+            }));
+        }
+    }
+
+    #[cfg(not(debug_assertions))]
+    #[inline(always)]
+    pub fn text(&mut self, _content: impl Into<String>, _position: [f32; 2], _color: Color) {}
+
+    // moves this frame's primitives to the end of `commands`
+    pub fn flush(&mut self, commands: &mut RenderCommands) {
+        for command in self.commands.drain(..) {
+            commands.push(command);
+        }
+    }
+}
+
+#[cfg(all(test, debug_assertions))]
+mod tests {
+    use super::*;
+
+    const GREEN: Color = [0.0, 1.0, 0.0, 1.0];
+
+    fn lines(commands: &RenderCommands) -> Vec<([f32; 2], [f32; 2])> {
+        commands
+            .iter()
+            .filter_map(|command| match command {
+                RenderCommand::Line(line) => Some((line.from, line.to)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn records_only_while_enabled() {
+        let mut debug = DebugDraw::new();
+        let mut commands = RenderCommands::new();
+        debug.rect([0.0, 0.0], [1.0, 1.0], GREEN);
+        debug.flush(&mut commands);
+        assert!(commands.commands.is_empty());
+        debug.toggle();
+        debug.rect([0.0, 0.0], [1.0, 2.0], GREEN);
+        debug.text("cell", [0.0, 0.0], GREEN);
+        debug.flush(&mut commands);
+        assert_eq!(
+            lines(&commands),
+            [
+                ([0.0, 0.0], [1.0, 0.0]),
+                ([1.0, 0.0], [1.0, 2.0]),
+                ([1.0, 2.0], [0.0, 2.0]),
+                ([0.0, 2.0], [0.0, 0.0]),
+            ]
+        );
+        assert_eq!(commands.commands.len(), 5);
+        // flushing starts the next frame empty
+        let mut next = RenderCommands::new();
+        debug.flush(&mut next);
+        assert!(next.commands.is_empty());
+    }
+
+    #[test]
+    fn closes_circles_and_heads_arrows() {
+        let mut debug = DebugDraw::new();
+        debug.toggle();
+        let mut commands = RenderCommands::new();
+        debug.circle([1.0, 1.0], 0.5, GREEN);
+        debug.flush(&mut commands);
+        let circle = lines(&commands);
+        assert_eq!(circle.len(), CIRCLE_SEGMENTS);
+        let (first, last) = (circle[0].0, circle[CIRCLE_SEGMENTS - 1].1);
+        assert!((first[0] - last[0]).abs() < 1e-5 && (first[1] - last[1]).abs() < 1e-5);
+
+        let mut commands = RenderCommands::new();
+        debug.arrow([0.0, 0.0], [1.0, 0.0], GREEN);
+        debug.flush(&mut commands);
+        let arrow = lines(&commands);
+        assert_eq!(arrow.len(), 3);
+        // both heads point back towards the tail, mirrored across the shaft
+        let (left, right) = (arrow[1].1, arrow[2].1);
+        assert!(left[0] < 1.0 && right[0] < 1.0);
+        assert!((left[1] + right[1]).abs() < 1e-5 && left[1] != 0.0);
+    }
+}
@@ -0,0 +1,43 @@
+// events the platform raises on its own, delivered through the window's event loop so that
+// they reach the queue in order with input
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlatformEvent {
+    // the renderer rebuilt its device, pipelines and swapchain after losing them
+    GraphicsRecovered(GraphicsLoss),
+    // asks the platform to change the window, which keeps the change for the next run
+    ChangeWindow(WindowChange),
+    // an asset couldn't be read or decoded, `path` is relative to the asset directory
+    AssetFailed { path: String, error: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GraphicsLoss {
+    Device,
+    Surface,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FullscreenMode {
+    Windowed,
+    // a window covering the monitor, switching to and from it is instant
+    Borderless,
+    // takes over the monitor in the video mode closest to the window size
+    Exclusive,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WindowChange {
+    Title(String),
+    // logical pixels
+    Size([u32; 2]),
+    // physical pixels, of the outer top left corner
+    Position([i32; 2]),
+    Resizable(bool),
+    MinSize(Option<[u32; 2]>),
+    MaxSize(Option<[u32; 2]>),
+    Fullscreen(FullscreenMode),
+    // by name, None for the primary monitor
+    Monitor(Option<String>),
+    CursorVisible(bool),
+    CursorGrab(bool),
+}
@@ -0,0 +1,465 @@
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread::JoinHandle;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+thread_local! {
+    // the pool and queue of the worker running on this thread
+    static WORKER: Cell<Option<(usize, usize)>> = const { Cell::new(None) };
+}
+
+// a job and what waits on it
+struct Node {
+    job: Mutex<Option<Job>>,
+    // unfinished dependencies, plus one while they are still being added
+    remaining: AtomicUsize,
+    state: Mutex<NodeState>,
+}
+
+#[derive(Default)]
+struct NodeState {
+    done: bool,
+    dependents: Vec<Arc<Node>>,
+}
+
+struct Shared {
+    // one per worker, the owner takes the newest job and thieves the oldest
+    queues: Vec<Mutex<VecDeque<Arc<Node>>>>,
+    // for jobs spawned from outside the pool
+    injector: Mutex<VecDeque<Arc<Node>>>,
+    // spawned and not finished, what the barrier waits for
+    unfinished: AtomicUsize,
+    stopping: AtomicBool,
+    // held while checking for work before going to sleep, so that no wakeup is missed
+    sleep: Mutex<()>,
+    wake: Condvar,
+}
+
+impl Shared {
+    fn id(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    fn worker(&self) -> Option<usize> {
+        WORKER
+            .with(|worker| worker.get())
+            .and_then(|(pool, index)| if pool == self.id() { Some(index) } else { None })
+    }
+
+    fn schedule(&self, node: Arc<Node>) {
+        match self.worker() {
+            Some(index) => self.queues[index].lock().unwrap().push_back(node),
+            None => self.injector.lock().unwrap().push_back(node),
+        }
+        let _sleep = self.sleep.lock().unwrap();
+        self.wake.notify_all();
+    }
+
+    fn find(&self, worker: Option<usize>) -> Option<Arc<Node>> {
+        if let Some(index) = worker {
+            if let Some(node) = self.queues[index].lock().unwrap().pop_back() {
+                return Some(node);
+            }
+        }
+        if let Some(node) = self.injector.lock().unwrap().pop_front() {
+            return Some(node);
+        }
+        let start = worker.map_or(0, |index| index + 1);
+        (0..self.queues.len())
+            .map(|offset| (start + offset) % self.queues.len())
+            .filter(|&victim| Some(victim) != worker)
+            .find_map(|victim| self.queues[victim].lock().unwrap().pop_front())
+    }
+
+    fn has_work(&self) -> bool {
+        !self.injector.lock().unwrap().is_empty()
+            || self
+                .queues
+                .iter()
+                .any(|queue| !queue.lock().unwrap().is_empty())
+    }
+
+    fn run(&self, node: Arc<Node>) {
+        if let Some(job) = node.job.lock().unwrap().take() {
+            // a panicking job counts as done, the panic is reported by the hook as usual
+            let _ = panic::catch_unwind(AssertUnwindSafe(job));
+        }
+        let dependents = {
+            let mut state = node.state.lock().unwrap();
+            state.done = true;
+            std::mem::take(&mut state.dependents)
+        };
+        for dependent in dependents {
+            if dependent.remaining.fetch_sub(1, Ordering::AcqRel) == 1 {
+                self.schedule(dependent);
+            }
+        }
+        self.unfinished.fetch_sub(1, Ordering::AcqRel);
+        let _sleep = self.sleep.lock().unwrap();
+        self.wake.notify_all();
+    }
+
+    // runs jobs until `done`, sleeping while there are none
+    fn help_until(&self, done: impl Fn() -> bool) {
+        let worker = self.worker();
+        loop {
+            if done() {
+                return;
+            }
+            if let Some(node) = self.find(worker) {
+                self.run(node);
+                continue;
+            }
+            let sleep = self.sleep.lock().unwrap();
+            if !done() && !self.has_work() {
+                drop(self.wake.wait(sleep).unwrap());
+            }
+        }
+    }
+}
+
+// finished once its job ran
+#[derive(Clone)]
+pub struct JobHandle(Arc<Node>);
+
+impl JobHandle {
+    pub fn is_done(&self) -> bool {
+        self.0.state.lock().unwrap().done
+    }
+}
+
+impl std::fmt::Debug for JobHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_tuple("JobHandle").field(&self.is_done()).finish()
+    }
+}
+
+// a work stealing thread pool. Jobs spawned by a job go to its worker's queue, idle workers
+// steal from the others. Waiting for a job runs other jobs in the meantime, so jobs can wait
+// for each other without running out of threads
+pub struct JobSystem {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl JobSystem {
+    pub fn new(threads: usize) -> Self {
+        let threads = threads.max(1);
+        let shared = Arc::new(Shared {
+            queues: (0..threads).map(|_| Mutex::default()).collect(),
+            injector: Mutex::default(),
+            unfinished: AtomicUsize::new(0),
+            stopping: AtomicBool::new(false),
+            sleep: Mutex::new(()),
+            wake: Condvar::new(),
+        });
+        let workers = (0..threads)
+            .map(|index| {
+                let shared = shared.clone();
+                std::thread::Builder::new()
+                    .name(format!("job worker {}", index))
+                    .spawn(move || work(&shared, index))
+                    .expect("Failed to start a job worker")
+            })
+            .collect();
+        Self { shared, workers }
+    }
+
+    // a worker for every core but the one the main loop runs on
+    pub fn with_available_threads() -> Self {
+        let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+        Self::new(cores.saturating_sub(1))
+    }
+
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    pub fn spawn(&self, job: impl FnOnce() + Send + 'static) -> JobHandle {
+        self.spawn_after(&[], job)
+    }
+
+    // runs `job` once every one of `dependencies` is done
+    pub fn spawn_after(
+        &self,
+        dependencies: &[JobHandle],
+        job: impl FnOnce() + Send + 'static,
+    ) -> JobHandle {
+        let node = Arc::new(Node {
+            job: Mutex::new(Some(Box::new(job))),
+            remaining: AtomicUsize::new(dependencies.len() + 1),
+            state: Mutex::default(),
+        });
+        self.shared.unfinished.fetch_add(1, Ordering::AcqRel);
+        let mut ready = 1;
+        for dependency in dependencies {
+            let mut state = dependency.0.state.lock().unwrap();
+            if state.done {
+                ready += 1;
+            } else {
+                state.dependents.push(node.clone());
+            }
+        }
+        if node.remaining.fetch_sub(ready, Ordering::AcqRel) == ready {
+            self.shared.schedule(node.clone());
+        }
+        JobHandle(node)
+    }
+
+    pub fn wait(&self, handle: &JobHandle) {
+        self.shared.help_until(|| handle.is_done());
+    }
+
+    // blocks until every job spawned so far, and every job those spawned, is done. Meant for
+    // the end of a frame, so that nothing of it is still running when the next one starts
+    pub fn barrier(&self) {
+        let shared = &self.shared;
+        shared.help_until(|| shared.unfinished.load(Ordering::Acquire) == 0);
+    }
+}
+
+impl Drop for JobSystem {
+    // finishes what was spawned before stopping the workers
+    fn drop(&mut self) {
+        self.barrier();
+        self.shared.stopping.store(true, Ordering::Release);
+        {
+            let _sleep = self.shared.sleep.lock().unwrap();
+            self.shared.wake.notify_all();
+        }
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl std::fmt::Debug for JobSystem {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("JobSystem")
+            .field("threads", &self.workers.len())
+            .field(
+                "unfinished",
+                &self.shared.unfinished.load(Ordering::Relaxed),
+            )
+            .finish()
+    }
+}
+
+fn work(shared: &Shared, index: usize) {
+    WORKER.with(|worker| worker.set(Some((shared.id(), index))));
+    shared.help_until(|| shared.stopping.load(Ordering::Acquire));
+}
+
+type SystemFn<C> = Arc<Mutex<dyn FnMut(&C, f32) + Send>>;
+
+struct System<C> {
+    name: &'static str,
+    reads: Vec<&'static str>,
+    writes: Vec<&'static str>,
+    run: SystemFn<C>,
+}
+
+impl<C> System<C> {
+    // whether the two can't run at the same time
+    fn conflicts(&self, other: &System<C>) -> bool {
+        let writes = |system: &System<C>, resource| system.writes.contains(resource);
+        self.writes
+            .iter()
+            .any(|resource| writes(other, resource) || other.reads.contains(resource))
+            || self.reads.iter().any(|resource| writes(other, resource))
+    }
+}
+
+// systems that declare which resources they read and write. Each one runs after the systems
+// added before it that touch what it writes or write what it reads, everything else runs in
+// parallel. The resources themselves live in the context every run is handed
+pub struct Schedule<C> {
+    systems: Vec<System<C>>,
+}
+
+impl<C> Default for Schedule<C> {
+    fn default() -> Self {
+        Self {
+            systems: Vec::new(),
+        }
+    }
+}
+
+impl<C: Send + Sync + 'static> Schedule<C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(
+        &mut self,
+        name: &'static str,
+        reads: &[&'static str],
+        writes: &[&'static str],
+        system: impl FnMut(&C, f32) + Send + 'static,
+    ) {
+        self.systems.push(System {
+            name,
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+            run: Arc::new(Mutex::new(system)),
+        });
+    }
+
+    // for each system, the earlier ones it waits for
+    fn dependencies(&self) -> Vec<Vec<usize>> {
+        self.systems
+            .iter()
+            .enumerate()
+            .map(|(index, system)| {
+                (0..index)
+                    .filter(|&earlier| system.conflicts(&self.systems[earlier]))
+                    .collect()
+            })
+            .collect()
+    }
+
+    // runs every system once and returns when they are all done, by then none of them holds
+    // on to `context` any more
+    pub fn run(&self, jobs: &JobSystem, context: &Arc<C>, dt: f32) {
+        let mut handles: Vec<JobHandle> = Vec::with_capacity(self.systems.len());
+        for (system, dependencies) in self.systems.iter().zip(self.dependencies()) {
+            let dependencies: Vec<JobHandle> = dependencies
+                .into_iter()
+                .map(|index| handles[index].clone())
+                .collect();
+            let run = system.run.clone();
+            let context = context.clone();
+            handles.push(jobs.spawn_after(&dependencies, move || {
+                // a system that panicked before leaves its state as it was
+                (*run.lock().unwrap_or_else(PoisonError::into_inner))(&context, dt)
+            }));
+        }
+        for handle in &handles {
+            jobs.wait(handle);
+        }
+    }
+}
+
+impl<C> std::fmt::Debug for Schedule<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.systems.iter().map(|system| system.name))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn runs_jobs_after_their_dependencies() {
+        let jobs = JobSystem::new(4);
+        let (sender, receiver) = mpsc::channel();
+        let first = {
+            let sender = sender.clone();
+            jobs.spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(20));
+                sender.send("first").unwrap();
+            })
+        };
+        let second = {
+            let sender = sender.clone();
+            jobs.spawn_after(std::slice::from_ref(&first), move || {
+                sender.send("second").unwrap()
+            })
+        };
+        jobs.spawn_after(&[first, second.clone()], move || {
+            sender.send("third").unwrap()
+        });
+        jobs.barrier();
+        assert!(second.is_done());
+        let order: Vec<_> = receiver.try_iter().collect();
+        assert_eq!(order, ["first", "second", "third"]);
+    }
+
+    #[test]
+    fn waits_for_jobs_spawned_by_jobs() {
+        let jobs = Arc::new(JobSystem::new(2));
+        let count = Arc::new(AtomicUsize::new(0));
+        for _ in 0..8 {
+            let (inner, count) = (jobs.clone(), count.clone());
+            jobs.spawn(move || {
+                let handles: Vec<_> = (0..8)
+                    .map(|_| {
+                        let count = count.clone();
+                        inner.spawn(move || {
+                            count.fetch_add(1, Ordering::SeqCst);
+                        })
+                    })
+                    .collect();
+                // waiting from inside a job runs other jobs instead of blocking a worker
+                for handle in &handles {
+                    inner.wait(handle);
+                }
+            });
+        }
+        jobs.barrier();
+        assert_eq!(count.load(Ordering::SeqCst), 64);
+    }
+
+    #[test]
+    fn survives_panicking_jobs() {
+        let jobs = JobSystem::new(1);
+        let failed = jobs.spawn(|| panic!("job failed"));
+        let after = jobs.spawn_after(&[failed], || {});
+        jobs.wait(&after);
+        assert!(after.is_done());
+    }
+
+    #[test]
+    fn orders_conflicting_systems() {
+        let mut schedule = Schedule::new();
+        let noop = |_: &(), _| {};
+        schedule.add("input", &[], &["input"], noop);
+        schedule.add("movement", &["input"], &["positions"], noop);
+        schedule.add("sound", &["input"], &["audio"], noop);
+        schedule.add("camera", &["positions"], &["camera"], noop);
+        schedule.add("ai", &["positions"], &[], noop);
+        assert_eq!(
+            schedule.dependencies(),
+            [vec![], vec![0], vec![0], vec![1], vec![1]]
+        );
+
+        let mut schedule = Schedule::new();
+        for name in &["a", "b", "c"] {
+            schedule.add(name, &[], &["shared"], move |log: &Mutex<Vec<_>>, dt| {
+                log.lock().unwrap().push((*name, dt))
+            });
+        }
+        let log = Arc::new(Mutex::new(Vec::new()));
+        schedule.run(&JobSystem::new(3), &log, 0.5);
+        assert_eq!(*log.lock().unwrap(), [("a", 0.5), ("b", 0.5), ("c", 0.5)]);
+    }
+
+    #[test]
+    fn runs_independent_systems_in_parallel() {
+        // each waits a while for the other to start, which only a parallel run lets it see
+        let mut schedule = Schedule::new();
+        for name in &["reader", "other reader"] {
+            schedule.add(name, &["shared"], &[], |started: &AtomicUsize, _| {
+                started.fetch_add(1, Ordering::SeqCst);
+                let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+                while started.load(Ordering::SeqCst) < 2 && std::time::Instant::now() < deadline {
+                    std::thread::yield_now();
+                }
+            });
+        }
+        assert_eq!(schedule.dependencies(), [vec![], vec![]]);
+        let started = Arc::new(AtomicUsize::new(0));
+        let begin = std::time::Instant::now();
+        schedule.run(&JobSystem::new(2), &started, 0.0);
+        assert_eq!(started.load(Ordering::SeqCst), 2);
+        assert!(begin.elapsed() < std::time::Duration::from_secs(5));
+    }
+}
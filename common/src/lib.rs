@@ -1,3 +1,13 @@
+pub mod archive;
+pub mod debug;
+pub mod event;
+pub mod jobs;
+pub mod plugin;
+pub mod random;
+pub mod render;
+pub mod settings;
+pub mod ui;
+
 // push everywhere except for continious events
 pub type Time = u128;
 
@@ -0,0 +1,532 @@
+use crate::render::{
+    Color, Layer, Line, RenderCommand, RenderCommands, Shape, ShapeKind, Space, Text,
+};
+use std::collections::{HashMap, HashSet};
+use std::ffi::{c_void, CStr};
+use std::os::raw::c_char;
+
+// everything below is the plugin ABI. Only C types cross it, so plugins don't have to be built
+// with the same compiler, or in rust at all. Additions bump the minor version, any other change
+// the major one
+pub const ABI_VERSION: AbiVersion = AbiVersion { major: 1, minor: 0 };
+
+// every plugin exports `extern "C" fn game_plugin() -> *const PluginDescriptor` under it,
+// the descriptor has to live as long as the library is loaded
+pub const PLUGIN_SYMBOL: &[u8] = b"game_plugin\0";
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AbiVersion {
+    pub major: u16,
+    pub minor: u16,
+}
+
+impl AbiVersion {
+    // a plugin may be built against an older minor version than the host, but not a newer one
+    #[allow(clippy::absurd_extreme_comparisons)]
+    pub fn supported(&self) -> bool {
+        self.major == ABI_VERSION.major && self.minor <= ABI_VERSION.minor
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+}
+
+impl Version {
+    // same major version and at least as new
+    pub fn satisfies(&self, required: Version) -> bool {
+        self.major == required.major && *self >= required
+    }
+}
+
+#[repr(C)]
+pub struct PluginDependency {
+    pub name: *const c_char,
+    pub min_version: Version,
+}
+
+#[repr(C)]
+pub struct PluginDescriptor {
+    // first, so that it can be checked before the rest is trusted
+    pub abi_version: AbiVersion,
+    pub name: *const c_char,
+    pub version: Version,
+    // loaded before this plugin, which isn't loaded without them
+    pub dependencies: *const PluginDependency,
+    pub dependency_count: usize,
+    // called once, in load order. Returning false leaves the plugin and its dependents out
+    pub register: unsafe extern "C" fn(registrar: *const Registrar) -> bool,
+}
+
+// `user` is handed back to the callback as it was registered
+pub type SystemFn = unsafe extern "C" fn(user: *mut c_void, world: *mut PluginWorld, dt: f32);
+pub type EventHandlerFn = unsafe extern "C" fn(user: *mut c_void, event: *const PluginEvent);
+pub type RenderHookFn = unsafe extern "C" fn(user: *mut c_void, canvas: *const Canvas);
+
+// what a plugin registers its callbacks with, only valid during `register`
+#[repr(C)]
+pub struct Registrar {
+    pub host: *mut c_void,
+    // run once a frame, in load order, on whichever thread of the job system is free
+    pub add_system: unsafe extern "C" fn(host: *mut c_void, system: SystemFn, user: *mut c_void),
+    pub add_event_handler:
+        unsafe extern "C" fn(host: *mut c_void, handler: EventHandlerFn, user: *mut c_void),
+    // draws on top of the world
+    pub add_render_hook:
+        unsafe extern "C" fn(host: *mut c_void, hook: RenderHookFn, user: *mut c_void),
+}
+
+// the parts of the world plugins may change
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PluginWorld {
+    pub player: [f32; 2],
+}
+
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PluginEventKind {
+    KeyPressed = 0,
+    KeyReleased = 1,
+    PointerMoved = 2,
+    PointerPressed = 3,
+    PointerReleased = 4,
+    Resized = 5,
+}
+
+// only the fields of its kind are set, the rest are zero
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PluginEvent {
+    pub kind: PluginEventKind,
+    // the key's scancode, which doesn't depend on the keyboard layout
+    pub scancode: u32,
+    // pixels from the top left corner of the window
+    pub pointer: [f32; 2],
+    pub size: [u32; 2],
+}
+
+// triangles in world space
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PluginShape {
+    pub position: [f32; 2],
+    pub scale: [f32; 2],
+    pub color: Color,
+}
+
+// world space
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PluginLine {
+    pub from: [f32; 2],
+    pub to: [f32; 2],
+    pub color: Color,
+}
+
+// screen space
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PluginText {
+    // utf-8, nul terminated
+    pub content: *const c_char,
+    pub position: [f32; 2],
+    pub size: f32,
+    pub color: Color,
+}
+
+// what render hooks draw with, only valid during the hook
+#[repr(C)]
+pub struct Canvas {
+    pub host: *mut c_void,
+    pub shape: unsafe extern "C" fn(host: *mut c_void, shape: *const PluginShape),
+    pub line: unsafe extern "C" fn(host: *mut c_void, line: *const PluginLine),
+    pub text: unsafe extern "C" fn(host: *mut c_void, text: *const PluginText),
+}
+
+// a descriptor read into rust types
+#[derive(Debug, Clone, PartialEq)]
+pub struct PluginInfo {
+    pub name: String,
+    pub version: Version,
+    pub dependencies: Vec<(String, Version)>,
+}
+
+impl PluginInfo {
+    // the ABI version is checked before anything else is read. The pointers in `descriptor` have
+    // to be valid once it is
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn read(descriptor: &PluginDescriptor) -> Result<Self, String> {
+        let abi = descriptor.abi_version;
+        if !abi.supported() {
+            return Err(format!(
+                "built for ABI {}.{}, the game supports {}.0 to {}.{}",
+                abi.major, abi.minor, ABI_VERSION.major, ABI_VERSION.major, ABI_VERSION.minor
+            ));
+        }
+        let name = string(descriptor.name).ok_or("has no name")?;
+        let dependencies = if descriptor.dependency_count == 0 {
+            &[][..]
+        } else {
+            std::slice::from_raw_parts(descriptor.dependencies, descriptor.dependency_count)
+        };
+        let dependencies = dependencies
+            .iter()
+            .map(|dependency| {
+                string(dependency.name)
+                    .map(|dependency_name| (dependency_name, dependency.min_version))
+                    .ok_or_else(|| format!("{} has a dependency without a name", name))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            name,
+            version: descriptor.version,
+            dependencies,
+        })
+    }
+}
+
+unsafe fn string(pointer: *const c_char) -> Option<String> {
+    if pointer.is_null() {
+        return None;
+    }
+    Some(CStr::from_ptr(pointer).to_string_lossy().into_owned())
+}
+
+// indices into `plugins` with every plugin after its dependencies, plugins that don't depend on
+// each other go by name. Plugins whose dependencies are missing, too old or circular are left
+// out, with the reason
+pub fn load_order(plugins: &[PluginInfo]) -> (Vec<usize>, Vec<String>) {
+    let mut errors = Vec::new();
+    let mut by_name: HashMap<&str, usize> = HashMap::new();
+    for (index, plugin) in plugins.iter().enumerate() {
+        if by_name.insert(&plugin.name, index).is_some() {
+            errors.push(format!(
+                "{} is loaded twice, keeping the last one",
+                plugin.name
+            ));
+        }
+    }
+    let mut names: Vec<&str> = by_name.keys().copied().collect();
+    names.sort_unstable();
+    let mut order = Vec::new();
+    let mut done: HashSet<&str> = HashSet::new();
+    let mut refused: HashSet<&str> = HashSet::new();
+    // repeatedly takes the first plugin by name whose dependencies are all loaded
+    loop {
+        let mut progressed = false;
+        for &name in &names {
+            if done.contains(name) || refused.contains(name) {
+                continue;
+            }
+            let plugin = &plugins[by_name[name]];
+            let mut ready = true;
+            for (dependency, required) in &plugin.dependencies {
+                let problem = match by_name.get(dependency.as_str()) {
+                    None => Some(format!("{} needs {}, which isn't there", name, dependency)),
+                    Some(_) if refused.contains(dependency.as_str()) => Some(format!(
+                        "{} needs {}, which wasn't loaded",
+                        name, dependency
+                    )),
+                    Some(&index) if !plugins[index].version.satisfies(*required) => {
+                        let found = plugins[index].version;
+                        Some(format!(
+                            "{} needs {} {}.{}.{}, found {}.{}.{}",
+                            name,
+                            dependency,
+                            required.major,
+                            required.minor,
+                            required.patch,
+                            found.major,
+                            found.minor,
+                            found.patch
+                        ))
+                    }
+                    Some(_) => {
+                        ready &= done.contains(dependency.as_str());
+                        None
+                    }
+                };
+                if let Some(problem) = problem {
+                    errors.push(problem);
+                    refused.insert(name);
+                    ready = false;
+                    break;
+                }
+            }
+            if ready {
+                done.insert(name);
+                order.push(by_name[name]);
+                progressed = true;
+                // restart so that plugins keep going by name as far as possible
+                break;
+            }
+            progressed |= refused.contains(name);
+        }
+        if !progressed {
+            break;
+        }
+    }
+    for name in names {
+        if !done.contains(name) && !refused.contains(name) {
+            errors.push(format!("{} is part of a dependency cycle", name));
+        }
+    }
+    (order, errors)
+}
+
+// a registered system. The ABI lets systems run on any thread, though only one at a time
+#[derive(Debug, Clone, Copy)]
+pub struct PluginSystem {
+    system: SystemFn,
+    user: *mut c_void,
+}
+
+unsafe impl Send for PluginSystem {}
+
+impl PluginSystem {
+    pub fn run(&self, world: &mut PluginWorld, dt: f32) {
+        unsafe { (self.system)(self.user, world, dt) }
+    }
+}
+
+// the callbacks plugins registered
+#[derive(Debug, Default)]
+pub struct PluginHost {
+    systems: Vec<PluginSystem>,
+    event_handlers: Vec<(EventHandlerFn, *mut c_void)>,
+    render_hooks: Vec<(RenderHookFn, *mut c_void)>,
+}
+
+impl PluginHost {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // false when the plugin refused to load, whatever it registered until then stays. The
+    // descriptor has to come from `PluginInfo::read` and its callbacks outlive the host
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn register(&mut self, descriptor: &PluginDescriptor) -> bool {
+        let registrar = Registrar {
+            host: self as *mut Self as *mut c_void,
+            add_system,
+            add_event_handler,
+            add_render_hook,
+        };
+        (descriptor.register)(&registrar)
+    }
+
+    // in load order
+    pub fn systems(&self) -> impl Iterator<Item = PluginSystem> + '_ {
+        self.systems.iter().copied()
+    }
+
+    pub fn handle_event(&self, event: &PluginEvent) {
+        for (handler, user) in &self.event_handlers {
+            unsafe { handler(*user, event) }
+        }
+    }
+
+    // appends what the hooks draw to `commands`
+    pub fn render(&self, commands: &mut RenderCommands) {
+        let canvas = Canvas {
+            host: commands as *mut RenderCommands as *mut c_void,
+            shape: draw_shape,
+            line: draw_line,
+            text: draw_text,
+        };
+        for (hook, user) in &self.render_hooks {
+            unsafe { hook(*user, &canvas) }
+        }
+    }
+}
+
+unsafe extern "C" fn add_system(host: *mut c_void, system: SystemFn, user: *mut c_void) {
+    (*(host as *mut PluginHost))
+        .systems
+        .push(PluginSystem { system, user });
+}
+
+unsafe extern "C" fn add_event_handler(
+    host: *mut c_void,
+    handler: EventHandlerFn,
+    user: *mut c_void,
+) {
+    (*(host as *mut PluginHost))
+        .event_handlers
+        .push((handler, user));
+}
+
+unsafe extern "C" fn add_render_hook(host: *mut c_void, hook: RenderHookFn, user: *mut c_void) {
+    (*(host as *mut PluginHost)).render_hooks.push((hook, user));
+}
+
+unsafe extern "C" fn draw_shape(host: *mut c_void, shape: *const PluginShape) {
+    let shape = &*shape;
+    (*(host as *mut RenderCommands)).push(RenderCommand::Shape(Shape {
+        kind: ShapeKind::Triangle,
+        position: shape.position,
+        scale: shape.scale,
+        color: shape.color,
+        layer: Layer::Effects,
+        z: 0,
+    }));
+}
+
+unsafe extern "C" fn draw_line(host: *mut c_void, line: *const PluginLine) {
+    let line = &*line;
+    (*(host as *mut RenderCommands)).push(RenderCommand::Line(Line {
+        from: line.from,
+        to: line.to,
+        color: line.color,
+        space: Space::World,
+        layer: Layer::Effects,
+        z: 0,
+    }));
+}
+
+unsafe extern "C" fn draw_text(host: *mut c_void, text: *const PluginText) {
+    let text = &*text;
+    if let Some(content) = string(text.content) {
+        (*(host as *mut RenderCommands)).push(RenderCommand::Text(Text {
+            color: text.color,
+            ..Text::new(content, text.position, text.size)
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plugin(name: &str, major: u16, dependencies: &[(&str, u16)]) -> PluginInfo {
+        let version = |major| Version {
+            major,
+            minor: 0,
+            patch: 0,
+        };
+        PluginInfo {
+            name: name.to_string(),
+            version: version(major),
+            dependencies: dependencies
+                .iter()
+                .map(|(name, major)| (name.to_string(), version(*major)))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn orders_plugins_after_their_dependencies() {
+        let plugins = [
+            plugin("b", 1, &[("c", 1)]),
+            plugin("a", 1, &[]),
+            plugin("c", 1, &[("a", 1)]),
+            plugin("d", 1, &[]),
+        ];
+        let (order, errors) = load_order(&plugins);
+        let names: Vec<&str> = order.iter().map(|&i| plugins[i].name.as_str()).collect();
+        assert_eq!(names, ["a", "c", "b", "d"]);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn leaves_out_unmet_dependencies() {
+        let plugins = [
+            plugin("old", 1, &[]),
+            plugin("needs-newer", 1, &[("old", 2)]),
+            plugin("needs-missing", 1, &[("missing", 1)]),
+            plugin("needs-refused", 1, &[("needs-missing", 1)]),
+            plugin("x", 1, &[("y", 1)]),
+            plugin("y", 1, &[("x", 1)]),
+        ];
+        let (order, errors) = load_order(&plugins);
+        assert_eq!(order, [0]);
+        assert_eq!(errors.len(), 5, "{:?}", errors);
+        assert!(errors.iter().any(|error| error.contains("found 1.0.0")));
+        assert!(errors.iter().any(|error| error.contains("cycle")));
+    }
+
+    #[test]
+    fn refuses_other_abi_versions() {
+        unsafe extern "C" fn register(_: *const Registrar) -> bool {
+            true
+        }
+        let mut descriptor = PluginDescriptor {
+            abi_version: AbiVersion {
+                major: ABI_VERSION.major + 1,
+                minor: 0,
+            },
+            name: b"future\0".as_ptr() as *const c_char,
+            version: Version {
+                major: 1,
+                minor: 0,
+                patch: 0,
+            },
+            dependencies: std::ptr::null(),
+            dependency_count: 0,
+            register,
+        };
+        assert!(unsafe { PluginInfo::read(&descriptor) }.is_err());
+        descriptor.abi_version = ABI_VERSION;
+        assert_eq!(
+            unsafe { PluginInfo::read(&descriptor) }.unwrap().name,
+            "future"
+        );
+    }
+
+    #[test]
+    fn calls_registered_callbacks() {
+        unsafe extern "C" fn system(user: *mut c_void, world: *mut PluginWorld, dt: f32) {
+            *(user as *mut u32) += 1;
+            (*world).player[0] += dt;
+        }
+        unsafe extern "C" fn hook(_: *mut c_void, canvas: *const Canvas) {
+            let canvas = &*canvas;
+            let text = PluginText {
+                content: b"hello\0".as_ptr() as *const c_char,
+                position: [1.0, 2.0],
+                size: 16.0,
+                color: [1.0; 4],
+            };
+            (canvas.text)(canvas.host, &text);
+        }
+        unsafe extern "C" fn register(registrar: *const Registrar) -> bool {
+            let registrar = &*registrar;
+            (registrar.add_system)(registrar.host, system, COUNT.as_ptr() as *mut c_void);
+            (registrar.add_render_hook)(registrar.host, hook, std::ptr::null_mut());
+            true
+        }
+        static COUNT: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+        let descriptor = PluginDescriptor {
+            abi_version: ABI_VERSION,
+            name: b"test\0".as_ptr() as *const c_char,
+            version: Version {
+                major: 1,
+                minor: 0,
+                patch: 0,
+            },
+            dependencies: std::ptr::null(),
+            dependency_count: 0,
+            register,
+        };
+        let mut host = PluginHost::new();
+        assert!(unsafe { host.register(&descriptor) });
+        let mut world = PluginWorld { player: [0.0, 0.0] };
+        for system in host.systems() {
+            system.run(&mut world, 0.5);
+        }
+        assert_eq!(world.player, [0.5, 0.0]);
+        assert_eq!(COUNT.load(std::sync::atomic::Ordering::SeqCst), 1);
+        let mut commands = RenderCommands::new();
+        host.render(&mut commands);
+        match &commands.commands[..] {
+            [RenderCommand::Text(text)] => assert_eq!(text.content, "hello"),
+            commands => panic!("{:?}", commands),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+// splitmix64, small and fast, and any state is a good one. The same seed always gives the same
+// numbers, which is what replays rely on. Not for anything that needs to be unpredictable
+#[derive(Debug, Clone, PartialEq)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    // passed to `new` this continues where the sequence left off
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    // in [min, max)
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeats_for_the_same_seed() {
+        let mut first = Random::new(7);
+        let numbers: Vec<u64> = (0..4).map(|_| first.next_u64()).collect();
+        let mut second = Random::new(7);
+        assert!(numbers.iter().all(|&number| number == second.next_u64()));
+        let mut resumed = Random::new(first.state());
+        assert_eq!(resumed.next_u64(), first.next_u64());
+        assert_ne!(Random::new(8).next_u64(), Random::new(7).next_u64());
+        for _ in 0..1000 {
+            let value = first.range(-1.0, 1.0);
+            assert!((-1.0..1.0).contains(&value));
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::fmt::Write;
+
+pub type Color = [f32; 4];
+
+// draw layers from back to front
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Layer {
+    Background,
+    Entities,
+    Effects,
+    Ui,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Space {
+    // pixels, origin in the top left corner of the window
+    Screen,
+    // world units
+    World,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Text {
+    pub content: String,
+    pub position: [f32; 2],
+    // line height, in pixels or world units depending on `space`
+    pub size: f32,
+    pub color: Color,
+    pub align: Align,
+    pub max_width: Option<f32>,
+    pub space: Space,
+    pub layer: Layer,
+    // order within the layer, higher is drawn on top
+    pub z: i16,
+}
+
+impl Text {
+    pub fn new(content: impl Into<String>, position: [f32; 2], size: f32) -> Self {
+        Self {
+            content: content.into(),
+            position,
+            size,
+            color: [1.0, 1.0, 1.0, 1.0],
+            align: Align::Left,
+            max_width: None,
+            space: Space::Screen,
+            layer: Layer::Ui,
+            z: 0,
+        }
+    }
+}
+
+// solid rectangle, `position` is its top left corner
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sprite {
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    pub color: Color,
+    pub space: Space,
+    pub layer: Layer,
+    pub z: i16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShapeKind {
+    Triangle,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shape {
+    pub kind: ShapeKind,
+    pub position: [f32; 2],
+    pub scale: [f32; 2],
+    pub color: Color,
+    pub layer: Layer,
+    pub z: i16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub from: [f32; 2],
+    pub to: [f32; 2],
+    pub color: Color,
+    pub space: Space,
+    pub layer: Layer,
+    pub z: i16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraView {
+    // the renderer eases the camera towards this point
+    pub focus: [f32; 2],
+    pub zoom: f32,
+    pub rotation: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RenderCommand {
+    Clear(Color),
+    Camera(CameraView),
+    Shape(Shape),
+    Sprite(Sprite),
+    Line(Line),
+    Text(Text),
+}
+
+// one frame's worth of drawing, in the order it was emitted
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RenderCommands {
+    pub commands: Vec<RenderCommand>,
+}
+
+impl RenderCommands {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, command: RenderCommand) {
+        self.commands.push(command);
+    }
+
+    pub fn clear(&mut self) {
+        self.commands.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &RenderCommand> {
+        self.commands.iter()
+    }
+
+    // one command per line, stable enough to compare against in tests
+    pub fn dump(&self) -> String {
+        let mut dump = String::new();
+        for command in &self.commands {
+            writeln!(dump, "{:?}", command).unwrap();
+        }
+        dump
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dumps_one_command_per_line() {
+        let mut commands = RenderCommands::new();
+        commands.push(RenderCommand::Clear([0.0, 0.0, 0.0, 1.0]));
+        commands.push(RenderCommand::Text(Text::new("hi", [1.0, 2.0], 16.0)));
+        let dump = commands.dump();
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], "Clear([0.0, 0.0, 0.0, 1.0])");
+        assert!(lines[1].starts_with("Text(Text { content: \"hi\""));
+    }
+}
@@ -0,0 +1,251 @@
+use std::convert::TryFrom;
+use std::ops::RangeInclusive;
+use toml::value::{Table, Value};
+
+// settings are named `<section>.<key>`, as in `[section]` and `key = value` in the settings file.
+// Every layer is a list of overrides applied on top of the one before, the first is the defaults
+
+// one value for one setting, and where it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Override {
+    pub key: String,
+    pub value: Value,
+    // a file, an environment variable or a command line flag
+    pub origin: String,
+}
+
+impl Override {
+    pub fn new(key: impl Into<String>, value: impl Into<Value>, origin: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            value: value.into(),
+            origin: origin.into(),
+        }
+    }
+
+    // the section and the key in it
+    pub fn split(&self) -> Option<(&str, &str)> {
+        let at = self.key.find('.')?;
+        Some((&self.key[..at], &self.key[at + 1..]))
+    }
+
+    // `reason` follows the key, "can't be 3" or "isn't a setting"
+    pub fn error(&self, reason: &str) -> String {
+        format!("{}: `{}` {}", self.origin, self.key, reason)
+    }
+}
+
+// every key of every section, values that aren't in a section end up without one
+pub fn file_overrides(table: &Table, origin: &str) -> Vec<Override> {
+    let mut overrides = vec![];
+    for (section, values) in table {
+        match values {
+            Value::Table(values) => {
+                for (key, value) in values {
+                    let key = format!("{}.{}", section, key);
+                    overrides.push(Override::new(key, value.clone(), origin));
+                }
+            }
+            value => overrides.push(Override::new(section.clone(), value.clone(), origin)),
+        }
+    }
+    overrides
+}
+
+// `<prefix>GRAPHICS_MAX_FPS=30` sets `graphics.max_fps`, section names have no underscores
+pub fn env_overrides(
+    vars: impl IntoIterator<Item = (String, String)>,
+    prefix: &str,
+) -> Vec<Override> {
+    let mut overrides: Vec<Override> = vars
+        .into_iter()
+        .filter_map(|(name, text)| {
+            let setting = name.strip_prefix(prefix)?.to_lowercase();
+            let at = setting.find('_')?;
+            let key = format!("{}.{}", &setting[..at], &setting[at + 1..]);
+            Some(Override::new(key, parse_value(&text), name))
+        })
+        .collect();
+    // the environment has no order, this keeps errors in the same one
+    overrides.sort_by(|a, b| a.key.cmp(&b.key));
+    overrides
+}
+
+// `file` with every setting that's different in `current` than it was in `loaded`, so that what
+// only the environment or the command line set isn't saved. All three are tables of sections
+pub fn changed_since(file: &Table, loaded: &Table, current: &Table) -> Table {
+    let mut file = file.clone();
+    let sections = |table: &Table| -> Vec<String> { table.keys().cloned().collect() };
+    let mut names = sections(loaded);
+    names.extend(sections(current));
+    names.sort();
+    names.dedup();
+    for name in names {
+        let section = |table: &Table| table.get(&name).and_then(Value::as_table).cloned();
+        let (before, after) = (
+            section(loaded).unwrap_or_default(),
+            section(current).unwrap_or_default(),
+        );
+        let mut keys = sections(&before);
+        keys.extend(sections(&after));
+        for key in keys {
+            if before.get(&key) == after.get(&key) {
+                continue;
+            }
+            let saved = file
+                .entry(name.clone())
+                .or_insert_with(|| Value::Table(Table::new()));
+            if let Value::Table(saved) = saved {
+                match after.get(&key) {
+                    Some(value) => saved.insert(key, value.clone()),
+                    // set before, None now
+                    None => saved.remove(&key),
+                };
+            }
+        }
+    }
+    file
+}
+
+// anything toml reads as a value, otherwise the text as a string, so that `W` needs no quotes
+pub fn parse_value(text: &str) -> Value {
+    format!("value = {}", text)
+        .parse::<Value>()
+        .ok()
+        .and_then(|table| table.get("value").cloned())
+        .unwrap_or_else(|| Value::String(text.to_string()))
+}
+
+// as it's printed, 0.8 rather than the 0.800000011920929 a cast gives
+pub fn float(number: f32) -> Value {
+    Value::Float(number.to_string().parse().unwrap_or(number as f64))
+}
+
+pub fn invalid(value: &Value) -> String {
+    format!("can't be {}", value)
+}
+
+pub fn boolean(value: &Value) -> Result<bool, String> {
+    value.as_bool().ok_or_else(|| invalid(value))
+}
+
+pub fn string(value: &Value) -> Result<String, String> {
+    value
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| invalid(value))
+}
+
+// integers are numbers too
+pub fn number(value: &Value) -> Result<f32, String> {
+    match value {
+        Value::Float(number) => Ok(*number as f32),
+        Value::Integer(number) => Ok(*number as f32),
+        value => Err(invalid(value)),
+    }
+}
+
+pub fn positive(value: &Value) -> Result<f32, String> {
+    match number(value)? {
+        number if number > 0.0 && number.is_finite() => Ok(number),
+        _ => Err(format!("{}, it has to be more than 0", invalid(value))),
+    }
+}
+
+// what a tick or frame limiter may be asked for per second, a rate near 0 would make a period
+// too long to be a `Duration`
+pub const RATES: RangeInclusive<f32> = 0.01..=10_000.0;
+
+pub fn rate(value: &Value) -> Result<f32, String> {
+    match number(value)? {
+        number if RATES.contains(&number) => Ok(number),
+        _ => Err(format!(
+            "{}, it has to be from {} to {}",
+            invalid(value),
+            RATES.start(),
+            RATES.end()
+        )),
+    }
+}
+
+// in [0, 1]
+pub fn fraction(value: &Value) -> Result<f32, String> {
+    match number(value)? {
+        number if (0.0..=1.0).contains(&number) => Ok(number),
+        _ => Err(format!("{}, it has to be from 0 to 1", invalid(value))),
+    }
+}
+
+pub fn integer<T: TryFrom<i64>>(value: &Value, min: i64, max: i64) -> Result<T, String> {
+    value
+        .as_integer()
+        .filter(|integer| (min..=max).contains(integer))
+        .and_then(|integer| T::try_from(integer).ok())
+        .ok_or_else(|| format!("{}, it has to be from {} to {}", invalid(value), min, max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_layers() {
+        let table: Table = toml::from_str("stray = 1\n[window]\nsize = [640, 480]\n").unwrap();
+        let overrides = file_overrides(&table, "settings.toml");
+        assert_eq!(overrides[0].key, "stray");
+        assert_eq!(overrides[0].split(), None);
+        assert_eq!(overrides[1].split(), Some(("window", "size")));
+        let vars = vec![
+            ("GAME_GRAPHICS_MAX_FPS".to_string(), "30".to_string()),
+            ("GAME_INPUT_UP".to_string(), "W".to_string()),
+            ("HOME".to_string(), "/root".to_string()),
+        ];
+        let overrides = env_overrides(vars, "GAME_");
+        assert_eq!(overrides.len(), 2);
+        assert_eq!(overrides[0].key, "graphics.max_fps");
+        assert_eq!(overrides[0].value, Value::Integer(30));
+        assert_eq!(overrides[1].value, Value::String("W".to_string()));
+        assert_eq!(
+            overrides[1].error("isn't a setting"),
+            "GAME_INPUT_UP: `input.up` isn't a setting"
+        );
+    }
+
+    #[test]
+    fn saves_only_changes() {
+        let table = |text: &str| toml::from_str::<Table>(text).unwrap();
+        let file = table("[window]\ntitle = \"mine\"\nposition = [1, 2]\n");
+        // the command line made it 800 wide
+        let loaded = table("[window]\ntitle = \"mine\"\nsize = [800, 600]\nposition = [1, 2]\n");
+        let current =
+            table("[window]\ntitle = \"mine\"\nsize = [800, 600]\n[audio]\nmuted = true\n");
+        assert_eq!(
+            changed_since(&file, &loaded, &current),
+            table("[window]\ntitle = \"mine\"\n[audio]\nmuted = true\n")
+        );
+        assert_eq!(changed_since(&file, &loaded, &loaded), file);
+    }
+
+    #[test]
+    fn checks_values() {
+        assert_eq!(
+            parse_value("[1, 2]"),
+            toml::from_str::<Table>("v = [1, 2]").unwrap()["v"]
+        );
+        assert_eq!(parse_value("true"), Value::Boolean(true));
+        assert_eq!(float(0.8), Value::Float(0.8));
+        assert_eq!(positive(&Value::Integer(2)), Ok(2.0));
+        assert!(positive(&Value::Float(0.0)).is_err());
+        assert_eq!(rate(&Value::Integer(60)), Ok(60.0));
+        assert!(rate(&Value::Float(1e-30)).is_err());
+        assert!(rate(&Value::Integer(20_000)).is_err());
+        assert_eq!(
+            rate(&Value::Integer(0)),
+            Err("can't be 0, it has to be from 0.01 to 10000".to_string())
+        );
+        assert!(fraction(&Value::Float(1.5)).is_err());
+        assert_eq!(integer::<u32>(&Value::Integer(3), 1, 4), Ok(3));
+        assert!(integer::<u32>(&Value::Integer(5), 1, 4).is_err());
+        assert!(boolean(&Value::String("yes".to_string())).is_err());
+    }
+}
@@ -0,0 +1,346 @@
+use crate::render::{Color, Layer, RenderCommand, RenderCommands, Space, Sprite, Text};
+use std::ops::RangeInclusive;
+
+const ROW_HEIGHT: f32 = 24.0;
+const TEXT_SIZE: f32 = 16.0;
+const PADDING: f32 = 4.0;
+const PANEL_COLOR: Color = [0.1, 0.1, 0.1, 0.85];
+const WIDGET_COLOR: Color = [0.25, 0.25, 0.25, 1.0];
+const HOT_COLOR: Color = [0.35, 0.35, 0.45, 1.0];
+const ACCENT_COLOR: Color = [0.4, 0.6, 1.0, 1.0];
+const TEXT_COLOR: Color = [1.0, 1.0, 1.0, 1.0];
+
+// pointer state for one frame, in pixels from the top left corner of the window
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct UiInput {
+    // None while the cursor is outside of the window
+    pub cursor: Option<[f32; 2]>,
+    pub down: bool,
+    // the button went down or up since the previous frame
+    pub pressed: bool,
+    pub released: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rect {
+    position: [f32; 2],
+    size: [f32; 2],
+}
+
+impl Rect {
+    fn contains(&self, point: [f32; 2]) -> bool {
+        point[0] >= self.position[0]
+            && point[1] >= self.position[1]
+            && point[0] < self.position[0] + self.size[0]
+            && point[1] < self.position[1] + self.size[1]
+    }
+}
+
+// immediate mode widgets drawn as screen space sprites and text on the ui layer. Call `begin`
+// with the frame's input, build panels, then `finish` to emit them. Panels capture the pointer
+// so that the game can ignore clicks meant for the ui
+#[derive(Debug, Default)]
+pub struct Ui {
+    input: UiInput,
+    // panels of the previous frame, they decide what the pointer is over
+    panels: Vec<Rect>,
+    drawn: Vec<Rect>,
+    // the widget holding the pointer since it was pressed on it
+    active: Option<String>,
+    commands: Vec<RenderCommand>,
+    // layout of the panel being built
+    panel: String,
+    row: [f32; 2],
+    width: f32,
+}
+
+impl Ui {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn begin(&mut self, input: UiInput) {
+        self.input = input;
+        self.drawn.clear();
+        self.commands.clear();
+    }
+
+    // moves this frame's widgets to the end of `commands`
+    pub fn finish(&mut self, commands: &mut RenderCommands) {
+        self.panels = std::mem::take(&mut self.drawn);
+        if !self.input.down {
+            self.active = None;
+        }
+        for command in self.commands.drain(..) {
+            commands.push(command);
+        }
+    }
+
+    // true while pointer input belongs to the ui rather than the game
+    pub fn wants_pointer(&self) -> bool {
+        self.active.is_some()
+            || match self.input.cursor {
+                Some(cursor) => self.panels.iter().any(|panel| panel.contains(cursor)),
+                None => false,
+            }
+    }
+
+    // a titled column of widgets, as tall as its contents
+    pub fn panel<F>(&mut self, title: &str, position: [f32; 2], width: f32, contents: F)
+    where
+        F: FnOnce(&mut Self),
+    {
+        self.panel = title.to_string();
+        self.row = [position[0] + PADDING, position[1] + PADDING];
+        self.width = width - PADDING * 2.0;
+        self.text(title, self.row, ACCENT_COLOR);
+        self.row[1] += ROW_HEIGHT;
+        contents(self);
+        let rect = Rect {
+            position,
+            size: [width, self.row[1] - position[1] + PADDING],
+        };
+        self.rect(rect, PANEL_COLOR, 0);
+        self.drawn.push(rect);
+    }
+
+    pub fn label(&mut self, text: &str) {
+        let row = self.next_row();
+        self.text(text, row.position, TEXT_COLOR);
+    }
+
+    // true on the frame it's clicked
+    pub fn button(&mut self, label: &str) -> bool {
+        let row = self.next_row();
+        let (hot, clicked) = self.interact(label, row);
+        self.rect(row, if hot { HOT_COLOR } else { WIDGET_COLOR }, 1);
+        self.text(
+            label,
+            [row.position[0] + PADDING, row.position[1]],
+            TEXT_COLOR,
+        );
+        clicked
+    }
+
+    // true when `value` changed
+    pub fn checkbox(&mut self, label: &str, value: &mut bool) -> bool {
+        let row = self.next_row();
+        let (hot, clicked) = self.interact(label, row);
+        if clicked {
+            *value = !*value;
+        }
+        let size = ROW_HEIGHT - PADDING * 2.0;
+        let check = Rect {
+            position: [row.position[0], row.position[1] + PADDING],
+            size: [size, size],
+        };
+        self.rect(check, if hot { HOT_COLOR } else { WIDGET_COLOR }, 1);
+        if *value {
+            let mark = Rect {
+                position: [check.position[0] + 3.0, check.position[1] + 3.0],
+                size: [size - 6.0, size - 6.0],
+            };
+            self.rect(mark, ACCENT_COLOR, 2);
+        }
+        let text = [row.position[0] + size + PADDING * 2.0, row.position[1]];
+        self.text(label, text, TEXT_COLOR);
+        clicked
+    }
+
+    // dragging anywhere on the row sets `value` from the cursor, true when it changed
+    pub fn slider(&mut self, label: &str, value: &mut f32, range: RangeInclusive<f32>) -> bool {
+        let row = self.next_row();
+        let (hot, _) = self.interact(label, row);
+        let (min, max) = (*range.start(), *range.end());
+        let mut changed = false;
+        if self.active.as_deref() == Some(&self.id(label)) {
+            if let Some(cursor) = self.input.cursor {
+                let t = ((cursor[0] - row.position[0]) / row.size[0]).clamp(0.0, 1.0);
+                let dragged = min + (max - min) * t;
+                changed = dragged != *value;
+                *value = dragged;
+            }
+        }
+        let t = ((*value - min) / (max - min)).clamp(0.0, 1.0);
+        self.rect(row, if hot { HOT_COLOR } else { WIDGET_COLOR }, 1);
+        let fill = Rect {
+            size: [row.size[0] * t, row.size[1]],
+            ..row
+        };
+        self.rect(fill, ACCENT_COLOR, 2);
+        let text = format!("{}: {:.2}", label, value);
+        self.text(
+            &text,
+            [row.position[0] + PADDING, row.position[1]],
+            TEXT_COLOR,
+        );
+        changed
+    }
+
+    fn next_row(&mut self) -> Rect {
+        let row = Rect {
+            position: self.row,
+            size: [self.width, ROW_HEIGHT - 2.0],
+        };
+        self.row[1] += ROW_HEIGHT;
+        row
+    }
+
+    fn id(&self, label: &str) -> String {
+        format!("{}/{}", self.panel, label)
+    }
+
+    // whether the pointer is over `rect` and whether it was clicked, pressing it makes it active
+    fn interact(&mut self, label: &str, rect: Rect) -> (bool, bool) {
+        let id = self.id(label);
+        let hot = match self.input.cursor {
+            Some(cursor) => rect.contains(cursor),
+            None => false,
+        };
+        if hot && self.input.pressed && self.active.is_none() {
+            self.active = Some(id.clone());
+        }
+        let clicked = hot && self.input.released && self.active.as_ref() == Some(&id);
+        (hot, clicked)
+    }
+
+    fn rect(&mut self, rect: Rect, color: Color, z: i16) {
+        self.commands.push(RenderCommand::Sprite(Sprite {
+            position: rect.position,
+            size: rect.size,
+            color,
+            space: Space::Screen,
+            layer: Layer::Ui,
+            z,
+        }));
+    }
+
+    fn text(&mut self, content: &str, position: [f32; 2], color: Color) {
+        let offset = (ROW_HEIGHT - TEXT_SIZE) / 2.0;
+        self.commands.push(RenderCommand::Text(Text {
+            color,
+            z: 3,
+            ..Text::new(content, [position[0], position[1] + offset], TEXT_SIZE)
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(ui: &mut Ui, input: UiInput, checked: &mut bool) -> (bool, RenderCommands) {
+        let mut commands = RenderCommands::new();
+        let mut clicked = false;
+        ui.begin(input);
+        ui.panel("debug", [10.0, 10.0], 200.0, |ui| {
+            ui.label("hello");
+            clicked = ui.button("reset");
+            ui.checkbox("lines", checked);
+        });
+        ui.finish(&mut commands);
+        (clicked, commands)
+    }
+
+    fn at(cursor: [f32; 2]) -> UiInput {
+        UiInput {
+            cursor: Some(cursor),
+            ..UiInput::default()
+        }
+    }
+
+    // title, label and button rows are 24 pixels apart starting at 14
+    const BUTTON: [f32; 2] = [50.0, 70.0];
+    const CHECKBOX: [f32; 2] = [20.0, 94.0];
+
+    #[test]
+    fn clicks_buttons_on_release() {
+        let mut ui = Ui::new();
+        let mut checked = false;
+        let press = UiInput {
+            down: true,
+            pressed: true,
+            ..at(BUTTON)
+        };
+        let release = UiInput {
+            released: true,
+            ..at(BUTTON)
+        };
+        assert!(!frame(&mut ui, at(BUTTON), &mut checked).0);
+        assert!(!frame(&mut ui, press, &mut checked).0);
+        assert!(frame(&mut ui, release, &mut checked).0);
+        // pressing elsewhere and releasing over it is not a click
+        let outside = UiInput {
+            down: true,
+            pressed: true,
+            ..at([300.0, 300.0])
+        };
+        frame(&mut ui, outside, &mut checked);
+        assert!(!frame(&mut ui, release, &mut checked).0);
+    }
+
+    #[test]
+    fn toggles_checkboxes() {
+        let mut ui = Ui::new();
+        let mut checked = false;
+        let press = UiInput {
+            down: true,
+            pressed: true,
+            ..at(CHECKBOX)
+        };
+        let release = UiInput {
+            released: true,
+            ..at(CHECKBOX)
+        };
+        frame(&mut ui, press, &mut checked);
+        let (_, commands) = frame(&mut ui, release, &mut checked);
+        assert!(checked);
+        // the check mark is drawn the frame it changes
+        let accents = commands
+            .iter()
+            .filter(|command| match command {
+                RenderCommand::Sprite(sprite) => sprite.color == ACCENT_COLOR,
+                _ => false,
+            })
+            .count();
+        assert_eq!(accents, 1);
+    }
+
+    #[test]
+    fn drags_sliders() {
+        let mut ui = Ui::new();
+        let mut value = 0.0;
+        let mut slide = |ui: &mut Ui, input: UiInput| {
+            ui.begin(input);
+            ui.panel("settings", [0.0, 0.0], 108.0, |ui| {
+                ui.slider("volume", &mut value, 0.0..=1.0);
+            });
+            ui.finish(&mut RenderCommands::new());
+        };
+        let press = UiInput {
+            down: true,
+            pressed: true,
+            ..at([54.0, 30.0])
+        };
+        slide(&mut ui, press);
+        // keeps following the cursor outside of the row while held
+        let drag = UiInput {
+            down: true,
+            ..at([500.0, 500.0])
+        };
+        slide(&mut ui, drag);
+        assert_eq!(value, 1.0);
+    }
+
+    #[test]
+    fn captures_pointer_over_panels() {
+        let mut ui = Ui::new();
+        let mut checked = false;
+        frame(&mut ui, at([300.0, 300.0]), &mut checked);
+        assert!(!ui.wants_pointer());
+        frame(&mut ui, at([20.0, 20.0]), &mut checked);
+        assert!(ui.wants_pointer());
+        frame(&mut ui, UiInput::default(), &mut checked);
+        assert!(!ui.wants_pointer());
+    }
+}
@@ -0,0 +1,241 @@
+use common::settings::{parse_value, Override};
+use log::LevelFilter;
+use std::path::PathBuf;
+use toml::Value;
+
+pub const HELP: &str = "\
+usage: main [options]
+
+window
+    --size <width>x<height>   window size in logical pixels
+    --windowed                in a window
+    --fullscreen              borderless fullscreen
+    --exclusive               exclusive fullscreen, in the video mode closest to the size
+    --monitor <name>          on this monitor instead of the primary one
+
+graphics
+    --backend <gpu|software>  the only renderer to try
+    --adapter <name>          the GPU whose name contains this, before any other
+
+simulation
+    --tick-rate <hz>          world updates per second, from 0.01 to 10000
+    --seed <number>           what everything random follows from, random by default
+    --replay <file>           feed the game a recording instead of live input until it ends,
+                              with its seed
+    --record <file>           record the game's input, to replay it later
+    --headless                no window and no rendering, ends with the replay
+
+settings
+    --config <file>           the settings file, settings.toml by default, changes made while
+                              the game runs are saved to it
+    --set <section.key=value> any setting, as it's written in the settings file
+                              GAME_<SECTION>_<KEY>=<value> in the environment does the same,
+                              the command line wins over the environment, which wins over the
+                              settings file
+
+other
+    --log-level <level>       off, error, warn, info, debug or trace, RUST_LOG by default
+    --queue-size <events>     how many events may wait for the game
+    -h, --help                print this and exit
+";
+
+// what was given on the command line, anything None is left to the settings and the defaults
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Options {
+    // the last layer of settings, checked when they're loaded
+    pub overrides: Vec<Override>,
+    pub seed: Option<u64>,
+    pub replay: Option<PathBuf>,
+    pub record: Option<PathBuf>,
+    pub headless: bool,
+    pub config: Option<PathBuf>,
+    pub log_level: Option<LevelFilter>,
+    pub queue_size: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Run(Options),
+    Help,
+}
+
+// `--flag value` and `--flag=value` both work
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.find('=') {
+            Some(at) if arg.starts_with("--") => {
+                (arg[..at].to_string(), Some(arg[at + 1..].to_string()))
+            }
+            _ => (arg, None),
+        };
+        let mut value = || {
+            inline
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("{} needs a value", flag))
+        };
+        let mut set = |key: &str, value: Value| {
+            let setting = Override::new(key, value, flag.as_str());
+            options.overrides.push(setting);
+        };
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--size" => {
+                let [width, height] = parse_size(&value()?)?;
+                set("window.size", vec![width, height].into());
+            }
+            "--windowed" => set("window.fullscreen", "windowed".into()),
+            "--fullscreen" => set("window.fullscreen", "borderless".into()),
+            "--exclusive" => set("window.fullscreen", "exclusive".into()),
+            "--monitor" => set("window.monitor", value()?.into()),
+            "--backend" => set("graphics.backends", vec![value()?].into()),
+            "--adapter" => set("graphics.adapter", value()?.into()),
+            "--tick-rate" => {
+                let rate: f64 = parsed(&flag, &value()?)?;
+                set("gameplay.tick_rate", rate.into());
+            }
+            "--set" => {
+                let setting = value()?;
+                let at = setting
+                    .find('=')
+                    .ok_or_else(|| format!("--set `{}` needs a value, <key>=<value>", setting))?;
+                set(&setting[..at], parse_value(&setting[at + 1..]));
+            }
+            "--seed" => options.seed = Some(parsed(&flag, &value()?)?),
+            "--replay" => options.replay = Some(value()?.into()),
+            "--record" => options.record = Some(value()?.into()),
+            "--headless" => options.headless = true,
+            "--config" => options.config = Some(value()?.into()),
+            "--log-level" => options.log_level = Some(parsed(&flag, &value()?)?),
+            "--queue-size" => {
+                let size: usize = parsed(&flag, &value()?)?;
+                if size == 0 {
+                    return Err("--queue-size can't be 0".to_string());
+                }
+                options.queue_size = Some(size);
+            }
+            _ => return Err(format!("unknown option `{}`", flag)),
+        }
+        if inline.is_some() && !takes_value(&flag) {
+            return Err(format!("{} doesn't take a value", flag));
+        }
+    }
+    Ok(Command::Run(options))
+}
+
+fn takes_value(flag: &str) -> bool {
+    !matches!(
+        flag,
+        "--windowed" | "--fullscreen" | "--exclusive" | "--headless"
+    )
+}
+
+// anything `FromStr`, named after the flag when it doesn't parse
+fn parsed<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} can't be `{}`", flag, value))
+}
+
+fn parse_size(value: &str) -> Result<[u32; 2], String> {
+    let invalid = || format!("--size can't be `{}`, it's <width>x<height>", value);
+    let mut parts = value.split('x');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(width), Some(height), None) => {
+            let width = width.parse().map_err(|_| invalid())?;
+            let height = height.parse().map_err(|_| invalid())?;
+            if width == 0 || height == 0 {
+                return Err(invalid());
+            }
+            Ok([width, height])
+        }
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Settings;
+
+    fn run(line: &str) -> Result<Options, String> {
+        match parse(line.split_whitespace().map(str::to_string))? {
+            Command::Run(options) => Ok(options),
+            Command::Help => Err("help".to_string()),
+        }
+    }
+
+    #[test]
+    fn parses_flags_and_values() {
+        let options = run("--size=640x480 --seed 3 --headless --set input.up=Up").unwrap();
+        assert_eq!(
+            options.overrides,
+            [
+                Override::new("window.size", vec![640, 480], "--size"),
+                Override::new("input.up", "Up", "--set"),
+            ]
+        );
+        assert_eq!(options.seed, Some(3));
+        assert!(options.headless);
+        assert_eq!(run("--windowed -h"), Err("help".to_string()));
+    }
+
+    #[test]
+    fn rejects_unknown_flags() {
+        assert_eq!(run("--bogus"), Err("unknown option `--bogus`".to_string()));
+        assert_eq!(run("-x"), Err("unknown option `-x`".to_string()));
+        assert_eq!(
+            run("--headless=yes"),
+            Err("--headless doesn't take a value".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_missing_values() {
+        assert_eq!(run("--seed"), Err("--seed needs a value".to_string()));
+        assert_eq!(
+            run("--windowed --tick-rate"),
+            Err("--tick-rate needs a value".to_string())
+        );
+        assert_eq!(
+            run("--set graphics.max_fps"),
+            Err("--set `graphics.max_fps` needs a value, <key>=<value>".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_values_that_dont_parse() {
+        assert_eq!(
+            run("--tick-rate fast"),
+            Err("--tick-rate can't be `fast`".to_string())
+        );
+        assert_eq!(run("--seed -1"), Err("--seed can't be `-1`".to_string()));
+        assert_eq!(
+            run("--queue-size 0"),
+            Err("--queue-size can't be 0".to_string())
+        );
+        assert!(run("--size 640x0").is_err());
+        assert!(run("--size 640").is_err());
+    }
+
+    // the tick rate is checked with the other settings, a tiny one would overflow the period
+    #[test]
+    fn bounds_the_tick_rate() {
+        let missing = std::env::temp_dir().join(format!("cli-{}.toml", std::process::id()));
+        let load = |line| {
+            let options = run(line).unwrap();
+            Settings::load(&missing, vec![], &options.overrides).map(|(settings, _)| settings)
+        };
+        assert_eq!(load("--tick-rate 30").unwrap().tick_rate, 30.0);
+        for line in &["--tick-rate 1e-30", "--tick-rate 0", "--tick-rate=20000"] {
+            let error = load(line).unwrap_err();
+            assert!(
+                error.starts_with("--tick-rate: `gameplay.tick_rate` can't be"),
+                "{}",
+                error
+            );
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use common::event::PlatformEvent;
+use common::jobs::JobSystem;
+use common::plugin::PluginHost;
+use common::render::RenderCommands;
+use common::ui::Ui;
+use std::ffi::c_void;
+use winit::event::Event as WEvent;
+use world::api::GameApi;
+use world::settings::GameSettings;
+
+// the game logic and its state. Starts out with the logic linked into main, with the
+// `hot-reload` feature it moves to every new build of world-dylib as it appears
+pub struct Game {
+    api: GameApi,
+    state: *mut c_void,
+    // given to every new build again, settings aren't saved with the state
+    #[cfg(feature = "hot-reload")]
+    settings: GameSettings,
+    // holds the code `api` points into, it's unloaded after `drop` destroyed the state
+    #[cfg(feature = "hot-reload")]
+    library: Option<GameLibrary>,
+}
+
+impl Game {
+    pub fn new(api: GameApi, seed: u64, settings: GameSettings) -> Self {
+        let state = unsafe { (api.start)(seed) };
+        unsafe { (api.configure)(state, &settings) };
+        Self {
+            api,
+            state,
+            #[cfg(feature = "hot-reload")]
+            settings,
+            #[cfg(feature = "hot-reload")]
+            library: None,
+        }
+    }
+
+    // pointer events are dropped while the ui captures the pointer
+    pub fn handle_event(&mut self, event: &WEvent<'static, PlatformEvent>, pointer_captured: bool) {
+        unsafe { (self.api.handle_event)(self.state, event, pointer_captured) }
+    }
+
+    pub fn update(&mut self, plugins: &PluginHost, jobs: &JobSystem, dt: f32) {
+        unsafe { (self.api.update)(self.state, plugins, jobs, dt) }
+    }
+
+    pub fn render(&mut self, commands: &mut RenderCommands) {
+        unsafe { (self.api.render)(self.state, commands) }
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        unsafe { (self.api.ui)(self.state, ui) }
+    }
+
+    // the state is saved by the build it came from and loaded by the new one
+    #[cfg(feature = "hot-reload")]
+    pub fn switch_to(&mut self, library: GameLibrary) {
+        unsafe {
+            let saved = (self.api.save)(self.state);
+            (self.api.destroy)(self.state);
+            self.api = library.api;
+            self.state = (self.api.load)(&saved);
+            (self.api.configure)(self.state, &self.settings);
+        }
+        self.library = Some(library);
+    }
+}
+
+impl Drop for Game {
+    fn drop(&mut self) {
+        unsafe { (self.api.destroy)(self.state) }
+    }
+}
+
+#[cfg(feature = "hot-reload")]
+mod reload {
+    use libloading::Library;
+    use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, Instant, SystemTime};
+    use world::api::{GameApi, GameApiEntry, GAME_API_SYMBOL};
+
+    // the file only counts as rebuilt once it stayed the same for this long, cargo may still
+    // be writing it before
+    const SETTLE: Duration = Duration::from_millis(500);
+
+    // a copy of world-dylib, loaded from a path of its own since the system would hand
+    // back the already loaded library for the same path
+    pub struct GameLibrary {
+        pub api: GameApi,
+        // unloaded after everything from it was dropped, see `Game`
+        _library: Library,
+        copy: PathBuf,
+    }
+
+    impl GameLibrary {
+        pub fn load(path: &Path, generation: u32) -> Result<Self, String> {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let copy = std::env::temp_dir().join(format!(
+                "{}-{}-{}",
+                std::process::id(),
+                generation,
+                name
+            ));
+            fs::copy(path, &copy)
+                .map_err(|error| format!("Failed to copy {:?}: {}", path, error))?;
+            let loaded = unsafe {
+                Library::new(&copy).and_then(|library| {
+                    let entry = library.get::<GameApiEntry>(GAME_API_SYMBOL)?;
+                    let api = entry(log::logger(), log::max_level());
+                    Ok((library, api))
+                })
+            };
+            match loaded {
+                Ok((library, api)) => Ok(Self {
+                    api,
+                    _library: library,
+                    copy,
+                }),
+                Err(error) => {
+                    let _ = fs::remove_file(&copy);
+                    Err(format!("Failed to load {:?}: {}", path, error))
+                }
+            }
+        }
+    }
+
+    impl Drop for GameLibrary {
+        fn drop(&mut self) {
+            // the library is still mapped until `_library` drops, which doesn't stop unlinking
+            let _ = fs::remove_file(&self.copy);
+        }
+    }
+
+    // `cargo build -p world-dylib` puts the library next to the executable
+    pub fn library_path() -> PathBuf {
+        let name = format!("{}world_dylib{}", DLL_PREFIX, DLL_SUFFIX);
+        std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|directory| directory.join(&name)))
+            .unwrap_or_else(|| PathBuf::from(name))
+    }
+
+    // polls the library for new builds
+    pub struct LibraryWatcher {
+        pub path: PathBuf,
+        loaded: Option<SystemTime>,
+        changed: Option<(SystemTime, Instant)>,
+        generation: u32,
+    }
+
+    impl LibraryWatcher {
+        pub fn new(path: PathBuf) -> Self {
+            let loaded = modified(&path);
+            log::info!("Reloading game logic when {:?} changes", path);
+            Self {
+                path,
+                loaded,
+                changed: None,
+                generation: 0,
+            }
+        }
+
+        // a new build once it settled, a broken one is logged and skipped
+        pub fn poll(&mut self) -> Option<GameLibrary> {
+            let modified = modified(&self.path);
+            if modified.is_none() || modified == self.loaded {
+                return None;
+            }
+            match self.changed {
+                Some((time, since)) if Some(time) == modified => {
+                    if since.elapsed() < SETTLE {
+                        return None;
+                    }
+                }
+                _ => {
+                    self.changed = modified.map(|time| (time, Instant::now()));
+                    return None;
+                }
+            }
+            self.loaded = modified;
+            self.changed = None;
+            self.generation += 1;
+            match GameLibrary::load(&self.path, self.generation) {
+                Ok(library) => {
+                    log::info!("Reloaded game logic from {:?}", self.path);
+                    Some(library)
+                }
+                Err(error) => {
+                    log::warn!("Keeping the current game logic, {}", error);
+                    None
+                }
+            }
+        }
+    }
+
+    fn modified(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+}
+
+#[cfg(feature = "hot-reload")]
+pub use reload::{library_path, GameLibrary, LibraryWatcher};
@@ -1,61 +1,164 @@
-use platform::{window::Window, Platform};
+mod cli;
+mod game;
+mod plugins;
+mod settings;
+mod simulation;
+
+use cli::{Command, Options};
+use common::jobs::JobSystem;
+use platform::{
+    assets::AssetManager,
+    replay::{Recorder, Replay},
+    window::Window,
+    Platform,
+};
 use queue::{create_queue, event::Event};
+use settings::Settings;
 use simple_logger::SimpleLogger;
+use simulation::{Simulation, SimulationConfig};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 use winit::event::{Event as E, WindowEvent};
 use winit::event_loop::ControlFlow;
-use world::{World, WorldState};
-
-// use libloading::Library;
 
-// struct Application(Library);
-// impl Application {
-//     fn build_platform(&self) -> Platform {
-//         unsafe {
-//             let f = self.0.get::<fn() -> Platform>(b"build_platform\0").unwrap();
-//             f()
-//         }
-//     }
-// }
-// const LIB_PATH: &'static str = "./target/debug/libplatform.dylib";
+// settings are read from here, and changed ones are saved to it when the game is closed
+const SETTINGS_PATH: &str = "settings.toml";
+// everything loaded at runtime is read from here
+const ASSET_DIRECTORY: &str = "assets";
+// events waiting for the game
+const QUEUE_SIZE: usize = 1000;
 
 fn main() {
-    SimpleLogger::from_env().init().unwrap();
-    // let app = Application(Library::new(LIB_PATH).unwrap_or_else(|error| panic!("{}", error)));
-    // let mut last_modified = std::fs::metadata(LIB_PATH).unwrap()
-    //     .modified().unwrap();
-    // TODO: refactor code to support  hot reloading
-    // let platform = app.build_platform();
-    let (queue, events) = create_queue(1000);
-    let window = Window::new().unwrap();
-    let mut platform = Platform::start(&window, events.clone()).unwrap();
-    let world = World::start(events);
-    let mut world_state = WorldState::new();
-    let event_loop = window.event_loop;
+    let options = match cli::parse(std::env::args().skip(1)) {
+        Ok(Command::Run(options)) => options,
+        Ok(Command::Help) => {
+            print!("{}", cli::HELP);
+            return;
+        }
+        Err(error) => {
+            eprint!("{}\n\n{}", error, cli::HELP);
+            std::process::exit(2);
+        }
+    };
+    match options.log_level {
+        Some(level) => SimpleLogger::new().with_level(level),
+        None => SimpleLogger::new().env(),
+    }
+    .init()
+    .unwrap();
+    let config_path = options
+        .config
+        .clone()
+        .unwrap_or_else(|| SETTINGS_PATH.into());
+    let (settings, settings_file) =
+        Settings::load(&config_path, std::env::vars(), &options.overrides).unwrap_or_else(
+            |error| {
+                eprint!("{}\n\n{}", error, cli::HELP);
+                std::process::exit(2);
+            },
+        );
+    let simulation_config = simulation_config(&options, &settings).unwrap_or_else(|error| {
+        log::error!("{}", error);
+        std::process::exit(1);
+    });
+    let (queue, events) = create_queue(options.queue_size.unwrap_or(QUEUE_SIZE));
+    let jobs = Arc::new(JobSystem::with_available_threads());
+    if options.headless {
+        if simulation_config.replay.is_none() {
+            log::warn!("Running headless without a replay, only killing the process ends it");
+        }
+        Simulation::start(events, simulation_config, jobs)
+            .unwrap()
+            .wait();
+        return;
+    }
+    let (window, event_loop) = Window::new(settings.window.clone()).unwrap();
+    // load errors reach the queue like any other platform event
+    let proxy = Mutex::new(window.proxy.clone());
+    let assets = AssetManager::new(ASSET_DIRECTORY, jobs.clone(), move |event| {
+        let _ = proxy.lock().unwrap().send_event(event);
+    });
+    let mut platform =
+        Platform::start(window, settings.graphics.clone(), events.clone(), &assets).unwrap();
+    let mut simulation = Simulation::start(events, simulation_config, jobs).unwrap();
     let start_time = std::time::Instant::now();
 
     event_loop.run(move |event, _, control_flow| {
-        match event {
+        let event = match event {
             E::WindowEvent {
                 event: WindowEvent::CloseRequested,
                 ..
             } => {
+                simulation.stop();
+                // the window may have been moved or resized
+                let mut settings = settings.clone();
+                settings.window = platform.window.config.clone();
+                if let Err(error) = settings_file.save(&settings) {
+                    log::warn!("{}", error);
+                }
                 *control_flow = ControlFlow::Exit;
+                return;
             }
+            // can't be made 'static, forward the new size as a resize instead
             E::WindowEvent {
-                event: WindowEvent::ScaleFactorChanged { .. },
-                ..
-            } => {}
-            _ => {
-                // TODO: map events to domain specific events
-                let event =
-                    Event::new(event.to_static().unwrap(), start_time.elapsed().as_millis());
-                // TODO: message gets read only once, hence double push. fix this
-                queue.push(event.clone()).unwrap();
-                world.proccess_events(&mut world_state);
-                #[cfg(feature = "crossbeam")]
-                queue.push(event).unwrap();
-                platform.proccess_events(&world_state);
-            }
+                window_id,
+                event: WindowEvent::ScaleFactorChanged { new_inner_size, .. },
+            } => E::WindowEvent {
+                window_id,
+                event: WindowEvent::Resized(*new_inner_size),
+            },
+            event => event.to_static().unwrap(),
+        };
+        if let E::MainEventsCleared = event {
+            assets.poll();
+        }
+        // TODO: map events to domain specific events
+        let event = Event::new(event, start_time.elapsed().as_millis());
+        // TODO: message gets read only once, hence double push. fix this
+        queue.push(event.clone()).unwrap();
+        #[cfg(feature = "crossbeam")]
+        queue.push(event).unwrap();
+        platform.proccess_events(&simulation.snapshot().commands);
+        // don't spin while there is nothing to draw to
+        *control_flow = if platform.graphics.is_minimized() {
+            ControlFlow::Wait
+        } else {
+            ControlFlow::Poll
         };
     });
 }
+
+// a replay brings its own seed
+fn simulation_config(options: &Options, settings: &Settings) -> Result<SimulationConfig, String> {
+    let replay = match &options.replay {
+        Some(path) => Some(Replay::load(path)?),
+        None => None,
+    };
+    let seed = match (&replay, options.seed) {
+        (Some(replay), Some(seed)) if replay.seed != seed => {
+            log::warn!(
+                "Ignoring --seed {}, the replay was recorded with {}",
+                seed,
+                replay.seed
+            );
+            replay.seed
+        }
+        (Some(replay), _) => replay.seed,
+        (None, Some(seed)) => seed,
+        (None, None) => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64),
+    };
+    let record = match &options.record {
+        Some(path) => Some(Recorder::create(path, seed)?),
+        None => None,
+    };
+    Ok(SimulationConfig {
+        tick_rate: settings.tick_rate,
+        seed,
+        replay,
+        record,
+        stop_after_replay: options.headless,
+        game: settings.game.clone(),
+    })
+}
@@ -0,0 +1,124 @@
+use common::plugin::{
+    load_order, PluginDescriptor, PluginEvent, PluginEventKind, PluginHost, PluginInfo,
+    PLUGIN_SYMBOL,
+};
+use libloading::Library;
+use std::env::consts::DLL_SUFFIX;
+use std::fs;
+use std::path::Path;
+use winit::event::{ElementState, Event as WEvent, WindowEvent};
+
+// plugins found in a directory, registered in load order
+pub struct Plugins {
+    pub host: PluginHost,
+    // after `host`, so that the callbacks are gone before their code is unloaded
+    libraries: Vec<Library>,
+}
+
+impl Plugins {
+    // a plugin that can't be loaded is logged and left out, along with whatever depends on it
+    pub fn load(directory: &Path) -> Self {
+        let mut plugins = Self {
+            host: PluginHost::new(),
+            libraries: Vec::new(),
+        };
+        let mut paths: Vec<_> = match fs::read_dir(directory) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.to_string_lossy().ends_with(DLL_SUFFIX))
+                .collect(),
+            Err(_) => return plugins,
+        };
+        paths.sort();
+        let mut found = Vec::new();
+        for path in paths {
+            match unsafe { open(&path) } {
+                Ok(plugin) => found.push(plugin),
+                Err(error) => log::error!("Skipping plugin {:?}: {}", path, error),
+            }
+        }
+        let infos: Vec<PluginInfo> = found.iter().map(|(_, _, info)| info.clone()).collect();
+        let (order, errors) = load_order(&infos);
+        for error in errors {
+            log::error!("Skipping plugin, {}", error);
+        }
+        let mut failed: Vec<&str> = Vec::new();
+        for index in order {
+            let (_, descriptor, info) = &found[index];
+            if let Some((dependency, _)) = info
+                .dependencies
+                .iter()
+                .find(|(dependency, _)| failed.contains(&dependency.as_str()))
+            {
+                log::error!(
+                    "Skipping plugin {}, {} failed to load",
+                    info.name,
+                    dependency
+                );
+                failed.push(&info.name);
+            } else if unsafe { plugins.host.register(&**descriptor) } {
+                log::info!(
+                    "Loaded plugin {} {}.{}.{}",
+                    info.name,
+                    info.version.major,
+                    info.version.minor,
+                    info.version.patch
+                );
+            } else {
+                log::error!("Plugin {} failed to register", info.name);
+                failed.push(&info.name);
+            }
+        }
+        // a plugin that failed may have registered some callbacks before, so everything stays
+        plugins.libraries = found.into_iter().map(|(library, _, _)| library).collect();
+        plugins
+    }
+}
+
+unsafe fn open(path: &Path) -> Result<(Library, *const PluginDescriptor, PluginInfo), String> {
+    let library = Library::new(path).map_err(|error| error.to_string())?;
+    let descriptor = library
+        .get::<unsafe extern "C" fn() -> *const PluginDescriptor>(PLUGIN_SYMBOL)
+        .map_err(|error| error.to_string())?();
+    if descriptor.is_null() {
+        return Err("no plugin descriptor".to_string());
+    }
+    let info = PluginInfo::read(&*descriptor)?;
+    Ok((library, descriptor, info))
+}
+
+// the events plugins get to see
+pub fn plugin_event<T>(event: &WEvent<T>) -> Option<PluginEvent> {
+    let event = match event {
+        WEvent::WindowEvent { event, .. } => event,
+        _ => return None,
+    };
+    let mut plugin_event = PluginEvent {
+        kind: PluginEventKind::Resized,
+        scancode: 0,
+        pointer: [0.0, 0.0],
+        size: [0, 0],
+    };
+    match event {
+        WindowEvent::KeyboardInput { input, .. } => {
+            plugin_event.kind = match input.state {
+                ElementState::Pressed => PluginEventKind::KeyPressed,
+                ElementState::Released => PluginEventKind::KeyReleased,
+            };
+            plugin_event.scancode = input.scancode;
+        }
+        WindowEvent::CursorMoved { position, .. } => {
+            plugin_event.kind = PluginEventKind::PointerMoved;
+            plugin_event.pointer = [position.x as f32, position.y as f32];
+        }
+        WindowEvent::MouseInput { state, .. } => {
+            plugin_event.kind = match state {
+                ElementState::Pressed => PluginEventKind::PointerPressed,
+                ElementState::Released => PluginEventKind::PointerReleased,
+            };
+        }
+        WindowEvent::Resized(size) => plugin_event.size = [size.width, size.height],
+        _ => return None,
+    }
+    Some(plugin_event)
+}
@@ -0,0 +1,259 @@
+use common::settings::{
+    changed_since, env_overrides, file_overrides, float, rate, string, Override,
+};
+use platform::{
+    audio::AudioConfig,
+    graphics::config::GraphicsConfig,
+    input::{key_from_name, key_name},
+    window::WindowConfig,
+};
+use std::path::{Path, PathBuf};
+use toml::value::{Table, Value};
+use world::settings::GameSettings;
+
+// `GAME_GRAPHICS_MAX_FPS=30` sets `graphics.max_fps`
+const ENV_PREFIX: &str = "GAME_";
+
+// everything that can be set, in the sections of the settings file
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub window: WindowConfig,
+    pub graphics: GraphicsConfig,
+    pub audio: AudioConfig,
+    // world updates per second
+    pub tick_rate: f32,
+    // the `gameplay` and `input` sections
+    pub game: GameSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            window: WindowConfig::default(),
+            graphics: GraphicsConfig::default(),
+            audio: AudioConfig::default(),
+            tick_rate: 60.0,
+            game: GameSettings::default(),
+        }
+    }
+}
+
+impl Settings {
+    // the defaults, then the settings file, then the environment, then `overrides`. Bad settings
+    // in the file or the environment are logged and skipped, on the command line they're an error
+    pub fn load(
+        path: &Path,
+        vars: impl IntoIterator<Item = (String, String)>,
+        overrides: &[Override],
+    ) -> Result<(Self, SettingsFile), String> {
+        let (table, readable) = match std::fs::read_to_string(path) {
+            Ok(text) => match toml::from_str::<Table>(&text) {
+                Ok(table) => (table, true),
+                Err(error) => {
+                    log::error!("Ignoring {:?}, {}", path, error);
+                    (Table::new(), false)
+                }
+            },
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => (Table::new(), true),
+            Err(error) => {
+                log::error!("Failed to read {:?}: {}", path, error);
+                (Table::new(), false)
+            }
+        };
+        let mut settings = Self::default();
+        let origin = path.display().to_string();
+        for setting in file_overrides(&table, &origin)
+            .iter()
+            .chain(&env_overrides(vars, ENV_PREFIX))
+        {
+            if let Err(reason) = settings.apply(setting) {
+                log::error!("Ignoring {}", setting.error(&reason));
+            }
+        }
+        for setting in overrides {
+            settings
+                .apply(setting)
+                .map_err(|reason| setting.error(&reason))?;
+        }
+        let file = SettingsFile {
+            path: path.to_path_buf(),
+            table,
+            loaded: settings.to_table(),
+            readable,
+        };
+        Ok((settings, file))
+    }
+
+    fn apply(&mut self, setting: &Override) -> Result<(), String> {
+        let (section, key) = setting.split().ok_or("isn't a setting")?;
+        self.set(section, key, &setting.value)
+    }
+
+    // one key of one section, the error says what's wrong with the value
+    pub fn set(&mut self, section: &str, key: &str, value: &Value) -> Result<(), String> {
+        match (section, key) {
+            ("window", key) => self.window.set(key, value),
+            ("graphics", key) => self.graphics.set(key, value),
+            ("audio", key) => self.audio.set(key, value),
+            ("gameplay", "tick_rate") => {
+                self.tick_rate = rate(value)?;
+                Ok(())
+            }
+            ("gameplay", key) => self.game.set(key, value),
+            ("input", action) => {
+                let key = key_from_name(&string(value)?)
+                    .ok_or_else(|| format!("can't be {}, no key is called that", value))?;
+                self.game.controls.bind(action, key)
+            }
+            _ => Err("isn't a setting".to_string()),
+        }
+    }
+
+    pub fn to_table(&self) -> Table {
+        let mut input = Table::new();
+        for (action, key) in self.game.controls.bindings() {
+            input.insert(action.to_string(), key_name(key).into());
+        }
+        let mut gameplay = self.game.to_table();
+        gameplay.insert("tick_rate".to_string(), float(self.tick_rate));
+        let mut table = Table::new();
+        table.insert("window".to_string(), self.window.to_table().into());
+        table.insert("graphics".to_string(), self.graphics.to_table().into());
+        table.insert("audio".to_string(), self.audio.to_table().into());
+        table.insert("input".to_string(), input.into());
+        table.insert("gameplay".to_string(), gameplay.into());
+        table
+    }
+}
+
+// the settings file as it was loaded, saving it only writes what changed since, so that the
+// environment and the command line don't end up in it
+#[derive(Debug)]
+pub struct SettingsFile {
+    path: PathBuf,
+    table: Table,
+    // the settings after every layer
+    loaded: Table,
+    // a file that couldn't be read isn't overwritten
+    readable: bool,
+}
+
+impl SettingsFile {
+    pub fn save(&self, settings: &Settings) -> Result<(), String> {
+        let table = changed_since(&self.table, &self.loaded, &settings.to_table());
+        if table == self.table {
+            return Ok(());
+        }
+        if !self.readable {
+            return Err(format!(
+                "Not saving settings to {:?}, it couldn't be read",
+                self.path
+            ));
+        }
+        let text = toml::to_string_pretty(&table).map_err(|error| error.to_string())?;
+        std::fs::write(&self.path, text)
+            .map_err(|error| format!("Failed to write {:?}: {}", self.path, error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings_file(name: &str, text: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("settings-{}-{}.toml", name, std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    const FILE: &str = "[gameplay]\ntick_rate = 30\nplayer_step = 0.5\n\
+                        [graphics]\nmax_fps = 20\n[audio]\nmuted = true\n";
+
+    #[test]
+    fn layers_defaults_file_environment_and_command_line() {
+        let path = settings_file("layers", FILE);
+        let env = vars(&[
+            ("GAME_GAMEPLAY_TICK_RATE", "40"),
+            ("GAME_GRAPHICS_MAX_FPS", "25"),
+        ]);
+        let cli = [Override::new("gameplay.tick_rate", 50, "--tick-rate")];
+        let (settings, _) = Settings::load(&path, env, &cli).unwrap();
+        assert_eq!(settings.tick_rate, 50.0);
+        assert_eq!(settings.graphics.max_fps, Some(25.0));
+        assert_eq!(settings.game.player_step, 0.5);
+        assert!(settings.audio.muted);
+        assert_eq!(settings.audio.music_volume, 0.8);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn saves_changes_without_the_other_layers() {
+        let path = settings_file("round-trip", FILE);
+        let env = vars(&[("GAME_GRAPHICS_MAX_FPS", "25")]);
+        let cli = [Override::new("gameplay.tick_rate", 50, "--tick-rate")];
+        let (mut settings, file) = Settings::load(&path, env, &cli).unwrap();
+        settings
+            .set("audio", "music_volume", &Value::Float(0.5))
+            .unwrap();
+        settings
+            .set("input", "up", &Value::String("Up".to_string()))
+            .unwrap();
+        file.save(&settings).unwrap();
+        let (reloaded, _) = Settings::load(&path, vec![], &[]).unwrap();
+        assert_eq!(reloaded.audio.music_volume, 0.5);
+        assert_eq!(reloaded.game.controls.up, settings.game.controls.up);
+        assert!(reloaded.audio.muted);
+        // from the file, not the environment or the command line
+        assert_eq!(reloaded.tick_rate, 30.0);
+        assert_eq!(reloaded.graphics.max_fps, Some(20.0));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reports_bad_environment_values() {
+        let errors: Vec<String> = env_overrides(
+            vars(&[
+                ("GAME_AUDIO_MUTED", "maybe"),
+                ("GAME_GAMEPLAY_TICK_RATE", "0"),
+                ("GAME_AUDIO_LOUDNESS", "11"),
+                ("GAME_INPUT_UP", "Nowhere"),
+            ]),
+            ENV_PREFIX,
+        )
+        .iter()
+        .map(|setting| {
+            let reason = Settings::default().apply(setting).unwrap_err();
+            setting.error(&reason)
+        })
+        .collect();
+        assert_eq!(
+            errors,
+            // sorted by variable
+            [
+                "GAME_AUDIO_LOUDNESS: `audio.loudness` isn't a setting",
+                "GAME_AUDIO_MUTED: `audio.muted` can't be \"maybe\"",
+                "GAME_GAMEPLAY_TICK_RATE: `gameplay.tick_rate` can't be 0, it has to be from 0.01 \
+                 to 10000",
+                "GAME_INPUT_UP: `input.up` can't be \"Nowhere\", no key is called that",
+            ]
+        );
+        // skipped, where the command line is an error
+        let path = std::env::temp_dir().join(format!("settings-none-{}.toml", std::process::id()));
+        let env = vars(&[("GAME_AUDIO_MUTED", "maybe")]);
+        let (settings, _) = Settings::load(&path, env, &[]).unwrap();
+        assert!(!settings.audio.muted);
+        let cli = [Override::new("audio.muted", "maybe", "--set")];
+        assert_eq!(
+            Settings::load(&path, vec![], &cli).unwrap_err(),
+            "--set: `audio.muted` can't be \"maybe\""
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use crate::game::Game;
+use crate::plugins::{plugin_event, Plugins};
+use common::event::PlatformEvent;
+use common::jobs::JobSystem;
+use common::render::RenderCommands;
+use common::ui::Ui;
+use platform::graphics::timing::FrameLimiter;
+use platform::input::PointerInput;
+use platform::replay::{Recorder, Replay};
+use queue::{event::Event, receiver::Receiver};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Instant;
+use winit::event::Event as WEvent;
+use world::settings::GameSettings;
+
+// every library in here is loaded as a plugin
+const PLUGIN_DIRECTORY: &str = "plugins";
+
+// what the world looked like after a tick, never changed once published
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    pub commands: RenderCommands,
+}
+
+// how the game is driven
+#[derive(Debug)]
+pub struct SimulationConfig {
+    // world updates per second
+    pub tick_rate: f32,
+    pub seed: u64,
+    pub game: GameSettings,
+    // fed to the game in place of live input until it runs out, with the time between its ticks
+    pub replay: Option<Replay>,
+    // everything the game is fed goes here, replayed input too
+    pub record: Option<Recorder>,
+    // the simulation ends with the replay instead of going on with live input
+    pub stop_after_replay: bool,
+}
+
+// runs the game on a thread of its own, so that a slow tick doesn't hold up drawing and a slow
+// frame doesn't hold up the world. The renderer draws whichever snapshot was published last
+#[derive(Debug)]
+pub struct Simulation {
+    latest: Arc<Mutex<Arc<Snapshot>>>,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Simulation {
+    // the game, its plugins and its ui live and die on the new thread, none of them are `Send`
+    pub fn start(
+        events: Receiver<Event<WEvent<'static, PlatformEvent>>>,
+        config: SimulationConfig,
+        jobs: Arc<JobSystem>,
+    ) -> Result<Self, ()> {
+        let latest = Arc::new(Mutex::new(Arc::new(Snapshot::default())));
+        let running = Arc::new(AtomicBool::new(true));
+        let thread = std::thread::Builder::new()
+            .name("simulation".to_string())
+            .spawn({
+                let latest = latest.clone();
+                let running = running.clone();
+                move || run(events, config, &jobs, &latest, &running)
+            })
+            .map_err(|error| log::error!("Failed to start the simulation: {}", error))?;
+        Ok(Self {
+            latest,
+            running,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn snapshot(&self) -> Arc<Snapshot> {
+        self.latest.lock().unwrap().clone()
+    }
+
+    // finishes the current tick and waits for the game to be torn down
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::Release);
+        self.wait();
+    }
+
+    // until the game ends on its own, only a replay does
+    pub fn wait(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("The simulation thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for Simulation {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn run(
+    mut events: Receiver<Event<WEvent<'static, PlatformEvent>>>,
+    mut config: SimulationConfig,
+    jobs: &JobSystem,
+    latest: &Mutex<Arc<Snapshot>>,
+    running: &AtomicBool,
+) {
+    log::info!("Starting with seed {}", config.seed);
+    let mut game = Game::new(world::api::game_api(), config.seed, config.game.clone());
+    let plugins = Plugins::load(Path::new(PLUGIN_DIRECTORY));
+    #[cfg(feature = "hot-reload")]
+    let mut watcher = crate::game::LibraryWatcher::new(crate::game::library_path());
+    let mut input = PointerInput::new();
+    let mut ui = Ui::new();
+    let mut limiter = FrameLimiter::new(config.tick_rate);
+    let mut last_tick = Instant::now();
+    while running.load(Ordering::Acquire) {
+        limiter.wait();
+        // everything that arrived since the last tick
+        let live: Vec<_> = events.by_ref().map(|event| event.payload.clone()).collect();
+        let now = Instant::now();
+        let mut dt = (now - last_tick).as_secs_f32();
+        last_tick = now;
+        let batch = match config.replay.as_mut().map(Iterator::next) {
+            Some(Some(tick)) => {
+                dt = tick.dt;
+                tick.events
+            }
+            Some(None) if config.stop_after_replay => break,
+            Some(None) => {
+                log::info!("Replay finished, going on with live input");
+                config.replay = None;
+                live
+            }
+            None => live,
+        };
+        if let Some(recorder) = &mut config.record {
+            if let Err(error) = recorder.tick(dt, &batch) {
+                log::error!("Stopped recording: {}", error);
+                config.record = None;
+            }
+        }
+        for event in &batch {
+            input.handle_event(event);
+        }
+        ui.begin(input.take());
+        let pointer_captured = ui.wants_pointer();
+        for event in &batch {
+            if let Some(event) = plugin_event(event) {
+                plugins.host.handle_event(&event);
+            }
+            game.handle_event(event, pointer_captured);
+        }
+        // between ticks, nothing from the old build is in use
+        #[cfg(feature = "hot-reload")]
+        {
+            if let Some(library) = watcher.poll() {
+                game.switch_to(library);
+            }
+        }
+        game.update(&plugins.host, jobs, dt);
+        let mut commands = RenderCommands::new();
+        game.render(&mut commands);
+        plugins.host.render(&mut commands);
+        game.ui(&mut ui);
+        ui.finish(&mut commands);
+        *latest.lock().unwrap() = Arc::new(Snapshot { commands });
+    }
+}
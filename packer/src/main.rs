@@ -0,0 +1,142 @@
+#[cfg(feature = "compile-shaders")]
+#[path = "../../platform/src/graphics/shaders/kind.rs"]
+mod kind;
+
+use common::archive::{ArchiveWriter, Compression};
+#[cfg(feature = "compile-shaders")]
+use kind::shader_kind;
+#[cfg(feature = "compile-shaders")]
+use shaderc::{Compiler, ShaderKind};
+use simple_logger::SimpleLogger;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "usage: packer <asset directory> <archive> [--store]";
+
+// bundles every file under a directory into one archive, the game reads it in place of the
+// directory when the directory isn't there. That includes the `<source>.spv` next to every GLSL
+// shader, which is what the game loads without `hot-reload`. With `compile-shaders` it's
+// compiled from the source instead
+fn main() {
+    SimpleLogger::new().env().init().unwrap();
+    let mut compress = true;
+    let mut paths = vec![];
+    for argument in std::env::args().skip(1) {
+        match argument.as_str() {
+            // don't compress anything
+            "--store" => compress = false,
+            _ => paths.push(PathBuf::from(argument)),
+        }
+    }
+    let (directory, archive) = match paths.as_slice() {
+        [directory, archive] => (directory, archive),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+    if pack(directory, archive, compress).is_err() {
+        std::process::exit(1);
+    }
+}
+
+fn pack(directory: &Path, archive: &Path, compress: bool) -> Result<(), ()> {
+    let mut files = vec![];
+    collect(directory, &mut files)?;
+    // the same assets always make the same archive
+    files.sort();
+    #[cfg(feature = "compile-shaders")]
+    let mut compiler =
+        Compiler::new().ok_or_else(|| log::error!("Failed to initialize shader compiler"))?;
+    let mut writer = ArchiveWriter::new();
+    for file in &files {
+        let contents = std::fs::read(file)
+            .map_err(|error| log::error!("Failed to read {}: {}", file.display(), error))?;
+        let path = archive_path(directory, file)?;
+        #[cfg(feature = "compile-shaders")]
+        if let Some(kind) = shader_kind(file) {
+            let spirv = compile_shader(&mut compiler, &path, &contents, kind)?;
+            writer.add(format!("{}.spv", path), spirv);
+        }
+        writer.add(path, contents);
+    }
+    let out = File::create(archive)
+        .map_err(|error| log::error!("Failed to create {}: {}", archive.display(), error))?;
+    let entries = writer
+        .write(&mut BufWriter::new(out), compress)
+        .map_err(|error| log::error!("Failed to write {}: {}", archive.display(), error))?;
+    let size: u64 = entries.iter().map(|entry| entry.size).sum();
+    let stored: u64 = entries.iter().map(|entry| entry.stored_size).sum();
+    let compressed = entries
+        .iter()
+        .filter(|entry| entry.compression != Compression::Stored)
+        .count();
+    log::info!(
+        "Packed {} files ({} compressed) into {}, {} bytes down to {}",
+        entries.len(),
+        compressed,
+        archive.display(),
+        size,
+        stored
+    );
+    Ok(())
+}
+
+// hidden files and build leftovers are editor and tool noise
+fn collect(directory: &Path, files: &mut Vec<PathBuf>) -> Result<(), ()> {
+    let entries = std::fs::read_dir(directory)
+        .map_err(|error| log::error!("Failed to list {}: {}", directory.display(), error))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|error| log::error!("Failed to list {}: {}", directory.display(), error))?
+            .path();
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        let hidden = name.starts_with('.') || name.ends_with('~');
+        // compiled again from the source next to it, which may be newer
+        #[cfg(feature = "compile-shaders")]
+        let hidden =
+            hidden || name.ends_with(".spv") && shader_kind(&path.with_extension("")).is_some();
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+            collect(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+// relative to the asset directory, always separated by `/`
+fn archive_path(directory: &Path, file: &Path) -> Result<String, ()> {
+    let relative = file.strip_prefix(directory).map_err(|_| ())?;
+    let parts: Option<Vec<&str>> = relative
+        .components()
+        .map(|component| component.as_os_str().to_str())
+        .collect();
+    parts
+        .map(|parts| parts.join("/"))
+        .ok_or_else(|| log::error!("{} isn't a valid UTF-8 path", file.display()))
+}
+
+#[cfg(feature = "compile-shaders")]
+fn compile_shader(
+    compiler: &mut Compiler,
+    path: &str,
+    source: &[u8],
+    kind: ShaderKind,
+) -> Result<Vec<u8>, ()> {
+    let source = std::str::from_utf8(source).map_err(|error| log::error!("{}: {}", path, error))?;
+    let compiled = compiler
+        .compile_into_spirv(source, kind, path, "main", None)
+        .map_err(|error| log::error!("{}", error))?;
+    if compiled.get_num_warnings() > 0 {
+        log::warn!("{}", compiled.get_warning_messages());
+    }
+    Ok(compiled.as_binary_u8().to_vec())
+}
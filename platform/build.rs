@@ -0,0 +1,76 @@
+const ASSET_DIR: &str = "../assets";
+const SHADER_DIR: &str = "../assets/shaders";
+
+// tells the crate where to embed SPIR-V from, for when the assets don't have it. That's the
+// checked-in `<source>.spv` next to every GLSL shader, so that building needs no shader
+// compiler. With `compile-shaders` the sources are compiled to `$OUT_DIR/<asset path>.spv`
+// instead, and any compilation error fails the build
+fn main() {
+    println!("cargo:rerun-if-changed={}", SHADER_DIR);
+    #[cfg(feature = "compile-shaders")]
+    let spirv_dir = compile::compile_shaders();
+    #[cfg(not(feature = "compile-shaders"))]
+    let spirv_dir =
+        std::path::Path::new(&std::env::var("CARGO_MANIFEST_DIR").unwrap()).join(ASSET_DIR);
+    println!("cargo:rustc-env=SPIRV_DIR={}", spirv_dir.display());
+}
+
+#[cfg(feature = "compile-shaders")]
+#[path = "src/graphics/shaders/kind.rs"]
+mod kind;
+
+#[cfg(feature = "compile-shaders")]
+mod compile {
+    use super::kind::shader_kind;
+    use super::{ASSET_DIR, SHADER_DIR};
+    use shaderc::Compiler;
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    pub fn compile_shaders() -> PathBuf {
+        let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+        let mut compiler = Compiler::new().expect("Failed to initialize shader compiler");
+        let mut errors = vec![];
+
+        for path in shader_files(Path::new(SHADER_DIR)) {
+            let kind = match shader_kind(&path) {
+                Some(kind) => kind,
+                None => continue,
+            };
+            println!("cargo:rerun-if-changed={}", path.display());
+            let source = fs::read_to_string(&path).unwrap();
+            let name = path.display().to_string();
+            match compiler.compile_into_spirv(&source, kind, &name, "main", None) {
+                Ok(compiled) => {
+                    if compiled.get_num_warnings() > 0 {
+                        println!("cargo:warning={}", compiled.get_warning_messages());
+                    }
+                    let relative = path.strip_prefix(ASSET_DIR).unwrap();
+                    let target = out_dir.join(format!("{}.spv", relative.display()));
+                    fs::create_dir_all(target.parent().unwrap()).unwrap();
+                    fs::write(target, compiled.as_binary_u8()).unwrap();
+                }
+                Err(error) => errors.push(error.to_string()),
+            }
+        }
+
+        if !errors.is_empty() {
+            panic!("\n{}", errors.join("\n"));
+        }
+        out_dir
+    }
+
+    fn shader_files(directory: &Path) -> Vec<PathBuf> {
+        let mut files = vec![];
+        for entry in fs::read_dir(directory).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.extend(shader_files(&path));
+            } else {
+                files.push(path);
+            }
+        }
+        files
+    }
+}
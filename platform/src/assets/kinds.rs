@@ -0,0 +1,240 @@
+use super::Asset;
+#[cfg(feature = "hot-reload")]
+use crate::graphics::shaders::{compile_shader, shader_kind};
+#[cfg(not(feature = "hot-reload"))]
+use crate::graphics::shaders::{embedded_spirv, spirv_words};
+use ab_glyph::FontVec;
+use std::path::Path;
+#[cfg(not(feature = "hot-reload"))]
+use std::path::PathBuf;
+use toml::value::{Table, Value};
+
+// 8 bit rgba
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Asset for Image {
+    fn decode(_: &Path, bytes: Vec<u8>) -> Result<Self, String> {
+        let mut decoder = png::Decoder::new(&bytes[..]);
+        // palettes to rgb
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let (info, mut reader) = decoder.read_info().map_err(|error| error.to_string())?;
+        let mut pixels = vec![0; info.buffer_size()];
+        reader
+            .next_frame(&mut pixels)
+            .map_err(|error| error.to_string())?;
+        let pixels = match info.color_type {
+            png::ColorType::RGBA => pixels,
+            png::ColorType::RGB => pixels
+                .chunks_exact(3)
+                .flat_map(|pixel| vec![pixel[0], pixel[1], pixel[2], 255])
+                .collect(),
+            png::ColorType::Grayscale => pixels
+                .iter()
+                .flat_map(|&value| vec![value, value, value, 255])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => pixels
+                .chunks_exact(2)
+                .flat_map(|pixel| vec![pixel[0], pixel[0], pixel[0], pixel[1]])
+                .collect(),
+            color_type => return Err(format!("{:?} images aren't supported", color_type)),
+        };
+        Ok(Self {
+            width: info.width,
+            height: info.height,
+            pixels,
+        })
+    }
+}
+
+// SPIR-V, asked for by the path of its GLSL source. Read from `<source>.spv`, which the packer
+// puts in the archive, or else built into the executable. With the `hot-reload` feature the
+// source is compiled when it's loaded
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shader {
+    pub spirv: Vec<u32>,
+}
+
+impl Asset for Shader {
+    #[cfg(not(feature = "hot-reload"))]
+    fn decode(_: &Path, bytes: Vec<u8>) -> Result<Self, String> {
+        Ok(Self {
+            spirv: spirv_words(&bytes)?,
+        })
+    }
+
+    #[cfg(feature = "hot-reload")]
+    fn decode(path: &Path, bytes: Vec<u8>) -> Result<Self, String> {
+        let kind = shader_kind(path).ok_or("unknown shader stage")?;
+        let source = String::from_utf8(bytes).map_err(|error| error.to_string())?;
+        Ok(Self {
+            spirv: compile_shader(&source, kind, &path.display().to_string())?,
+        })
+    }
+
+    #[cfg(not(feature = "hot-reload"))]
+    fn file(path: &Path) -> PathBuf {
+        let mut file = path.as_os_str().to_owned();
+        file.push(".spv");
+        PathBuf::from(file)
+    }
+
+    #[cfg(not(feature = "hot-reload"))]
+    fn embedded(path: &Path) -> Option<&'static [u8]> {
+        embedded_spirv(path.to_str()?)
+    }
+}
+
+// a TTF or OTF font
+#[derive(Debug)]
+pub struct FontFace {
+    pub font: FontVec,
+}
+
+impl Asset for FontFace {
+    fn decode(_: &Path, bytes: Vec<u8>) -> Result<Self, String> {
+        let font = FontVec::try_from_vec(bytes).map_err(|_| "not a TTF or OTF font")?;
+        Ok(Self { font })
+    }
+}
+
+// 16 bit PCM from a WAV file, channels interleaved
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sound {
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub samples: Vec<i16>,
+}
+
+impl Asset for Sound {
+    fn decode(_: &Path, bytes: Vec<u8>) -> Result<Self, String> {
+        if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+            return Err("not a WAV file".to_string());
+        }
+        let u16_at = |at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+        let u32_at = |at: usize| {
+            u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+        };
+        let mut format = None;
+        let mut at = 12;
+        while at + 8 <= bytes.len() {
+            let id = &bytes[at..at + 4];
+            let size = u32_at(at + 4) as usize;
+            let body = at + 8;
+            let end = body
+                .checked_add(size)
+                .filter(|&end| end <= bytes.len())
+                .ok_or("truncated chunk")?;
+            match id {
+                b"fmt " if size >= 16 => {
+                    let (encoding, channels, sample_rate, bits) = (
+                        u16_at(body),
+                        u16_at(body + 2),
+                        u32_at(body + 4),
+                        u16_at(body + 14),
+                    );
+                    if encoding != 1 || bits != 16 {
+                        return Err(format!(
+                            "only 16 bit PCM is supported, not format {} with {} bits",
+                            encoding, bits
+                        ));
+                    }
+                    format = Some((channels, sample_rate));
+                }
+                b"data" => {
+                    let (channels, sample_rate) = format.ok_or("data before format")?;
+                    let samples = bytes[body..end]
+                        .chunks_exact(2)
+                        .map(|sample| i16::from_le_bytes([sample[0], sample[1]]))
+                        .collect();
+                    return Ok(Self {
+                        channels,
+                        sample_rate,
+                        samples,
+                    });
+                }
+                _ => {}
+            }
+            // chunks are padded to an even size
+            at = end + size % 2;
+        }
+        Err("no sample data".to_string())
+    }
+}
+
+// a level description, a toml table
+#[derive(Debug, Clone, PartialEq)]
+pub struct Level(pub Table);
+
+impl Asset for Level {
+    fn decode(_: &Path, bytes: Vec<u8>) -> Result<Self, String> {
+        toml_table(bytes).map(Self)
+    }
+}
+
+// settings, a toml table
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config(pub Table);
+
+impl Asset for Config {
+    fn decode(_: &Path, bytes: Vec<u8>) -> Result<Self, String> {
+        toml_table(bytes).map(Self)
+    }
+}
+
+fn toml_table(bytes: Vec<u8>) -> Result<Table, String> {
+    let text = String::from_utf8(bytes).map_err(|error| error.to_string())?;
+    match text.parse::<Value>().map_err(|error| error.to_string())? {
+        Value::Table(table) => Ok(table),
+        _ => Err("not a table".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wav(format: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut bytes = b"RIFF\0\0\0\0WAVE".to_vec();
+        for (id, body) in &[(b"fmt ", format), (b"data", data)] {
+            bytes.extend_from_slice(*id);
+            bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(body);
+        }
+        bytes
+    }
+
+    #[test]
+    fn decodes_pcm_sounds() {
+        let mut format = vec![];
+        for field in &[1u16, 2] {
+            format.extend_from_slice(&field.to_le_bytes());
+        }
+        format.extend_from_slice(&44100u32.to_le_bytes());
+        format.extend_from_slice(&(44100u32 * 4).to_le_bytes());
+        for field in &[4u16, 16] {
+            format.extend_from_slice(&field.to_le_bytes());
+        }
+        let data: Vec<u8> = [1i16, -1, 300, -300]
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes().to_vec())
+            .collect();
+        let sound = Sound::decode(Path::new("beep.wav"), wav(&format, &data)).unwrap();
+        assert_eq!((sound.channels, sound.sample_rate), (2, 44100));
+        assert_eq!(sound.samples, [1, -1, 300, -300]);
+        format[14] = 8;
+        assert!(Sound::decode(Path::new("beep.wav"), wav(&format, &data)).is_err());
+        assert!(Sound::decode(Path::new("beep.wav"), b"RIFF".to_vec()).is_err());
+    }
+
+    #[test]
+    fn decodes_tables() {
+        let level = Level::decode(Path::new("one.toml"), b"name = \"one\"".to_vec()).unwrap();
+        assert_eq!(level.0["name"].as_str(), Some("one"));
+        assert!(Config::decode(Path::new("bad.toml"), b"name = ".to_vec()).is_err());
+    }
+}
@@ -0,0 +1,453 @@
+pub mod kinds;
+
+use common::archive::Archive;
+use common::event::PlatformEvent;
+use common::jobs::{JobHandle, JobSystem};
+#[cfg(feature = "hot-reload")]
+use notify::{watcher, DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+#[cfg(feature = "hot-reload")]
+use std::time::Duration;
+
+// something that can be loaded from a file
+pub trait Asset: Sized + Send + Sync + 'static {
+    // runs on a job thread, `path` is what was asked for, only for error messages
+    fn decode(path: &Path, bytes: Vec<u8>) -> Result<Self, String>;
+
+    // the file that is actually read for `path`, relative to the asset directory
+    fn file(path: &Path) -> PathBuf {
+        path.to_path_buf()
+    }
+
+    // what is decoded when `file` can't be read, for assets built into the executable
+    fn embedded(_path: &Path) -> Option<&'static [u8]> {
+        None
+    }
+}
+
+#[derive(Debug)]
+enum State<T> {
+    Loading,
+    Loaded(Arc<T>),
+    Failed(String),
+}
+
+#[derive(Debug)]
+struct Slot<T> {
+    path: PathBuf,
+    state: Mutex<State<T>>,
+    // bumped every time new contents are loaded
+    version: AtomicU32,
+    job: Mutex<Option<JobHandle>>,
+}
+
+// reference counted, the asset is freed once the last handle to it is dropped. Loads in the
+// background, `get` is None until it's done
+#[derive(Debug)]
+pub struct Handle<T>(Arc<Slot<T>>);
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<T> Handle<T> {
+    pub fn path(&self) -> &Path {
+        &self.0.path
+    }
+
+    // the latest contents. A reload that failed keeps the previous ones
+    pub fn get(&self) -> Option<Arc<T>> {
+        match &*self.0.state.lock().unwrap() {
+            State::Loaded(asset) => Some(asset.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        matches!(*self.0.state.lock().unwrap(), State::Loading)
+    }
+
+    pub fn error(&self) -> Option<String> {
+        match &*self.0.state.lock().unwrap() {
+            State::Failed(error) => Some(error.clone()),
+            _ => None,
+        }
+    }
+
+    // changes whenever `get` would return something new, for noticing reloads
+    pub fn version(&self) -> u32 {
+        self.0.version.load(Ordering::Acquire)
+    }
+}
+
+// a slot of any asset type
+trait AnySlot: Send + Sync {
+    fn file(&self) -> PathBuf;
+    fn start(self: Arc<Self>, shared: &Arc<Shared>);
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync>;
+}
+
+impl<T: Asset> AnySlot for Slot<T> {
+    fn file(&self) -> PathBuf {
+        T::file(&self.path)
+    }
+
+    fn start(self: Arc<Self>, shared: &Arc<Shared>) {
+        let slot = self.clone();
+        let shared_for_job = shared.clone();
+        let job = shared.jobs.spawn(move || {
+            let loaded = shared_for_job
+                .read(&slot.file())
+                .or_else(|error| T::embedded(&slot.path).map(<[u8]>::to_vec).ok_or(error))
+                .and_then(|bytes| T::decode(&slot.path, bytes));
+            let mut state = slot.state.lock().unwrap();
+            match loaded {
+                Ok(asset) => {
+                    *state = State::Loaded(Arc::new(asset));
+                    slot.version.fetch_add(1, Ordering::AcqRel);
+                }
+                Err(error) => {
+                    log::error!("Failed to load {:?}: {}", slot.path, error);
+                    // a reload that failed keeps what was loaded before
+                    if let State::Loading = *state {
+                        *state = State::Failed(error.clone());
+                    }
+                    drop(state);
+                    (shared_for_job.report)(PlatformEvent::AssetFailed {
+                        path: slot.path.display().to_string(),
+                        error,
+                    });
+                }
+            }
+        });
+        *self.job.lock().unwrap() = Some(job);
+    }
+
+    fn into_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
+
+type Report = Box<dyn Fn(PlatformEvent) + Send + Sync>;
+
+struct Shared {
+    root: PathBuf,
+    // the packed assets, read from when a file isn't under `root`
+    archive: Option<Archive>,
+    jobs: Arc<JobSystem>,
+    // where load errors go, meant to reach the queue
+    report: Report,
+    // only weak, so that dropping the last handle frees the asset
+    slots: Mutex<HashMap<(TypeId, PathBuf), Weak<dyn AnySlot>>>,
+    #[cfg(feature = "hot-reload")]
+    watcher: Option<(
+        RecommendedWatcher,
+        Mutex<std::sync::mpsc::Receiver<DebouncedEvent>>,
+    )>,
+}
+
+impl Shared {
+    fn read(&self, file: &Path) -> Result<Vec<u8>, String> {
+        match std::fs::read(self.root.join(file)) {
+            Ok(bytes) => Ok(bytes),
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error.to_string()),
+            Err(error) => self
+                .archive
+                .as_ref()
+                .and_then(|archive| archive.read(&archive_path(file)?))
+                .unwrap_or_else(|| Err(error.to_string())),
+        }
+    }
+}
+
+// archives always separate with `/`
+fn archive_path(file: &Path) -> Option<String> {
+    let parts: Option<Vec<&str>> = file
+        .components()
+        .map(|component| component.as_os_str().to_str())
+        .collect();
+    parts.map(|parts| parts.join("/"))
+}
+
+// loads everything under one directory by path, each file at most once as each type. Shared
+// between threads. Files missing from the directory are looked up in `<directory>.pak`, made by
+// the packer, so a release ships the archive alone and development uses loose files
+pub struct AssetManager {
+    shared: Arc<Shared>,
+}
+
+impl AssetManager {
+    pub fn new(
+        root: impl Into<PathBuf>,
+        jobs: Arc<JobSystem>,
+        report: impl Fn(PlatformEvent) + Send + Sync + 'static,
+    ) -> Self {
+        let root = root.into();
+        let archive_file = root.with_extension("pak");
+        let archive = if archive_file.exists() {
+            match Archive::open(&archive_file) {
+                Ok(archive) => {
+                    log::info!("Reading assets from {}", archive_file.display());
+                    Some(archive)
+                }
+                Err(error) => {
+                    log::error!("Failed to open {}: {}", archive_file.display(), error);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        #[cfg(feature = "hot-reload")]
+        let watcher = watch(&root)
+            .map_err(|error| log::warn!("Asset hot reloading disabled: {:?}", error))
+            .ok();
+        Self {
+            shared: Arc::new(Shared {
+                root,
+                archive,
+                jobs,
+                report: Box::new(report),
+                slots: Mutex::default(),
+                #[cfg(feature = "hot-reload")]
+                watcher,
+            }),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.shared.root
+    }
+
+    // the raw contents of a file, for what is read once and never reloaded
+    pub fn read(&self, file: impl AsRef<Path>) -> Result<Vec<u8>, String> {
+        self.shared.read(file.as_ref())
+    }
+
+    // the same handle for as long as one is alive, otherwise starts loading it
+    pub fn load<T: Asset>(&self, path: impl AsRef<Path>) -> Handle<T> {
+        let path = path.as_ref().to_path_buf();
+        let key = (TypeId::of::<T>(), path.clone());
+        let mut slots = self.shared.slots.lock().unwrap();
+        let alive = slots
+            .get(&key)
+            .and_then(Weak::upgrade)
+            .and_then(|slot| slot.into_any().downcast::<Slot<T>>().ok());
+        if let Some(slot) = alive {
+            return Handle(slot);
+        }
+        let slot = Arc::new(Slot {
+            path,
+            state: Mutex::new(State::Loading),
+            version: AtomicU32::new(0),
+            job: Mutex::default(),
+        });
+        let erased: Arc<dyn AnySlot> = slot.clone();
+        slots.insert(key, Arc::downgrade(&erased));
+        drop(slots);
+        erased.start(&self.shared);
+        Handle(slot)
+    }
+
+    // blocks until the handle finished loading, running other jobs in the meantime
+    pub fn wait<T>(&self, handle: &Handle<T>) -> Result<Arc<T>, String> {
+        let job = handle.0.job.lock().unwrap().clone();
+        if let Some(job) = job {
+            self.shared.jobs.wait(&job);
+        }
+        match &*handle.0.state.lock().unwrap() {
+            State::Loaded(asset) => Ok(asset.clone()),
+            State::Failed(error) => Err(error.clone()),
+            State::Loading => Err("still loading".to_string()),
+        }
+    }
+
+    // loads every live asset read from `file` again, keeping the old contents until it's done
+    pub fn reload(&self, file: &Path) {
+        let mut slots = self.shared.slots.lock().unwrap();
+        slots.retain(|_, slot| slot.strong_count() > 0);
+        let changed: Vec<Arc<dyn AnySlot>> = slots
+            .values()
+            .filter_map(Weak::upgrade)
+            .filter(|slot| slot.file() == file)
+            .collect();
+        drop(slots);
+        for slot in changed {
+            log::info!("Reloading {:?}", file);
+            slot.start(&self.shared);
+        }
+    }
+
+    // reloads what changed on disk since the last call, with the `hot-reload` feature
+    pub fn poll(&self) {
+        #[cfg(feature = "hot-reload")]
+        {
+            let changed: Vec<PathBuf> = match &self.shared.watcher {
+                Some((_, events)) => events
+                    .lock()
+                    .unwrap()
+                    .try_iter()
+                    .filter_map(|event| match event {
+                        DebouncedEvent::Write(path)
+                        | DebouncedEvent::Create(path)
+                        | DebouncedEvent::Rename(_, path) => Some(path),
+                        _ => None,
+                    })
+                    .collect(),
+                None => return,
+            };
+            let root = self.shared.root.canonicalize().unwrap_or_default();
+            for path in changed {
+                if let Ok(file) = path.strip_prefix(&root) {
+                    self.reload(file);
+                }
+            }
+        }
+    }
+
+    // assets with at least one handle left
+    pub fn loaded(&self) -> usize {
+        let mut slots = self.shared.slots.lock().unwrap();
+        slots.retain(|_, slot| slot.strong_count() > 0);
+        slots.len()
+    }
+}
+
+impl std::fmt::Debug for AssetManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("AssetManager")
+            .field("root", &self.shared.root)
+            .field("archive", &self.shared.archive.is_some())
+            .finish()
+    }
+}
+
+#[cfg(feature = "hot-reload")]
+fn watch(
+    root: &Path,
+) -> Result<
+    (
+        RecommendedWatcher,
+        Mutex<std::sync::mpsc::Receiver<DebouncedEvent>>,
+    ),
+    notify::Error,
+> {
+    let (sender, events) = std::sync::mpsc::channel();
+    let mut watcher = watcher(sender, Duration::from_millis(100))?;
+    watcher.watch(root, RecursiveMode::Recursive)?;
+    log::info!("Watching {} for asset changes", root.display());
+    Ok((watcher, Mutex::new(events)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Words(Vec<String>);
+
+    impl Asset for Words {
+        fn decode(_: &Path, bytes: Vec<u8>) -> Result<Self, String> {
+            let text = String::from_utf8(bytes).map_err(|error| error.to_string())?;
+            Ok(Self(text.split_whitespace().map(String::from).collect()))
+        }
+    }
+
+    fn root(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("assets-{}-{}", std::process::id(), name))
+    }
+
+    fn manager(name: &str) -> (AssetManager, PathBuf, Arc<Mutex<Vec<PlatformEvent>>>) {
+        let root = root(name);
+        std::fs::create_dir_all(&root).unwrap();
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let sink = reported.clone();
+        let assets = AssetManager::new(&root, Arc::new(JobSystem::new(2)), move |event| {
+            sink.lock().unwrap().push(event)
+        });
+        (assets, root, reported)
+    }
+
+    #[test]
+    fn shares_handles_until_dropped() {
+        let (assets, root, _) = manager("share");
+        std::fs::write(root.join("greeting.txt"), "hello there").unwrap();
+        let first = assets.load::<Words>("greeting.txt");
+        let second = assets.load::<Words>("greeting.txt");
+        assert!(first == second);
+        let words = assets.wait(&first).unwrap();
+        assert_eq!(words.0, ["hello", "there"]);
+        assert_eq!(second.get(), Some(words));
+        assert_eq!(assets.loaded(), 1);
+        drop(first);
+        drop(second);
+        assert_eq!(assets.loaded(), 0);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn reports_load_errors() {
+        let (assets, root, reported) = manager("errors");
+        let missing = assets.load::<Words>("missing.txt");
+        assert!(assets.wait(&missing).is_err());
+        assert!(missing.error().is_some());
+        match &reported.lock().unwrap()[..] {
+            [PlatformEvent::AssetFailed { path, .. }] => assert_eq!(path, "missing.txt"),
+            reported => panic!("{:?}", reported),
+        }
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn reloads_changed_files() {
+        let (assets, root, reported) = manager("reload");
+        std::fs::write(root.join("level.txt"), "one").unwrap();
+        let level = assets.load::<Words>("level.txt");
+        assets.wait(&level).unwrap();
+        let version = level.version();
+        std::fs::write(root.join("level.txt"), "two").unwrap();
+        assets.reload(Path::new("level.txt"));
+        assert_eq!(assets.wait(&level).unwrap().0, ["two"]);
+        assert!(level.version() > version);
+        // a broken reload keeps the last good contents
+        std::fs::write(root.join("level.txt"), [0xff]).unwrap();
+        assets.reload(Path::new("level.txt"));
+        assert_eq!(assets.wait(&level).unwrap().0, ["two"]);
+        assert_eq!(reported.lock().unwrap().len(), 1);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn falls_back_to_the_archive() {
+        let mut writer = common::archive::ArchiveWriter::new();
+        writer.add("levels/one.txt", b"packed one".to_vec());
+        writer.add("levels/two.txt", b"packed two".to_vec());
+        let archive = root("packed").with_extension("pak");
+        writer
+            .write(&mut std::fs::File::create(&archive).unwrap(), true)
+            .unwrap();
+        let (assets, root, _) = manager("packed");
+        std::fs::create_dir_all(root.join("levels")).unwrap();
+        std::fs::write(root.join("levels/two.txt"), "loose two").unwrap();
+        let one = assets.load::<Words>("levels/one.txt");
+        assert_eq!(assets.wait(&one).unwrap().0, ["packed", "one"]);
+        // loose files win, that's how they're edited
+        let two = assets.load::<Words>("levels/two.txt");
+        assert_eq!(assets.wait(&two).unwrap().0, ["loose", "two"]);
+        assert!(assets.read("levels/three.txt").is_err());
+        std::fs::remove_dir_all(root).unwrap();
+        std::fs::remove_file(archive).unwrap();
+    }
+}
@@ -0,0 +1,62 @@
+use common::settings::{boolean, float, fraction};
+use toml::value::{Table, Value};
+
+// volumes from 0 to 1, nothing plays sound yet, a mixer reads these once there is one
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioConfig {
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub effects_volume: f32,
+    pub muted: bool,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            master_volume: 1.0,
+            music_volume: 0.8,
+            effects_volume: 1.0,
+            muted: false,
+        }
+    }
+}
+
+impl AudioConfig {
+    // what music plays at
+    pub fn music_gain(&self) -> f32 {
+        self.gain(self.music_volume)
+    }
+
+    pub fn effects_gain(&self) -> f32 {
+        self.gain(self.effects_volume)
+    }
+
+    fn gain(&self, volume: f32) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            self.master_volume * volume
+        }
+    }
+
+    // one key of the `audio` section, the error says what's wrong with the value
+    pub fn set(&mut self, key: &str, value: &Value) -> Result<(), String> {
+        match key {
+            "master_volume" => self.master_volume = fraction(value)?,
+            "music_volume" => self.music_volume = fraction(value)?,
+            "effects_volume" => self.effects_volume = fraction(value)?,
+            "muted" => self.muted = boolean(value)?,
+            _ => return Err("isn't a setting".to_string()),
+        }
+        Ok(())
+    }
+
+    pub fn to_table(&self) -> Table {
+        let mut table = Table::new();
+        table.insert("master_volume".to_string(), float(self.master_volume));
+        table.insert("music_volume".to_string(), float(self.music_volume));
+        table.insert("effects_volume".to_string(), float(self.effects_volume));
+        table.insert("muted".to_string(), self.muted.into());
+        table
+    }
+}
@@ -0,0 +1,203 @@
+use gfx_hal::adapter::{Adapter, DeviceType, PhysicalDevice};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdapterKind {
+    Discrete,
+    Integrated,
+    Virtual,
+    // software rasterizers like llvmpipe or WARP
+    Cpu,
+    Other,
+}
+
+impl From<&DeviceType> for AdapterKind {
+    fn from(device_type: &DeviceType) -> Self {
+        match device_type {
+            DeviceType::DiscreteGpu => Self::Discrete,
+            DeviceType::IntegratedGpu => Self::Integrated,
+            DeviceType::VirtualGpu => Self::Virtual,
+            DeviceType::Cpu => Self::Cpu,
+            DeviceType::Other => Self::Other,
+        }
+    }
+}
+
+// what gets reported about an adapter and what it is selected by
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdapterSummary {
+    pub name: String,
+    pub kind: AdapterKind,
+    pub vendor: usize,
+    pub device: usize,
+    // the graphics api driving the adapter, gfx-hal doesn't expose driver versions
+    pub driver: &'static str,
+    pub max_image_size: u32,
+    pub max_push_constants_size: usize,
+    pub max_bound_descriptor_sets: u16,
+}
+
+impl AdapterSummary {
+    pub fn new<B: gfx_hal::Backend>(adapter: &Adapter<B>, driver: &'static str) -> Self {
+        let limits = adapter.physical_device.limits();
+        Self {
+            name: adapter.info.name.clone(),
+            kind: AdapterKind::from(&adapter.info.device_type),
+            vendor: adapter.info.vendor,
+            device: adapter.info.device,
+            driver,
+            max_image_size: limits.max_image_2d_size,
+            max_push_constants_size: limits.max_push_constants_size,
+            max_bound_descriptor_sets: limits.max_bound_descriptor_sets,
+        }
+    }
+}
+
+impl fmt::Display for AdapterSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} ({:?}, {}, vendor {:#06x}, device {:#06x}, max image {}, push constants {}B, \
+             descriptor sets {})",
+            self.name,
+            self.kind,
+            self.driver,
+            self.vendor,
+            self.device,
+            self.max_image_size,
+            self.max_push_constants_size,
+            self.max_bound_descriptor_sets
+        )
+    }
+}
+
+pub fn report(adapters: &[AdapterSummary]) -> String {
+    let mut report = format!("{} adapter(s) available", adapters.len());
+    for (index, adapter) in adapters.iter().enumerate() {
+        report.push_str(&format!("\n  {}: {}", index, adapter));
+    }
+    report
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdapterSelection {
+    // tried first, matched case insensitively against part of the adapter name
+    pub name: Option<String>,
+    // kinds to try in order, anything not listed is never picked unless named
+    pub preference: Vec<AdapterKind>,
+}
+
+impl Default for AdapterSelection {
+    fn default() -> Self {
+        Self {
+            name: None,
+            preference: vec![
+                AdapterKind::Discrete,
+                AdapterKind::Integrated,
+                AdapterKind::Virtual,
+                AdapterKind::Cpu,
+                AdapterKind::Other,
+            ],
+        }
+    }
+}
+
+impl AdapterSelection {
+    // indices into `adapters` in the order they should be tried
+    pub fn rank(&self, adapters: &[AdapterSummary]) -> Vec<usize> {
+        let named: Vec<usize> = match &self.name {
+            Some(name) => {
+                let name = name.to_lowercase();
+                let named: Vec<usize> = (0..adapters.len())
+                    .filter(|&index| adapters[index].name.to_lowercase().contains(&name))
+                    .collect();
+                if named.is_empty() {
+                    log::warn!("No adapter named {:?}, choosing by preference", name);
+                }
+                named
+            }
+            None => vec![],
+        };
+        let mut preferred: Vec<(usize, usize)> = adapters
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !named.contains(index))
+            .filter_map(|(index, adapter)| {
+                self.preference
+                    .iter()
+                    .position(|kind| *kind == adapter.kind)
+                    .map(|rank| (rank, index))
+            })
+            .collect();
+        // stable, so equally preferred adapters keep the order the instance reported
+        preferred.sort_by_key(|(rank, _)| *rank);
+        named
+            .into_iter()
+            .chain(preferred.into_iter().map(|(_, index)| index))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adapter(name: &str, kind: AdapterKind) -> AdapterSummary {
+        AdapterSummary {
+            name: name.to_string(),
+            kind,
+            vendor: 0x10de,
+            device: 0x1c82,
+            driver: "vulkan",
+            max_image_size: 16384,
+            max_push_constants_size: 256,
+            max_bound_descriptor_sets: 8,
+        }
+    }
+
+    fn adapters() -> Vec<AdapterSummary> {
+        vec![
+            adapter("llvmpipe (LLVM 11.0.0, 256 bits)", AdapterKind::Cpu),
+            adapter("Intel(R) UHD Graphics 630", AdapterKind::Integrated),
+            adapter("GeForce GTX 1050 Ti", AdapterKind::Discrete),
+        ]
+    }
+
+    #[test]
+    fn ranks_by_preference() {
+        let selection = AdapterSelection::default();
+        assert_eq!(selection.rank(&adapters()), [2, 1, 0]);
+        let selection = AdapterSelection {
+            name: None,
+            preference: vec![AdapterKind::Cpu, AdapterKind::Integrated],
+        };
+        assert_eq!(selection.rank(&adapters()), [0, 1]);
+    }
+
+    #[test]
+    fn tries_named_adapter_first() {
+        let selection = AdapterSelection {
+            name: Some("intel".to_string()),
+            ..AdapterSelection::default()
+        };
+        assert_eq!(selection.rank(&adapters()), [1, 2, 0]);
+        // an unknown name falls back to the preference
+        let selection = AdapterSelection {
+            name: Some("radeon".to_string()),
+            ..AdapterSelection::default()
+        };
+        assert_eq!(selection.rank(&adapters()), [2, 1, 0]);
+    }
+
+    #[test]
+    fn reports_every_adapter() {
+        let report = report(&adapters());
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "3 adapter(s) available");
+        assert_eq!(
+            lines[3],
+            "  2: GeForce GTX 1050 Ti (Discrete, vulkan, vendor 0x10de, device 0x1c82, \
+             max image 16384, push constants 256B, descriptor sets 8)"
+        );
+    }
+}
@@ -0,0 +1,534 @@
+use super::{Draw, Frame, RenderBackend};
+use crate::assets::{kinds::Shader, AssetManager, Handle};
+use crate::graphics::capture::Image;
+use crate::graphics::config::GraphicsConfig;
+use crate::graphics::resources::{
+    pipeline_interfaces, Backend, FrameError, ResourceHolder, Resources, WindowHandle,
+};
+use crate::graphics::shaders::PIPELINES;
+use crate::graphics::texture::{ColorTarget, DepthBuffer};
+use common::event::GraphicsLoss;
+use gfx_hal::{
+    command::{
+        ClearColor, ClearDepthStencil, ClearValue, CommandBuffer, CommandBufferFlags,
+        SubpassContents,
+    },
+    device::Device,
+    format::SurfaceType,
+    image::Extent,
+    pso::{Rect, ShaderStageFlags, Viewport},
+    queue::{CommandQueue, Submission},
+    window::{Extent2D, PresentationSurface},
+};
+use raw_window_handle::HasRawWindowHandle;
+use std::borrow::Borrow;
+use std::sync::Arc;
+use winit::window::Window;
+
+// draws through gfx-hal with whichever backend the `metal`, `dx12` or `vulkan` feature picked
+#[derive(Debug)]
+pub struct GfxBackend {
+    // None from losing the device or surface until they are rebuilt
+    pub resources: Option<ResourceHolder>,
+    pub surface_extent: Extent2D,
+    pub swapchain_dirty: bool,
+    // the atlas texture was created again and has to be uploaded whether it changed or not
+    pub atlas_stale: bool,
+    pub lost: Option<GraphicsLoss>,
+    window: WindowHandle,
+    config: GraphicsConfig,
+    // indexed like `PIPELINES`, with the versions last seen and the ones that worked, which
+    // rebuilt resources start out with
+    shaders: Vec<(Handle<Shader>, Handle<Shader>)>,
+    versions: Vec<(u32, u32)>,
+    spirv: Vec<(Arc<Shader>, Arc<Shader>)>,
+}
+
+impl GfxBackend {
+    pub fn new(
+        window: &Window,
+        extent: Extent2D,
+        config: &GraphicsConfig,
+        assets: &AssetManager,
+    ) -> Result<Self, ()> {
+        let window = WindowHandle(window.raw_window_handle());
+        let shaders: Vec<_> = PIPELINES
+            .iter()
+            .map(|pipeline| {
+                (
+                    assets.load::<Shader>(pipeline.vertex),
+                    assets.load::<Shader>(pipeline.fragment),
+                )
+            })
+            .collect();
+        let mut spirv = vec![];
+        for ((vertex, fragment), (pipeline, interface)) in shaders
+            .iter()
+            .zip(PIPELINES.iter().zip(&pipeline_interfaces()))
+        {
+            let (vertex, fragment) = assets
+                .wait(vertex)
+                .and_then(|vertex| Ok((vertex, assets.wait(fragment)?)))
+                .and_then(|(vertex, fragment)| {
+                    pipeline.validate(&vertex.spirv, &fragment.spirv, interface)?;
+                    Ok((vertex, fragment))
+                })
+                .map_err(|error| log::error!("Unusable shaders, {}", error))?;
+            spirv.push((vertex, fragment));
+        }
+        let versions = shaders
+            .iter()
+            .map(|(vertex, fragment)| (vertex.version(), fragment.version()))
+            .collect();
+        Ok(Self {
+            resources: Some(
+                ResourceHolder::new(&window, config, &spirv).map_err(|error| {
+                    log::error!("Failed to create graphics resources, {:?}", error)
+                })?,
+            ),
+            surface_extent: extent,
+            swapchain_dirty: true,
+            atlas_stale: false,
+            lost: None,
+            window,
+            config: config.clone(),
+            shaders,
+            versions,
+            spirv,
+        })
+    }
+
+    // rebuilds the pipelines whose shaders were reloaded since
+    fn reload_shaders(&mut self) -> Result<(), FrameError> {
+        let resources = match &mut self.resources {
+            Some(resources) => &mut resources.0,
+            None => return Ok(()),
+        };
+        let pipelines = self
+            .shaders
+            .iter()
+            .zip(&mut self.versions)
+            .zip(&mut self.spirv);
+        for (index, (((vertex, fragment), versions), spirv)) in pipelines.enumerate() {
+            let latest = (vertex.version(), fragment.version());
+            if latest == *versions {
+                continue;
+            }
+            *versions = latest;
+            if let (Some(vertex), Some(fragment)) = (vertex.get(), fragment.get()) {
+                if resources.reload_pipeline(index, &vertex, &fragment)? {
+                    *spirv = (vertex, fragment);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // everything is dropped straight away, the device may not outlive the loss for long
+    fn lose(&mut self, loss: GraphicsLoss) {
+        log::warn!("Graphics {:?} lost, rebuilding resources", loss);
+        self.resources = None;
+        self.lost = Some(loss);
+    }
+
+    // tries once per frame until it succeeds, returning what was recovered from
+    fn rebuild(&mut self) -> Option<GraphicsLoss> {
+        let loss = self.lost?;
+        match ResourceHolder::new(&self.window, &self.config, &self.spirv) {
+            Ok(resources) => {
+                log::info!("Recovered from graphics {:?} loss", loss);
+                self.resources = Some(resources);
+                self.lost = None;
+                self.swapchain_dirty = true;
+                self.atlas_stale = true;
+                Some(loss)
+            }
+            Err(error) => {
+                log::warn!(
+                    "Failed to rebuild graphics resources, retrying next frame, {:?}",
+                    error
+                );
+                None
+            }
+        }
+    }
+
+    fn draw_frame(&mut self, frame: &Frame) -> Result<(), FrameError> {
+        let resources: &mut Resources<_> = match &mut self.resources {
+            Some(resources) => &mut resources.0,
+            None => return Ok(()),
+        };
+        let extent = &mut self.surface_extent;
+        let swapchain_dirty = &mut self.swapchain_dirty;
+        if *swapchain_dirty {
+            *extent = resources.configure_swapchain(*extent)?;
+            *swapchain_dirty = false;
+        }
+        let Resources {
+            adapter,
+            command_buffers,
+            command_pool,
+            depth_buffer,
+            descriptor_sets,
+            device,
+            fences,
+            frame: frame_count,
+            framebuffers,
+            pipeline_layouts,
+            pipelines,
+            queue_group,
+            render_passes,
+            semaphores,
+            surface,
+            textures,
+            ..
+        } = resources;
+        *frame_count += 1;
+        let frame_index = *frame_count as usize % fences.len();
+        let fence = &fences[frame_index];
+        unsafe {
+            // We refuse to wait more than a second, to avoid hanging.
+            let render_timeout_ns = 1_000_000_000;
+            if !device.wait_for_fence(fence, render_timeout_ns)? {
+                return Err(FrameError::Skipped(
+                    "Timed out waiting for the previous frame".to_string(),
+                ));
+            }
+            // the GPU is done with everything this frame slot recorded last time
+            if let Some(framebuffer) = framebuffers[frame_index].take() {
+                device.destroy_framebuffer(framebuffer);
+            }
+        }
+        // before acquiring, a failed upload leaves no image that is never presented
+        if frame.atlas.dirty || self.atlas_stale {
+            unsafe {
+                // frames still in flight may be sampling the atlas
+                device.wait_idle()?;
+                textures[0].upload(
+                    device,
+                    &adapter.physical_device,
+                    command_pool,
+                    &mut queue_group.queues[0],
+                    &frame.atlas.pixels,
+                )?;
+            }
+            self.atlas_stale = false;
+        }
+        let surface_image = unsafe {
+            // We refuse to wait more than a second, to avoid hanging.
+            let acquire_timeout_ns = 1_000_000_000;
+            let (image, suboptimal) = surface.acquire_image(acquire_timeout_ns)?;
+            *swapchain_dirty |= suboptimal.is_some();
+            image
+        };
+        let framebuffer = unsafe {
+            device.create_framebuffer(
+                &render_passes[0],
+                vec![
+                    surface_image.borrow(),
+                    &depth_buffer
+                        .as_ref()
+                        .expect("Swapchain is not configured")
+                        .view,
+                ],
+                Extent {
+                    width: extent.width,
+                    height: extent.height,
+                    depth: 1,
+                },
+            )
+        };
+        let framebuffer = match framebuffer {
+            Ok(framebuffer) => framebuffer,
+            Err(error) => {
+                // the acquired image is never presented, a new swapchain gets it back
+                *swapchain_dirty = true;
+                return Err(FrameError::Skipped(format!(
+                    "Failed to create framebuffer: {:?}",
+                    error
+                )));
+            }
+        };
+        let command_buffer = &mut command_buffers[frame_index];
+        let semaphore = &semaphores[frame_index];
+        // only reset once something is going to be submitted, otherwise the next frame
+        // would wait on a fence that never gets signalled
+        unsafe {
+            if let Err(error) = device.reset_fence(fence) {
+                device.destroy_framebuffer(framebuffer);
+                *swapchain_dirty = true;
+                return Err(error.into());
+            }
+            command_buffer.reset(false);
+        }
+        let viewport = {
+            Viewport {
+                rect: Rect {
+                    x: 0,
+                    y: 0,
+                    w: extent.width as i16,
+                    h: extent.height as i16,
+                },
+                depth: 0.0..1.0,
+            }
+        };
+
+        unsafe {
+            command_buffer.begin_primary(CommandBufferFlags::ONE_TIME_SUBMIT);
+            record_frame::<Backend>(
+                command_buffer,
+                &render_passes[0],
+                &framebuffer,
+                &viewport,
+                frame,
+                pipelines,
+                pipeline_layouts,
+                descriptor_sets,
+            );
+            command_buffer.finish();
+        }
+
+        let submission = Submission {
+            command_buffers: vec![&*command_buffer],
+            wait_semaphores: None,
+            signal_semaphores: vec![semaphore],
+        };
+        let queue = &mut queue_group.queues[0];
+        framebuffers[frame_index] = Some(framebuffer);
+        unsafe {
+            queue.submit(submission, Some(fence));
+            let suboptimal = queue.present(surface, surface_image, Some(semaphore))?;
+            *swapchain_dirty |= suboptimal.is_some();
+        }
+        Ok(())
+    }
+}
+
+impl RenderBackend for GfxBackend {
+    fn resize(&mut self, extent: Extent2D) {
+        self.surface_extent = extent;
+        self.swapchain_dirty = true;
+    }
+
+    fn draw(&mut self, frame: &Frame) -> Option<GraphicsLoss> {
+        let recovered = self.rebuild();
+        match self.reload_shaders().and_then(|()| self.draw_frame(frame)) {
+            Ok(()) => {}
+            Err(FrameError::OutOfDate) => self.swapchain_dirty = true,
+            Err(FrameError::Skipped(reason)) => log::warn!("Skipped frame, {}", reason),
+            Err(FrameError::Lost(loss)) => self.lose(loss),
+        }
+        recovered
+    }
+
+    // gfx-hal doesn't expose swapchain images, so the frame is drawn again into a target
+    // that can be copied from. Everything is created for the one read, which blocks
+    fn read_pixels(&mut self, frame: &Frame) -> Result<Image, String> {
+        match self.read_frame(frame) {
+            Ok(image) => Ok(image),
+            Err(FrameError::OutOfDate) => {
+                self.swapchain_dirty = true;
+                Err("the swapchain is out of date".to_string())
+            }
+            Err(FrameError::Skipped(reason)) => Err(reason),
+            Err(FrameError::Lost(loss)) => {
+                self.lose(loss);
+                Err(format!("graphics {:?} lost", loss))
+            }
+        }
+    }
+}
+
+impl GfxBackend {
+    fn read_frame(&mut self, frame: &Frame) -> Result<Image, FrameError> {
+        let Extent2D { width, height } = self.surface_extent;
+        let resources = match &mut self.resources {
+            Some(resources) => &mut resources.0,
+            None => return Err(FrameError::Skipped("No graphics resources".to_string())),
+        };
+        let Resources {
+            adapter,
+            command_pool,
+            depth_format,
+            descriptor_sets,
+            device,
+            pipeline_layouts,
+            pipelines,
+            queue_group,
+            render_passes,
+            surface_color_format,
+            ..
+        } = &mut **resources;
+        let bgra = match surface_color_format.base_format().0 {
+            SurfaceType::R8_G8_B8_A8 => false,
+            SurfaceType::B8_G8_R8_A8 => true,
+            _ => {
+                return Err(FrameError::Skipped(format!(
+                    "Can't read back {:?} frames",
+                    surface_color_format
+                )))
+            }
+        };
+        let physical_device = &adapter.physical_device;
+        let viewport = Viewport {
+            rect: Rect {
+                x: 0,
+                y: 0,
+                w: width as i16,
+                h: height as i16,
+            },
+            depth: 0.0..1.0,
+        };
+        let mut pixels = unsafe {
+            let target = ColorTarget::<Backend>::new(
+                device,
+                physical_device,
+                *surface_color_format,
+                width,
+                height,
+            )?;
+            // whatever was created is destroyed again whether the read worked or not
+            let pixels =
+                DepthBuffer::<Backend>::new(device, physical_device, *depth_format, width, height)
+                    .and_then(|depth| {
+                        let pixels = device
+                            .create_framebuffer(
+                                &render_passes[1],
+                                vec![&target.view, &depth.view],
+                                Extent {
+                                    width,
+                                    height,
+                                    depth: 1,
+                                },
+                            )
+                            .map_err(|error| {
+                                FrameError::skipped("Failed to create framebuffer", error)
+                            })
+                            .and_then(|framebuffer| {
+                                let pixels = target.read(
+                                    device,
+                                    physical_device,
+                                    command_pool,
+                                    &mut queue_group.queues[0],
+                                    |command_buffer| {
+                                        record_frame::<Backend>(
+                                            command_buffer,
+                                            &render_passes[1],
+                                            &framebuffer,
+                                            &viewport,
+                                            frame,
+                                            pipelines,
+                                            pipeline_layouts,
+                                            descriptor_sets,
+                                        )
+                                    },
+                                );
+                                device.destroy_framebuffer(framebuffer);
+                                pixels
+                            });
+                        depth.destroy(device);
+                        pixels
+                    });
+            target.destroy(device);
+            pixels?
+        };
+        if bgra {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        Ok(Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+// clears and draws `frame` in one render pass
+#[allow(clippy::too_many_arguments)]
+unsafe fn record_frame<B: gfx_hal::Backend>(
+    command_buffer: &mut B::CommandBuffer,
+    render_pass: &B::RenderPass,
+    framebuffer: &B::Framebuffer,
+    viewport: &Viewport,
+    frame: &Frame,
+    pipelines: &[B::GraphicsPipeline],
+    pipeline_layouts: &[B::PipelineLayout],
+    descriptor_sets: &[B::DescriptorSet],
+) {
+    command_buffer.set_viewports(0, std::slice::from_ref(viewport));
+    command_buffer.set_scissors(0, [viewport.rect]);
+    command_buffer.begin_render_pass(
+        render_pass,
+        framebuffer,
+        viewport.rect,
+        [
+            ClearValue {
+                color: ClearColor {
+                    float32: frame.clear_color,
+                },
+            },
+            ClearValue {
+                depth_stencil: ClearDepthStencil {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+            },
+        ],
+        SubpassContents::Inline,
+    );
+    let mut bound = None;
+    for draw in &frame.draws {
+        let pipeline = draw.pipeline();
+        if bound != Some(pipeline) {
+            command_buffer.bind_graphics_pipeline(&pipelines[pipeline]);
+            if let Draw::Sprite(_) = draw {
+                command_buffer.bind_graphics_descriptor_sets(
+                    &pipeline_layouts[pipeline],
+                    0,
+                    &descriptor_sets[0..1],
+                    &[],
+                );
+            }
+            bound = Some(pipeline);
+        }
+        match draw {
+            Draw::Triangle(triangle) => {
+                command_buffer.push_graphics_constants(
+                    &pipeline_layouts[pipeline],
+                    ShaderStageFlags::VERTEX,
+                    0,
+                    push_constant_bytes(triangle),
+                );
+                command_buffer.draw(0..3, 0..1);
+            }
+            Draw::Sprite(sprite) => {
+                command_buffer.push_graphics_constants(
+                    &pipeline_layouts[pipeline],
+                    ShaderStageFlags::VERTEX,
+                    0,
+                    push_constant_bytes(sprite),
+                );
+                command_buffer.draw(0..6, 0..1);
+            }
+            Draw::Line(line) => {
+                command_buffer.push_graphics_constants(
+                    &pipeline_layouts[pipeline],
+                    ShaderStageFlags::VERTEX,
+                    0,
+                    push_constant_bytes(line),
+                );
+                command_buffer.draw(0..2, 0..1);
+            }
+        }
+    }
+    command_buffer.end_render_pass();
+}
+
+unsafe fn push_constant_bytes<T>(push_constants: &T) -> &[u32] {
+    let size_in_bytes = std::mem::size_of::<T>();
+    let size_in_u32s = size_in_bytes / std::mem::size_of::<u32>();
+    let start_ptr = push_constants as *const T as *const u32;
+    std::slice::from_raw_parts(start_ptr, size_in_u32s)
+}
@@ -0,0 +1,115 @@
+#[cfg(feature = "gfx")]
+pub mod gfx;
+mod present;
+pub mod software;
+
+use super::camera::Matrix;
+use super::capture::Image;
+use super::shaders::reflect::{Field, ShaderLayout};
+use super::text::GlyphAtlas;
+use common::event::GraphicsLoss;
+use common::render::Color;
+use gfx_hal::window::Extent2D;
+use std::fmt::Debug;
+
+// everything a backend needs to draw one frame, prepared by the renderer
+#[derive(Debug)]
+pub struct Frame<'a> {
+    pub clear_color: Color,
+    // back to front, the depth of each draw is already part of its matrix
+    pub draws: Vec<Draw>,
+    // sprites sample it, upload it when `dirty`
+    pub atlas: &'a GlyphAtlas,
+}
+
+pub trait RenderBackend: Debug {
+    fn resize(&mut self, extent: Extent2D);
+    // a device or surface loss is recovered from by rebuilding everything, which is reported
+    // by the first frame drawn afterwards
+    fn draw(&mut self, frame: &Frame) -> Option<GraphicsLoss>;
+    // the pixels of `frame`, which has just been drawn, the error says why they couldn't be read
+    fn read_pixels(&mut self, frame: &Frame) -> Result<Image, String>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Draw {
+    Triangle(PushConstants),
+    Sprite(SpritePushConstants),
+    Line(LinePushConstants),
+}
+
+impl Draw {
+    // index into the backend's pipelines
+    pub fn pipeline(&self) -> usize {
+        match self {
+            Draw::Triangle(_) => 0,
+            Draw::Sprite(_) => 1,
+            Draw::Line(_) => 2,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PushConstants {
+    pub view_projection: Matrix,
+    pub color: [f32; 4],
+    pub pos: [f32; 2],
+    pub scale: [f32; 2],
+}
+
+impl ShaderLayout for PushConstants {
+    fn fields() -> Vec<Field> {
+        let value = Self::default();
+        vec![
+            Field::new("view_projection", &value, &value.view_projection),
+            Field::new("color", &value, &value.color),
+            Field::new("pos", &value, &value.pos),
+            Field::new("scale", &value, &value.scale),
+        ]
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SpritePushConstants {
+    pub view_projection: Matrix,
+    pub color: [f32; 4],
+    pub pos: [f32; 2],
+    pub scale: [f32; 2],
+    pub uv: [f32; 4],
+}
+
+impl ShaderLayout for SpritePushConstants {
+    fn fields() -> Vec<Field> {
+        let value = Self::default();
+        vec![
+            Field::new("view_projection", &value, &value.view_projection),
+            Field::new("color", &value, &value.color),
+            Field::new("pos", &value, &value.pos),
+            Field::new("scale", &value, &value.scale),
+            Field::new("uv", &value, &value.uv),
+        ]
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct LinePushConstants {
+    pub view_projection: Matrix,
+    pub color: [f32; 4],
+    pub from: [f32; 2],
+    pub to: [f32; 2],
+}
+
+impl ShaderLayout for LinePushConstants {
+    fn fields() -> Vec<Field> {
+        let value = Self::default();
+        vec![
+            Field::new("view_projection", &value, &value.view_projection),
+            Field::new("color", &value, &value.color),
+            Field::new("from", &value, &value.from),
+            Field::new("to", &value, &value.to),
+        ]
+    }
+}
@@ -0,0 +1,235 @@
+use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+
+// copies RGBA8 pixels into a window with whatever the window system draws images with, no GPU
+// involved. Xlib and GDI are supported, other window systems fail to create one
+pub struct Presenter {
+    // the pixels as the window system takes them, BGRX for both
+    buffer: Vec<u8>,
+    target: Target,
+}
+
+impl Presenter {
+    pub fn new(window: &impl HasRawWindowHandle) -> Result<Self, String> {
+        Ok(Self {
+            buffer: vec![],
+            target: Target::new(window.raw_window_handle())?,
+        })
+    }
+
+    // `pixels` are rows of RGBA8 from the top left corner
+    pub fn present(&mut self, pixels: &[u8], width: u32, height: u32) -> Result<(), String> {
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        to_bgrx(pixels, &mut self.buffer);
+        self.target.blit(&mut self.buffer, width, height)
+    }
+}
+
+fn to_bgrx(rgba: &[u8], bgrx: &mut Vec<u8>) {
+    bgrx.resize(rgba.len(), 0);
+    for (to, from) in bgrx.chunks_exact_mut(4).zip(rgba.chunks_exact(4)) {
+        to.copy_from_slice(&[from[2], from[1], from[0], 255]);
+    }
+}
+
+impl std::fmt::Debug for Presenter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Presenter").finish()
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+use xlib::Target;
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod xlib {
+    use super::RawWindowHandle;
+    use std::os::raw::c_char;
+    use std::ptr;
+    use x11_dl::xlib::{self, Xlib};
+
+    pub struct Target {
+        xlib: Xlib,
+        display: *mut xlib::Display,
+        window: xlib::Window,
+        gc: xlib::GC,
+        visual: *mut xlib::Visual,
+        depth: i32,
+    }
+
+    impl Target {
+        pub fn new(handle: RawWindowHandle) -> Result<Self, String> {
+            let handle =
+                match handle {
+                    RawWindowHandle::Xlib(handle) if !handle.display.is_null() => handle,
+                    RawWindowHandle::Wayland(_) => return Err(
+                        "Can't present on Wayland, set WINIT_UNIX_BACKEND=x11 to use X11 instead"
+                            .to_string(),
+                    ),
+                    _ => return Err("Can only present on Xlib windows".to_string()),
+                };
+            let xlib = Xlib::open().map_err(|error| error.to_string())?;
+            let display = handle.display as *mut xlib::Display;
+            let mut attributes: xlib::XWindowAttributes = unsafe { std::mem::zeroed() };
+            if unsafe { (xlib.XGetWindowAttributes)(display, handle.window, &mut attributes) } == 0
+            {
+                return Err("Failed to read the window's attributes".to_string());
+            }
+            // BGRX in memory is what a true color visual with these masks takes
+            let visual = unsafe { &*attributes.visual };
+            let bgrx = visual.red_mask == 0xff_0000
+                && visual.green_mask == 0xff00
+                && visual.blue_mask == 0xff;
+            if attributes.depth < 24 || !bgrx {
+                return Err(format!(
+                    "Can't present on a {} bit visual with masks {:x} {:x} {:x}",
+                    attributes.depth, visual.red_mask, visual.green_mask, visual.blue_mask
+                ));
+            }
+            let gc = unsafe { (xlib.XCreateGC)(display, handle.window, 0, ptr::null_mut()) };
+            Ok(Self {
+                xlib,
+                display,
+                window: handle.window,
+                gc,
+                visual: attributes.visual,
+                depth: attributes.depth,
+            })
+        }
+
+        pub fn blit(&mut self, buffer: &mut [u8], width: u32, height: u32) -> Result<(), String> {
+            unsafe {
+                let image = (self.xlib.XCreateImage)(
+                    self.display,
+                    self.visual,
+                    self.depth as u32,
+                    xlib::ZPixmap,
+                    0,
+                    buffer.as_mut_ptr() as *mut c_char,
+                    width,
+                    height,
+                    32,
+                    0,
+                );
+                if image.is_null() {
+                    return Err("Failed to create an image to present".to_string());
+                }
+                (self.xlib.XPutImage)(
+                    self.display,
+                    self.window,
+                    self.gc,
+                    image,
+                    0,
+                    0,
+                    0,
+                    0,
+                    width,
+                    height,
+                );
+                // the pixels are the buffer's, only the image itself is freed
+                (*image).data = ptr::null_mut();
+                (self.xlib.XDestroyImage)(image);
+                (self.xlib.XFlush)(self.display);
+            }
+            Ok(())
+        }
+    }
+
+    impl Drop for Target {
+        fn drop(&mut self) {
+            unsafe { (self.xlib.XFreeGC)(self.display, self.gc) };
+        }
+    }
+}
+
+#[cfg(windows)]
+use gdi::Target;
+
+#[cfg(windows)]
+mod gdi {
+    use super::RawWindowHandle;
+    use std::mem;
+    use winapi::shared::windef::HWND;
+    use winapi::um::wingdi::{
+        SetDIBitsToDevice, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS,
+    };
+    use winapi::um::winuser::{GetDC, ReleaseDC};
+
+    pub struct Target {
+        window: HWND,
+    }
+
+    impl Target {
+        pub fn new(handle: RawWindowHandle) -> Result<Self, String> {
+            match handle {
+                RawWindowHandle::Windows(handle) => Ok(Self {
+                    window: handle.hwnd as HWND,
+                }),
+                _ => Err("Can only present on Win32 windows".to_string()),
+            }
+        }
+
+        pub fn blit(&mut self, buffer: &mut [u8], width: u32, height: u32) -> Result<(), String> {
+            let mut info: BITMAPINFO = unsafe { mem::zeroed() };
+            info.bmiHeader.biSize = mem::size_of::<BITMAPINFOHEADER>() as u32;
+            info.bmiHeader.biWidth = width as i32;
+            // negative for rows from the top
+            info.bmiHeader.biHeight = -(height as i32);
+            info.bmiHeader.biPlanes = 1;
+            info.bmiHeader.biBitCount = 32;
+            info.bmiHeader.biCompression = BI_RGB;
+            unsafe {
+                let context = GetDC(self.window);
+                if context.is_null() {
+                    return Err("Failed to get the window's device context".to_string());
+                }
+                let lines = SetDIBitsToDevice(
+                    context,
+                    0,
+                    0,
+                    width,
+                    height,
+                    0,
+                    0,
+                    0,
+                    height,
+                    buffer.as_ptr() as *const _,
+                    &info,
+                    DIB_RGB_COLORS,
+                );
+                ReleaseDC(self.window, context);
+                if lines == 0 {
+                    return Err("Failed to copy the pixels to the window".to_string());
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(any(windows, all(unix, not(target_os = "macos")))))]
+struct Target;
+
+#[cfg(not(any(windows, all(unix, not(target_os = "macos")))))]
+impl Target {
+    fn new(_: RawWindowHandle) -> Result<Self, String> {
+        Err("Can't present without a GPU on this platform".to_string())
+    }
+
+    fn blit(&mut self, _: &mut [u8], _: u32, _: u32) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swaps_red_and_blue() {
+        let mut bgrx = vec![9; 12];
+        to_bgrx(&[1, 2, 3, 4, 5, 6, 7, 0], &mut bgrx);
+        assert_eq!(bgrx, [3, 2, 1, 255, 7, 6, 5, 255]);
+    }
+}
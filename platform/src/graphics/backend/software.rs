@@ -0,0 +1,467 @@
+use super::present::Presenter;
+use super::{Draw, Frame, RenderBackend};
+use crate::graphics::camera::Matrix;
+use crate::graphics::capture::Image;
+use crate::graphics::text::GlyphAtlas;
+use common::event::GraphicsLoss;
+use gfx_hal::window::Extent2D;
+use raw_window_handle::HasRawWindowHandle;
+
+// the same geometry the vertex shaders generate from gl_VertexIndex
+const TRIANGLE: [[f32; 2]; 3] = [[0.0, -0.5], [-0.5, 0.5], [0.5, 0.5]];
+const CORNERS: [[f32; 2]; 6] = [
+    [0.0, 0.0],
+    [0.0, 1.0],
+    [1.0, 1.0],
+    [0.0, 0.0],
+    [1.0, 1.0],
+    [1.0, 0.0],
+];
+
+struct Vertex {
+    // pixels, with depth in 0..1
+    position: [f32; 3],
+    uv: [f32; 2],
+}
+
+// rasterizes frames on the CPU into an RGBA8 framebuffer, following the GPU pipelines: back
+// faces are culled, lines are one pixel wide, depth is tested with less-or-equal and everything
+// is alpha blended. Every frame is then blitted to the window, for machines without a GPU
+#[derive(Debug)]
+pub struct SoftwareBackend {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+    pub depth: Vec<f32>,
+    // without one frames are only drawn into `pixels`, like in tests
+    presenter: Option<Presenter>,
+}
+
+impl SoftwareBackend {
+    pub fn new(extent: Extent2D) -> Self {
+        let mut backend = Self {
+            width: 0,
+            height: 0,
+            pixels: vec![],
+            depth: vec![],
+            presenter: None,
+        };
+        backend.resize(extent);
+        backend
+    }
+
+    // the error says why the window can't be drawn into
+    pub fn with_window(window: &impl HasRawWindowHandle, extent: Extent2D) -> Result<Self, String> {
+        let presenter = Presenter::new(window)?;
+        Ok(Self {
+            presenter: Some(presenter),
+            ..Self::new(extent)
+        })
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let index = ((y * self.width + x) * 4) as usize;
+        let mut pixel = [0; 4];
+        pixel.copy_from_slice(&self.pixels[index..index + 4]);
+        pixel
+    }
+
+    fn clear(&mut self, color: [f32; 4]) {
+        let color = to_bytes(color);
+        for pixel in self.pixels.chunks_mut(4) {
+            pixel.copy_from_slice(&color);
+        }
+        for depth in &mut self.depth {
+            *depth = 1.0;
+        }
+    }
+
+    fn project(&self, view_projection: &Matrix, point: [f32; 2], uv: [f32; 2]) -> Vertex {
+        let m = view_projection;
+        let clip = [
+            m[0][0] * point[0] + m[1][0] * point[1] + m[3][0],
+            m[0][1] * point[0] + m[1][1] * point[1] + m[3][1],
+            m[0][2] * point[0] + m[1][2] * point[1] + m[3][2],
+            m[0][3] * point[0] + m[1][3] * point[1] + m[3][3],
+        ];
+        Vertex {
+            position: [
+                (clip[0] / clip[3] + 1.0) / 2.0 * self.width as f32,
+                (clip[1] / clip[3] + 1.0) / 2.0 * self.height as f32,
+                clip[2] / clip[3],
+            ],
+            uv,
+        }
+    }
+
+    fn rasterize<F>(&mut self, vertices: [&Vertex; 3], write_depth: bool, shade: F)
+    where
+        F: Fn([f32; 2]) -> [f32; 4],
+    {
+        let [a, b, c] = vertices;
+        let (a, b, c) = (a.position, b.position, c.position);
+        let area = edge(a, b, c);
+        // counter clockwise on screen is front facing, matching `Face::BACK` culling
+        if area >= 0.0 {
+            return;
+        }
+        let min_x = a[0].min(b[0]).min(c[0]).floor().max(0.0) as u32;
+        let min_y = a[1].min(b[1]).min(c[1]).floor().max(0.0) as u32;
+        let max_x = (a[0].max(b[0]).max(c[0]).ceil().max(0.0) as u32).min(self.width);
+        let max_y = (a[1].max(b[1]).max(c[1]).ceil().max(0.0) as u32).min(self.height);
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let point = [x as f32 + 0.5, y as f32 + 0.5, 0.0];
+                let edges = [(b, c), (c, a), (a, b)];
+                let weights = [
+                    edge(b, c, point) / area,
+                    edge(c, a, point) / area,
+                    edge(a, b, point) / area,
+                ];
+                let outside = weights.iter().zip(&edges).any(|(&weight, &(from, to))| {
+                    weight < 0.0 || weight == 0.0 && !top_left(from, to, area)
+                });
+                if outside {
+                    continue;
+                }
+                let depth = weights[0] * a[2] + weights[1] * b[2] + weights[2] * c[2];
+                let [va, vb, vc] = vertices;
+                let uv = [
+                    weights[0] * va.uv[0] + weights[1] * vb.uv[0] + weights[2] * vc.uv[0],
+                    weights[0] * va.uv[1] + weights[1] * vb.uv[1] + weights[2] * vc.uv[1],
+                ];
+                self.fragment(x, y, depth, write_depth, || shade(uv));
+            }
+        }
+    }
+
+    // one pixel per step along the major axis, like a diamond exit rule without the ends
+    fn rasterize_line(&mut self, from: &Vertex, to: &Vertex, color: [f32; 4]) {
+        let (a, b) = (from.position, to.position);
+        let steps = (b[0] - a[0]).abs().max((b[1] - a[1]).abs()).round() as u32;
+        for step in 0..steps {
+            let t = (step as f32 + 0.5) / steps as f32;
+            let x = a[0] + (b[0] - a[0]) * t;
+            let y = a[1] + (b[1] - a[1]) * t;
+            if x < 0.0 || y < 0.0 || x >= self.width as f32 || y >= self.height as f32 {
+                continue;
+            }
+            let depth = a[2] + (b[2] - a[2]) * t;
+            self.fragment(x as u32, y as u32, depth, false, || color);
+        }
+    }
+
+    fn fragment<F>(&mut self, x: u32, y: u32, depth: f32, write_depth: bool, shade: F)
+    where
+        F: FnOnce() -> [f32; 4],
+    {
+        let index = (y * self.width + x) as usize;
+        if depth > self.depth[index] {
+            return;
+        }
+        if write_depth {
+            self.depth[index] = depth;
+        }
+        let pixel = &mut self.pixels[index * 4..index * 4 + 4];
+        let blended = blend(shade(), from_bytes(pixel));
+        pixel.copy_from_slice(&to_bytes(blended));
+    }
+}
+
+impl RenderBackend for SoftwareBackend {
+    fn resize(&mut self, extent: Extent2D) {
+        self.width = extent.width;
+        self.height = extent.height;
+        let size = (extent.width * extent.height) as usize;
+        self.pixels = vec![0; size * 4];
+        self.depth = vec![1.0; size];
+    }
+
+    // nothing here can be lost
+    fn draw(&mut self, frame: &Frame) -> Option<GraphicsLoss> {
+        self.clear(frame.clear_color);
+        for draw in &frame.draws {
+            match draw {
+                Draw::Triangle(triangle) => {
+                    let vertices: Vec<Vertex> = TRIANGLE
+                        .iter()
+                        .map(|corner| {
+                            let point = [
+                                corner[0] * triangle.scale[0] + triangle.pos[0],
+                                corner[1] * triangle.scale[1] + triangle.pos[1],
+                            ];
+                            self.project(&triangle.view_projection, point, [0.0, 0.0])
+                        })
+                        .collect();
+                    let color = triangle.color;
+                    self.rasterize([&vertices[0], &vertices[1], &vertices[2]], true, |_| color);
+                }
+                Draw::Sprite(sprite) => {
+                    let vertices: Vec<Vertex> = CORNERS
+                        .iter()
+                        .map(|corner| {
+                            let point = [
+                                sprite.pos[0] + corner[0] * sprite.scale[0],
+                                sprite.pos[1] + corner[1] * sprite.scale[1],
+                            ];
+                            let uv = [
+                                sprite.uv[0] + (sprite.uv[2] - sprite.uv[0]) * corner[0],
+                                sprite.uv[1] + (sprite.uv[3] - sprite.uv[1]) * corner[1],
+                            ];
+                            self.project(&sprite.view_projection, point, uv)
+                        })
+                        .collect();
+                    let color = sprite.color;
+                    let atlas = frame.atlas;
+                    for triangle in vertices.chunks(3) {
+                        self.rasterize([&triangle[0], &triangle[1], &triangle[2]], false, |uv| {
+                            let texel = sample(atlas, uv);
+                            [
+                                color[0] * texel[0],
+                                color[1] * texel[1],
+                                color[2] * texel[2],
+                                color[3] * texel[3],
+                            ]
+                        });
+                    }
+                }
+                Draw::Line(line) => {
+                    let from = self.project(&line.view_projection, line.from, [0.0, 0.0]);
+                    let to = self.project(&line.view_projection, line.to, [0.0, 0.0]);
+                    self.rasterize_line(&from, &to, line.color);
+                }
+            }
+        }
+        if let Some(presenter) = &mut self.presenter {
+            if let Err(error) = presenter.present(&self.pixels, self.width, self.height) {
+                // the window keeps the last frame that made it
+                log::error!("Stopped presenting frames: {}", error);
+                self.presenter = None;
+            }
+        }
+        None
+    }
+
+    fn read_pixels(&mut self, _frame: &Frame) -> Result<Image, String> {
+        Ok(Image {
+            width: self.width,
+            height: self.height,
+            pixels: self.pixels.clone(),
+        })
+    }
+}
+
+// twice the signed area of abc, negative when counter clockwise on a y down screen
+fn edge(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> f32 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+// pixels exactly on an edge belong to the triangle it is a top or left edge of, so the
+// diagonal shared by the two halves of a quad is not blended twice
+fn top_left(from: [f32; 3], to: [f32; 3], area: f32) -> bool {
+    // points into the triangle
+    let normal = [(from[1] - to[1]) / area, (to[0] - from[0]) / area];
+    normal[0] > 0.0 || normal[0] == 0.0 && normal[1] > 0.0
+}
+
+// bilinear with clamped edges, like the atlas sampler
+fn sample(atlas: &GlyphAtlas, uv: [f32; 2]) -> [f32; 4] {
+    let x = (uv[0] * atlas.width as f32 - 0.5).max(0.0);
+    let y = (uv[1] * atlas.height as f32 - 0.5).max(0.0);
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+    let texel = |x: u32, y: u32| {
+        let (x, y) = (x.min(atlas.width - 1), y.min(atlas.height - 1));
+        let index = ((y * atlas.width + x) * 4) as usize;
+        from_bytes(&atlas.pixels[index..index + 4])
+    };
+    let (top_left, top_right) = (texel(x0, y0), texel(x0 + 1, y0));
+    let (bottom_left, bottom_right) = (texel(x0, y0 + 1), texel(x0 + 1, y0 + 1));
+    let mut color = [0.0; 4];
+    for channel in 0..4 {
+        let top = top_left[channel] + (top_right[channel] - top_left[channel]) * tx;
+        let bottom = bottom_left[channel] + (bottom_right[channel] - bottom_left[channel]) * tx;
+        color[channel] = top + (bottom - top) * ty;
+    }
+    color
+}
+
+// `BlendState::ALPHA`
+fn blend(source: [f32; 4], destination: [f32; 4]) -> [f32; 4] {
+    let alpha = source[3];
+    [
+        source[0] * alpha + destination[0] * (1.0 - alpha),
+        source[1] * alpha + destination[1] * (1.0 - alpha),
+        source[2] * alpha + destination[2] * (1.0 - alpha),
+        alpha + destination[3] * (1.0 - alpha),
+    ]
+}
+
+fn from_bytes(pixel: &[u8]) -> [f32; 4] {
+    [
+        f32::from(pixel[0]) / 255.0,
+        f32::from(pixel[1]) / 255.0,
+        f32::from(pixel[2]) / 255.0,
+        f32::from(pixel[3]) / 255.0,
+    ]
+}
+
+fn to_bytes(color: [f32; 4]) -> [u8; 4] {
+    let byte = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    [
+        byte(color[0]),
+        byte(color[1]),
+        byte(color[2]),
+        byte(color[3]),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::backend::{LinePushConstants, PushConstants, SpritePushConstants};
+    use crate::graphics::camera::screen_projection;
+
+    const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+    const IDENTITY: Matrix = [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ];
+
+    fn backend() -> SoftwareBackend {
+        SoftwareBackend::new(Extent2D {
+            width: 16,
+            height: 16,
+        })
+    }
+
+    fn draw(backend: &mut SoftwareBackend, draws: Vec<Draw>) {
+        let atlas = GlyphAtlas::new(8, 8);
+        backend.draw(&Frame {
+            clear_color: [0.0, 0.0, 0.0, 1.0],
+            draws,
+            atlas: &atlas,
+        });
+    }
+
+    fn with_depth(depth: f32) -> Matrix {
+        let mut matrix = IDENTITY;
+        matrix[3][2] = depth;
+        matrix
+    }
+
+    fn triangle(view_projection: Matrix, color: [f32; 4], scale: f32) -> Draw {
+        Draw::Triangle(PushConstants {
+            view_projection,
+            color,
+            pos: [0.0, 0.0],
+            scale: [scale, scale],
+        })
+    }
+
+    fn rect(view_projection: Matrix, color: [f32; 4]) -> Draw {
+        let atlas = GlyphAtlas::new(8, 8);
+        Draw::Sprite(SpritePushConstants {
+            view_projection,
+            color,
+            pos: [4.0, 4.0],
+            scale: [8.0, 8.0],
+            uv: atlas.white_uv(),
+        })
+    }
+
+    #[test]
+    fn draws_triangles() {
+        let mut backend = backend();
+        draw(&mut backend, vec![triangle(IDENTITY, RED, 1.0)]);
+        assert_eq!(backend.pixel(8, 10), [255, 0, 0, 255]);
+        // outside of the triangle
+        assert_eq!(backend.pixel(1, 1), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn culls_back_faces() {
+        let mut backend = backend();
+        let mut mirrored = IDENTITY;
+        mirrored[0][0] = -1.0;
+        draw(&mut backend, vec![triangle(mirrored, RED, 1.0)]);
+        assert_eq!(backend.pixel(8, 10), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn keeps_closer_geometry() {
+        let mut backend = backend();
+        let green = [0.0, 1.0, 0.0, 1.0];
+        draw(
+            &mut backend,
+            vec![
+                triangle(with_depth(0.2), RED, 1.0),
+                triangle(with_depth(0.5), green, 1.5),
+            ],
+        );
+        assert_eq!(backend.pixel(8, 10), [255, 0, 0, 255]);
+        // only the bigger, farther triangle covers this pixel
+        assert_eq!(backend.pixel(8, 13), [0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn blends_solid_sprites() {
+        let mut backend = backend();
+        let projection = screen_projection([16.0, 16.0]);
+        draw(
+            &mut backend,
+            vec![
+                rect(projection, [0.0, 0.0, 1.0, 1.0]),
+                rect(projection, [1.0, 0.0, 0.0, 0.5]),
+            ],
+        );
+        assert_eq!(backend.pixel(8, 8), [128, 0, 128, 255]);
+        assert_eq!(backend.pixel(2, 2), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn draws_lines_over_the_scene() {
+        let mut backend = backend();
+        let mut behind = screen_projection([16.0, 16.0]);
+        behind[3][2] = 0.5;
+        let mut front = screen_projection([16.0, 16.0]);
+        front[3][2] = 0.1;
+        let white = [1.0, 1.0, 1.0, 1.0];
+        draw(
+            &mut backend,
+            vec![
+                rect(behind, RED),
+                Draw::Line(LinePushConstants {
+                    view_projection: front,
+                    color: white,
+                    from: [2.0, 8.5],
+                    to: [14.0, 8.5],
+                }),
+            ],
+        );
+        assert_eq!(backend.pixel(2, 8), [255, 255, 255, 255]);
+        assert_eq!(backend.pixel(8, 8), [255, 255, 255, 255]);
+        assert_eq!(backend.pixel(13, 8), [255, 255, 255, 255]);
+        assert_eq!(backend.pixel(14, 8), [0, 0, 0, 255]);
+        assert_eq!(backend.pixel(8, 9), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn reads_back_frames() {
+        let mut backend = backend();
+        let atlas = GlyphAtlas::new(8, 8);
+        let frame = Frame {
+            clear_color: [0.0, 0.0, 1.0, 1.0],
+            draws: vec![triangle(IDENTITY, RED, 1.0)],
+            atlas: &atlas,
+        };
+        backend.draw(&frame);
+        let image = backend.read_pixels(&frame).unwrap();
+        assert_eq!((image.width, image.height), (16, 16));
+        let index = (10 * 16 + 8) * 4;
+        assert_eq!(image.pixels[index..index + 4], [255, 0, 0, 255]);
+        assert_eq!(image.pixels[..4], [0, 0, 255, 255]);
+    }
+}
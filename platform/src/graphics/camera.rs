@@ -0,0 +1,183 @@
+// 2D orthographic camera. World and screen space both point y down, screen coordinates are
+// pixels from the top left corner of the viewport
+#[derive(Debug, Clone, PartialEq)]
+pub struct Camera {
+    pub position: [f32; 2],
+    pub zoom: f32,
+    // radians, turns the world clockwise on screen
+    pub rotation: f32,
+    pub viewport: [f32; 2],
+    pub units_per_pixel: f32,
+    // how quickly `follow` catches up, higher is snappier
+    pub follow_speed: f32,
+}
+
+pub type Matrix = [[f32; 4]; 4];
+
+impl Camera {
+    pub fn new(viewport: [f32; 2], units_per_pixel: f32) -> Self {
+        Self {
+            position: [0.0, 0.0],
+            zoom: 1.0,
+            rotation: 0.0,
+            viewport,
+            units_per_pixel,
+            follow_speed: 8.0,
+        }
+    }
+
+    // column major, maps world space to normalized device coordinates
+    pub fn view_projection(&self) -> Matrix {
+        let [[m00, m01], [m10, m11]] = self.linear();
+        let [x, y] = self.position;
+        [
+            [m00, m10, 0.0, 0.0],
+            [m01, m11, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [-(m00 * x + m01 * y), -(m10 * x + m11 * y), 0.0, 1.0],
+        ]
+    }
+
+    pub fn world_to_screen(&self, point: [f32; 2]) -> [f32; 2] {
+        let [[m00, m01], [m10, m11]] = self.linear();
+        let [x, y] = [point[0] - self.position[0], point[1] - self.position[1]];
+        let ndc = [m00 * x + m01 * y, m10 * x + m11 * y];
+        [
+            (ndc[0] + 1.0) / 2.0 * self.viewport[0],
+            (ndc[1] + 1.0) / 2.0 * self.viewport[1],
+        ]
+    }
+
+    pub fn screen_to_world(&self, point: [f32; 2]) -> [f32; 2] {
+        let [[m00, m01], [m10, m11]] = self.linear();
+        let ndc = [
+            point[0] / self.viewport[0] * 2.0 - 1.0,
+            point[1] / self.viewport[1] * 2.0 - 1.0,
+        ];
+        let determinant = m00 * m11 - m01 * m10;
+        [
+            self.position[0] + (m11 * ndc[0] - m01 * ndc[1]) / determinant,
+            self.position[1] + (m00 * ndc[1] - m10 * ndc[0]) / determinant,
+        ]
+    }
+
+    // eases towards `target`, independent of the frame rate
+    pub fn follow(&mut self, target: [f32; 2], delta_seconds: f32) {
+        let t = 1.0 - (-self.follow_speed * delta_seconds).exp();
+        self.position[0] += (target[0] - self.position[0]) * t;
+        self.position[1] += (target[1] - self.position[1]) * t;
+    }
+
+    pub fn world_units_per_pixel(&self) -> f32 {
+        self.units_per_pixel / self.zoom
+    }
+
+    // rotation followed by the world to NDC scale, row major
+    fn linear(&self) -> [[f32; 2]; 2] {
+        let pixels_per_unit = self.zoom / self.units_per_pixel;
+        let scale = [
+            pixels_per_unit * 2.0 / self.viewport[0],
+            pixels_per_unit * 2.0 / self.viewport[1],
+        ];
+        let (sin, cos) = self.rotation.sin_cos();
+        [
+            [scale[0] * cos, -scale[0] * sin],
+            [scale[1] * sin, scale[1] * cos],
+        ]
+    }
+}
+
+// maps pixels from the top left corner of the viewport to normalized device coordinates
+pub fn screen_projection(viewport: [f32; 2]) -> Matrix {
+    [
+        [2.0 / viewport[0], 0.0, 0.0, 0.0],
+        [0.0, 2.0 / viewport[1], 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [-1.0, -1.0, 0.0, 1.0],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: [f32; 2], expected: [f32; 2]) {
+        let distance = (actual[0] - expected[0]).hypot(actual[1] - expected[1]);
+        assert!(distance < 1e-4, "{:?} != {:?}", actual, expected);
+    }
+
+    fn transform(matrix: &Matrix, point: [f32; 2]) -> [f32; 2] {
+        [
+            matrix[0][0] * point[0] + matrix[1][0] * point[1] + matrix[3][0],
+            matrix[0][1] * point[0] + matrix[1][1] * point[1] + matrix[3][1],
+        ]
+    }
+
+    #[test]
+    fn centers_on_position() {
+        let mut camera = Camera::new([800.0, 600.0], 0.01);
+        camera.position = [3.0, -2.0];
+        assert_near(camera.world_to_screen([3.0, -2.0]), [400.0, 300.0]);
+        assert_near(
+            transform(&camera.view_projection(), [3.0, -2.0]),
+            [0.0, 0.0],
+        );
+    }
+
+    #[test]
+    fn keeps_aspect_ratio() {
+        let camera = Camera::new([800.0, 400.0], 0.01);
+        let origin = camera.world_to_screen([0.0, 0.0]);
+        let right = camera.world_to_screen([1.0, 0.0]);
+        let down = camera.world_to_screen([0.0, 1.0]);
+        assert_near([right[0] - origin[0], down[1] - origin[1]], [100.0, 100.0]);
+    }
+
+    #[test]
+    fn zoom_scales_units_per_pixel() {
+        let mut camera = Camera::new([100.0, 100.0], 0.5);
+        camera.zoom = 2.0;
+        assert_eq!(camera.world_units_per_pixel(), 0.25);
+        assert_near(camera.screen_to_world([100.0, 50.0]), [12.5, 0.0]);
+    }
+
+    #[test]
+    fn converts_screen_to_world_and_back() {
+        let mut camera = Camera::new([640.0, 480.0], 0.02);
+        camera.position = [1.5, 4.0];
+        camera.zoom = 1.7;
+        camera.rotation = 0.6;
+        let world = camera.screen_to_world([100.0, 321.0]);
+        assert_near(camera.world_to_screen(world), [100.0, 321.0]);
+        assert_near(
+            transform(&camera.view_projection(), world),
+            [100.0 / 320.0 - 1.0, 321.0 / 240.0 - 1.0],
+        );
+    }
+
+    #[test]
+    fn rotates_clockwise() {
+        let mut camera = Camera::new([100.0, 100.0], 0.1);
+        camera.rotation = std::f32::consts::FRAC_PI_2;
+        // what was to the right of the center is now below it
+        assert_near(camera.world_to_screen([1.0, 0.0]), [50.0, 60.0]);
+    }
+
+    #[test]
+    fn follows_smoothly() {
+        let mut camera = Camera::new([100.0, 100.0], 0.1);
+        camera.follow([10.0, 0.0], 0.1);
+        assert!(camera.position[0] > 0.0 && camera.position[0] < 10.0);
+        for _ in 0..100 {
+            camera.follow([10.0, 0.0], 0.1);
+        }
+        assert_near(camera.position, [10.0, 0.0]);
+    }
+
+    #[test]
+    fn projects_screen_pixels() {
+        let projection = screen_projection([200.0, 100.0]);
+        assert_near(transform(&projection, [0.0, 0.0]), [-1.0, -1.0]);
+        assert_near(transform(&projection, [200.0, 100.0]), [1.0, 1.0]);
+    }
+}
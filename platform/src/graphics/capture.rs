@@ -0,0 +1,148 @@
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// RGBA8 pixels read back from a backend, rows from the top
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn save_png(&self, path: &Path) -> Result<(), String> {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)
+                .map_err(|error| format!("Failed to create {:?}: {}", directory, error))?;
+        }
+        let file = File::create(path)
+            .map_err(|error| format!("Failed to create {:?}: {}", path, error))?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&self.pixels))
+            .map_err(|error| format!("Failed to write {:?}: {}", path, error))
+    }
+}
+
+// every drawn frame is saved as a numbered image, frames advance a fixed virtual time so
+// that the sequence plays back at `fps` however long each frame took to render
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    pub directory: PathBuf,
+    pub fps: u32,
+    pub frame: u32,
+}
+
+impl Recording {
+    pub fn new(directory: PathBuf, fps: u32) -> Self {
+        Self {
+            directory,
+            fps,
+            frame: 0,
+        }
+    }
+
+    pub fn frame_seconds(&self) -> f32 {
+        1.0 / self.fps as f32
+    }
+
+    // the path for the next frame, advancing the frame counter
+    pub fn next_path(&mut self) -> PathBuf {
+        let path = self.directory.join(format!("frame-{:06}.png", self.frame));
+        self.frame += 1;
+        path
+    }
+}
+
+pub fn screenshot_path(directory: &Path, time: SystemTime) -> PathBuf {
+    directory.join(format!("screenshot-{}.png", timestamp(time)))
+}
+
+// UTC as YYYY-MM-DD_HH-MM-SS.mmm, sortable and safe in file names
+pub fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    let seconds_of_day = seconds % 86400;
+    format!(
+        "{:04}-{:02}-{:02}_{:02}-{:02}-{:02}.{:03}",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+// days since 1970-01-01 to a proleptic gregorian date, after Howard Hinnant's algorithm
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn formats_timestamps() {
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01_00-00-00.000");
+        // 2000-02-29 13:45:07.250, a leap day
+        let time = UNIX_EPOCH + Duration::from_millis(951_831_907_250);
+        assert_eq!(timestamp(time), "2000-02-29_13-45-07.250");
+        let path = screenshot_path(Path::new("screenshots"), time);
+        assert_eq!(
+            path,
+            Path::new("screenshots/screenshot-2000-02-29_13-45-07.250.png")
+        );
+    }
+
+    #[test]
+    fn numbers_recorded_frames() {
+        let mut recording = Recording::new(PathBuf::from("capture"), 30);
+        assert_eq!(recording.next_path(), Path::new("capture/frame-000000.png"));
+        assert_eq!(recording.next_path(), Path::new("capture/frame-000001.png"));
+        assert!((recording.frame_seconds() - 1.0 / 30.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn saves_png() {
+        let image = Image {
+            width: 2,
+            height: 1,
+            pixels: vec![255, 0, 0, 255, 0, 0, 255, 128],
+        };
+        let path = std::env::temp_dir()
+            .join(format!("capture-test-{}", std::process::id()))
+            .join("image.png");
+        image.save_png(&path).unwrap();
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let (info, mut reader) = decoder.read_info().unwrap();
+        let mut pixels = vec![0; info.buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert_eq!((info.width, info.height), (2, 1));
+        assert_eq!(pixels, image.pixels);
+    }
+}
@@ -0,0 +1,209 @@
+use super::adapter::AdapterSelection;
+use common::settings::{boolean, float, integer, invalid, positive, rate, string};
+use std::path::PathBuf;
+use toml::value::{Table, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BackendKind {
+    // gfx-hal on whichever api the platform was built with
+    Gpu,
+    // rasterizes on the CPU and blits the frames to the window, for when there's no GPU
+    Software,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PresentMode {
+    // waits for vertical blank, never tears
+    Vsync,
+    // presents right away, may tear
+    Immediate,
+    // replaces the queued image, no tearing and no waiting
+    Mailbox,
+    // vsync unless a frame is late, then it tears instead of waiting
+    Adaptive,
+}
+
+#[derive(Debug, Clone)]
+pub struct GraphicsConfig {
+    // tried in order until one initializes
+    pub backends: Vec<BackendKind>,
+    pub adapter: AdapterSelection,
+    // falls back to vsync when the surface doesn't support it
+    pub present_mode: PresentMode,
+    // frames per second, on top of whatever the present mode limits to
+    pub max_fps: Option<f32>,
+    // F9 toggles it at runtime
+    pub show_frame_stats: bool,
+    // how many frames the CPU may record ahead of the GPU
+    pub frames_in_flight: usize,
+    // compiled pipelines are loaded from and saved to this file to speed up startup
+    pub pipeline_cache: Option<PathBuf>,
+    // world units covered by one pixel at zoom 1
+    pub units_per_pixel: f32,
+    // F12 saves a screenshot here, F10 records numbered frames to a timestamped subdirectory
+    pub capture_directory: PathBuf,
+    // virtual frame rate of recordings
+    pub capture_fps: u32,
+}
+
+impl Default for GraphicsConfig {
+    fn default() -> Self {
+        Self {
+            backends: vec![BackendKind::Gpu, BackendKind::Software],
+            adapter: AdapterSelection::default(),
+            present_mode: PresentMode::Vsync,
+            max_fps: None,
+            show_frame_stats: false,
+            frames_in_flight: 2,
+            pipeline_cache: None,
+            // the default window shows the same two units across as the old NDC coordinates
+            units_per_pixel: 1.0 / 256.0,
+            capture_directory: PathBuf::from("captures"),
+            capture_fps: 60,
+        }
+    }
+}
+
+const BACKEND_NAMES: &[(BackendKind, &str)] = &[
+    (BackendKind::Gpu, "gpu"),
+    (BackendKind::Software, "software"),
+];
+
+const PRESENT_MODE_NAMES: &[(PresentMode, &str)] = &[
+    (PresentMode::Vsync, "vsync"),
+    (PresentMode::Immediate, "immediate"),
+    (PresentMode::Mailbox, "mailbox"),
+    (PresentMode::Adaptive, "adaptive"),
+];
+
+fn named<T: Copy>(names: &[(T, &str)], value: &Value) -> Result<T, String> {
+    let known: Vec<&str> = names.iter().map(|(_, name)| *name).collect();
+    names
+        .iter()
+        .find(|(_, name)| value.as_str() == Some(name))
+        .map(|(item, _)| *item)
+        .ok_or_else(|| format!("{}, it's one of {}", invalid(value), known.join(", ")))
+}
+
+fn name_of<T: PartialEq>(names: &[(T, &'static str)], item: &T) -> &'static str {
+    names
+        .iter()
+        .find(|(named, _)| named == item)
+        .map(|(_, name)| *name)
+        .expect("Every variant is named")
+}
+
+impl GraphicsConfig {
+    // one key of the `graphics` section, the error says what's wrong with the value
+    pub fn set(&mut self, key: &str, value: &Value) -> Result<(), String> {
+        match key {
+            "backends" => {
+                let backends = value.as_array().ok_or_else(|| invalid(value))?;
+                if backends.is_empty() {
+                    return Err(format!("{}, it needs at least one backend", invalid(value)));
+                }
+                self.backends = backends
+                    .iter()
+                    .map(|backend| named(BACKEND_NAMES, backend))
+                    .collect::<Result<_, _>>()?;
+            }
+            // an empty name picks by kind alone
+            "adapter" => {
+                let name = string(value)?;
+                self.adapter.name = Some(name).filter(|name| !name.is_empty());
+            }
+            "present_mode" => self.present_mode = named(PRESENT_MODE_NAMES, value)?,
+            // 0 for no limit
+            "max_fps" => {
+                self.max_fps = match value.as_integer() {
+                    Some(0) => None,
+                    _ => Some(rate(value)?),
+                }
+            }
+            "show_frame_stats" => self.show_frame_stats = boolean(value)?,
+            "frames_in_flight" => self.frames_in_flight = integer(value, 1, 3)?,
+            // an empty path for none
+            "pipeline_cache" => {
+                let path = string(value)?;
+                self.pipeline_cache = Some(path)
+                    .filter(|path| !path.is_empty())
+                    .map(PathBuf::from);
+            }
+            "units_per_pixel" => self.units_per_pixel = positive(value)?,
+            "capture_directory" => self.capture_directory = string(value)?.into(),
+            "capture_fps" => self.capture_fps = integer(value, 1, 240)?,
+            _ => return Err("isn't a setting".to_string()),
+        }
+        Ok(())
+    }
+
+    pub fn to_table(&self) -> Table {
+        let mut table = Table::new();
+        let backends = self
+            .backends
+            .iter()
+            .map(|backend| name_of(BACKEND_NAMES, backend).into())
+            .collect();
+        table.insert("backends".to_string(), Value::Array(backends));
+        let adapter = self.adapter.name.clone().unwrap_or_default();
+        table.insert("adapter".to_string(), adapter.into());
+        let present_mode = name_of(PRESENT_MODE_NAMES, &self.present_mode);
+        table.insert("present_mode".to_string(), present_mode.into());
+        let max_fps = self.max_fps.map_or(Value::Integer(0), float);
+        table.insert("max_fps".to_string(), max_fps);
+        table.insert("show_frame_stats".to_string(), self.show_frame_stats.into());
+        table.insert(
+            "frames_in_flight".to_string(),
+            (self.frames_in_flight as i64).into(),
+        );
+        let pipeline_cache = self
+            .pipeline_cache
+            .as_ref()
+            .map_or(String::new(), |path| path.to_string_lossy().into_owned());
+        table.insert("pipeline_cache".to_string(), pipeline_cache.into());
+        table.insert("units_per_pixel".to_string(), float(self.units_per_pixel));
+        table.insert(
+            "capture_directory".to_string(),
+            self.capture_directory.to_string_lossy().into_owned().into(),
+        );
+        table.insert("capture_fps".to_string(), (self.capture_fps as i64).into());
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falls_back_to_software() {
+        assert_eq!(
+            GraphicsConfig::default().backends,
+            [BackendKind::Gpu, BackendKind::Software]
+        );
+    }
+
+    #[test]
+    fn round_trips_through_a_table() {
+        let mut config = GraphicsConfig::default();
+        config
+            .set("backends", &Value::Array(vec!["software".into()]))
+            .unwrap();
+        config.set("max_fps", &Value::Integer(30)).unwrap();
+        config.set("adapter", &"Intel".into()).unwrap();
+        config.set("present_mode", &"mailbox".into()).unwrap();
+        let mut copy = GraphicsConfig::default();
+        for (key, value) in &config.to_table() {
+            copy.set(key, value).unwrap();
+        }
+        assert_eq!(copy.backends, [BackendKind::Software]);
+        assert_eq!(copy.max_fps, Some(30.0));
+        assert_eq!(copy.adapter.name.as_deref(), Some("Intel"));
+        assert_eq!(copy.present_mode, PresentMode::Mailbox);
+        assert_eq!(copy.to_table(), config.to_table());
+        let error = config.set("present_mode", &"fast".into()).unwrap_err();
+        assert!(error.contains("vsync, immediate"), "{}", error);
+        assert!(config.set("backends", &Value::Array(vec![])).is_err());
+        assert!(config.set("frames_in_flight", &Value::Integer(0)).is_err());
+    }
+}
@@ -0,0 +1,76 @@
+pub use common::render::Layer;
+
+const LAYER_COUNT: u32 = 4;
+const Z_STEPS: u32 = 1 << 16;
+
+// depth buffer value in 0..1, later layers and higher `z` are closer to the camera
+pub fn depth(layer: Layer, z: i16) -> f32 {
+    let step = layer as u32 * Z_STEPS + (i32::from(z) - i32::from(i16::MIN)) as u32;
+    1.0 - (step + 1) as f32 / (LAYER_COUNT * Z_STEPS + 1) as f32
+}
+
+// collects drawables and hands them out back to front. Items with the same layer and z keep
+// the order they were pushed in, so overlapping transparent sprites blend predictably
+#[derive(Debug)]
+pub struct DrawQueue<T> {
+    items: Vec<(Layer, i16, T)>,
+}
+
+impl<T> Default for DrawQueue<T> {
+    fn default() -> Self {
+        Self { items: vec![] }
+    }
+}
+
+impl<T> DrawQueue<T> {
+    pub fn push(&mut self, layer: Layer, z: i16, item: T) {
+        self.items.push((layer, z, item));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    // items with their depth, sorted back to front
+    pub fn drain_sorted(&mut self) -> Vec<(f32, T)> {
+        // `sort_by_key` is stable
+        self.items.sort_by_key(|(layer, z, _)| (*layer, *z));
+        self.items
+            .drain(..)
+            .map(|(layer, z, item)| (depth(layer, z), item))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orders_depth_by_layer_then_z() {
+        assert!(depth(Layer::Background, i16::MAX) > depth(Layer::Entities, i16::MIN));
+        assert!(depth(Layer::Entities, 0) > depth(Layer::Entities, 1));
+        assert!(depth(Layer::Ui, i16::MAX) > 0.0);
+        assert!(depth(Layer::Background, i16::MIN) < 1.0);
+    }
+
+    #[test]
+    fn sorts_back_to_front_keeping_push_order() {
+        let mut queue = DrawQueue::default();
+        queue.push(Layer::Ui, 0, "label");
+        queue.push(Layer::Entities, 1, "first sprite");
+        queue.push(Layer::Background, 0, "sky");
+        queue.push(Layer::Entities, 1, "second sprite");
+        queue.push(Layer::Entities, -1, "shadow");
+        let order: Vec<&str> = queue
+            .drain_sorted()
+            .into_iter()
+            .map(|(_, item)| item)
+            .collect();
+        assert_eq!(
+            order,
+            vec!["sky", "shadow", "first sprite", "second sprite", "label"]
+        );
+        assert!(queue.is_empty());
+    }
+}
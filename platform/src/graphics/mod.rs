@@ -1,3 +1,16 @@
+pub mod adapter;
+pub mod backend;
+pub mod camera;
+pub mod capture;
+pub mod config;
+pub mod layer;
 pub mod renderer;
+#[cfg(feature = "gfx")]
 mod resources;
-mod shaders;
+// the software backend only needs the push constant layouts
+#[cfg_attr(not(feature = "gfx"), allow(dead_code))]
+pub(crate) mod shaders;
+pub mod text;
+#[cfg(feature = "gfx")]
+mod texture;
+pub mod timing;
@@ -1,186 +1,395 @@
-use super::resources::{PushConstants, ResourceHolder, Resources};
-use crate::window::Window;
-use gfx_hal::{
-    command::{ClearColor, ClearValue, CommandBuffer, CommandBufferFlags, SubpassContents},
-    device::Device,
-    image::Extent,
-    pool::CommandPool,
-    pso::{Rect, ShaderStageFlags, Viewport},
-    queue::{CommandQueue, Submission},
-    window::{Extent2D, PresentationSurface, Surface, SwapchainConfig},
+#[cfg(feature = "gfx")]
+use super::backend::gfx::GfxBackend;
+use super::backend::{
+    software::SoftwareBackend, Draw, Frame, LinePushConstants, PushConstants, RenderBackend,
+    SpritePushConstants,
 };
+use super::camera::{screen_projection, Camera, Matrix};
+use super::capture::{screenshot_path, timestamp, Recording};
+use super::config::{BackendKind, GraphicsConfig};
+use super::layer::DrawQueue;
+use super::text::{Align, Font, GlyphQuad, Space, Text, TextRenderer};
+use super::timing::{FrameLimiter, FrameStats, FrameSummary};
+use crate::assets::AssetManager;
+use crate::window::Window;
+use common::event::PlatformEvent;
+use common::render::{Color, RenderCommand, RenderCommands, ShapeKind};
+use gfx_hal::window::Extent2D;
 use queue::{event::Event, receiver::Receiver};
-use std::borrow::Borrow;
-use world::WorldState;
+use std::path::PathBuf;
+use std::time::{Instant, SystemTime};
 
 // TODO: remove winit dependency
-use winit::event::Event as WEvent;
+use winit::event::{ElementState, Event as WEvent, KeyboardInput, VirtualKeyCode, WindowEvent};
+use winit::event_loop::EventLoopProxy;
+
+// in the asset directory
+const FONT_PATH: &str = "fonts/DejaVuSansMono.ttf";
+const FONT_SIZE: f32 = 32.0;
 
 #[derive(Debug)]
 pub struct Renderer<'a> {
-    pub resources: ResourceHolder,
-    pub events: Receiver<Event<WEvent<'a, ()>>>,
+    pub backend: Box<dyn RenderBackend>,
+    pub events: Receiver<Event<WEvent<'a, PlatformEvent>>>,
+    // reaches the queue through the event loop like any window event
+    pub platform_events: EventLoopProxy<PlatformEvent>,
     pub surface_extent: Extent2D,
+    pub text: TextRenderer,
+    pub camera: Camera,
+    pub last_frame: Instant,
+    pub capture_directory: PathBuf,
+    pub capture_fps: u32,
+    // taken after the next frame is drawn
+    pub screenshot_requested: bool,
+    pub recording: Option<Recording>,
+    pub limiter: Option<FrameLimiter>,
+    pub stats: FrameStats,
+    pub show_frame_stats: bool,
 }
 
 impl<'a> Renderer<'a> {
-    pub fn new(window: &Window, events: Receiver<Event<WEvent<'a, ()>>>) -> Result<Self, ()> {
-        let resources = ResourceHolder::new(&window.window)?;
+    pub fn new(
+        window: &Window,
+        config: GraphicsConfig,
+        events: Receiver<Event<WEvent<'a, PlatformEvent>>>,
+        assets: &AssetManager,
+    ) -> Result<Self, ()> {
+        let extent = window.surface_extent;
+        let backend = config
+            .backends
+            .iter()
+            .find_map(|kind| make_backend(*kind, window, &config, assets))
+            .ok_or_else(|| log::error!("None of {:?} could be initialized", config.backends))?;
+        let viewport = [extent.width as f32, extent.height as f32];
 
         Ok(Self {
-            resources,
+            backend,
             events,
-            surface_extent: window.surface_extent,
+            platform_events: window.proxy.clone(),
+            surface_extent: extent,
+            text: TextRenderer::with_font(load_font(assets)),
+            camera: Camera::new(viewport, config.units_per_pixel),
+            last_frame: Instant::now(),
+            capture_directory: config.capture_directory.clone(),
+            capture_fps: config.capture_fps,
+            screenshot_requested: false,
+            recording: None,
+            limiter: config.max_fps.map(FrameLimiter::new),
+            stats: FrameStats::new(),
+            show_frame_stats: config.show_frame_stats,
         })
     }
 
-    pub fn update(&mut self, world: &WorldState) {
-        let mut extent = &mut self.surface_extent;
-        let mut resources = &mut self.resources;
-
+    pub fn update(&mut self, commands: &RenderCommands) {
         let event = self.events.try_recv().unwrap();
         match event.payload {
-            // redraw continiously
-            WEvent::MainEventsCleared => {
-                Renderer::draw(&mut resources, &world, &mut extent);
+            WEvent::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                self.surface_extent = Extent2D {
+                    width: size.width,
+                    height: size.height,
+                };
+                self.camera.viewport = [size.width as f32, size.height as f32];
+                self.backend.resize(self.surface_extent);
             }
+            WEvent::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(key),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => match key {
+                VirtualKeyCode::F9 => self.show_frame_stats = !self.show_frame_stats,
+                VirtualKeyCode::F12 => self.screenshot(),
+                VirtualKeyCode::F10 if self.recording.is_some() => self.stop_recording(),
+                VirtualKeyCode::F10 => {
+                    let directory = self.capture_directory.join(timestamp(SystemTime::now()));
+                    self.start_recording(directory, self.capture_fps);
+                }
+                _ => {}
+            },
+            // redraw continiously, unless there is nothing to draw to
+            WEvent::MainEventsCleared if !self.is_minimized() => self.draw(commands),
             _ => {}
         }
     }
 
-    fn draw(resources: &mut ResourceHolder, world: &WorldState, extent: &mut Extent2D) {
-        let resources: &mut Resources<_> = &mut resources.0;
-        let Resources {
-            adapter,
-            command_buffer,
-            command_pool,
-            device,
-            frame,
-            pipeline_layouts,
-            pipelines,
-            queue_group,
-            render_passes,
-            rendering_complete_semaphore: semaphore,
-            submission_complete_fence: fence,
-            surface,
-            surface_color_format,
-            ..
-        } = resources;
-        *frame += 1;
-        println!("FRAME {}", frame);
-        unsafe {
-            // We refuse to wait more than a second, to avoid hanging.
-            let render_timeout_ns = 1_000_000_000;
-            device
-                .wait_for_fence(&fence, render_timeout_ns)
-                .expect("Out of memory or device lost");
-            device.reset_fence(&fence).expect("Out of memory");
-            command_pool.reset(false);
-        }
-        let caps = surface.capabilities(&adapter.physical_device);
-        let mut swapchain_config =
-            SwapchainConfig::from_caps(&caps, *surface_color_format, *extent);
-        // This seems to fix some fullscreen slowdown on macOS.
-        if caps.image_count.contains(&3) {
-            swapchain_config.image_count = 3;
+    pub fn screenshot(&mut self) {
+        self.screenshot_requested = true;
+    }
+
+    pub fn start_recording(&mut self, directory: PathBuf, fps: u32) {
+        log::info!("Recording frames at {} fps to {:?}", fps, directory);
+        self.recording = Some(Recording::new(directory, fps));
+    }
+
+    pub fn stop_recording(&mut self) {
+        if let Some(recording) = self.recording.take() {
+            log::info!(
+                "Recorded {} frames to {:?}",
+                recording.frame,
+                recording.directory
+            );
         }
-        *extent = swapchain_config.extent;
-        unsafe {
-            surface
-                .configure_swapchain(&device, swapchain_config)
-                .expect("Failed to configure swapchain");
+    }
+
+    pub fn frame_stats(&self) -> FrameSummary {
+        self.stats.summary()
+    }
+
+    pub fn set_max_fps(&mut self, max_fps: Option<f32>) {
+        self.limiter = max_fps.map(FrameLimiter::new);
+    }
+
+    pub fn is_minimized(&self) -> bool {
+        self.surface_extent.width == 0 || self.surface_extent.height == 0
+    }
+
+    fn draw(&mut self, commands: &RenderCommands) {
+        let now = Instant::now();
+        let delta_seconds = match &self.recording {
+            Some(recording) => recording.frame_seconds(),
+            None => (now - self.last_frame).as_secs_f32(),
         };
-        let surface_image = unsafe {
-            // We refuse to wait more than a second, to avoid hanging.
-            let acquire_timeout_ns = 1_000_000_000;
-            match surface.acquire_image(acquire_timeout_ns) {
-                Ok((image, _)) => image,
-                Err(_) => {
-                    return;
+        self.last_frame = now;
+        self.stats.tick(now);
+        for command in commands.iter() {
+            match command {
+                RenderCommand::Camera(view) => {
+                    self.camera.zoom = view.zoom;
+                    self.camera.rotation = view.rotation;
+                    self.camera.follow(view.focus, delta_seconds);
                 }
+                RenderCommand::Text(command) => self.text.queue(command.clone()),
+                _ => {}
             }
+        }
+        if self.show_frame_stats {
+            let stats = self.stats.summary();
+            let mut overlay = Text::new(
+                format!(
+                    "{:.0} fps  avg {:.2} ms  p99 {:.2} ms  hitches {}",
+                    stats.fps, stats.average, stats.p99, stats.hitches
+                ),
+                [self.camera.viewport[0] - 8.0, 8.0],
+                16.0,
+            );
+            overlay.align = Align::Right;
+            self.text.queue(overlay);
+        }
+        let glyphs = self.text.flush();
+        let (clear_color, draws) = self.prepare(commands, &glyphs);
+        let frame = Frame {
+            clear_color,
+            draws,
+            atlas: &self.text.atlas,
         };
-        let framebuffer = unsafe {
-            device
-                .create_framebuffer(
-                    &render_passes[0],
-                    vec![surface_image.borrow()],
-                    Extent {
-                        width: extent.width,
-                        height: extent.height,
-                        depth: 1,
-                    },
-                )
-                .unwrap()
-        };
-        let viewport = {
-            Viewport {
-                rect: Rect {
-                    x: 0,
-                    y: 0,
-                    w: extent.width as i16,
-                    h: extent.height as i16,
-                },
-                depth: 0.0..1.0,
+        if let Some(loss) = self.backend.draw(&frame) {
+            let event = PlatformEvent::GraphicsRecovered(loss);
+            if self.platform_events.send_event(event).is_err() {
+                log::warn!("Event loop closed before recovering from {:?} loss", loss);
             }
-        };
+        }
+        if self.screenshot_requested || self.recording.is_some() {
+            let paths: Vec<PathBuf> = self
+                .screenshot_requested
+                .then(|| screenshot_path(&self.capture_directory, SystemTime::now()))
+                .into_iter()
+                .chain(self.recording.as_mut().map(Recording::next_path))
+                .collect();
+            match self.backend.read_pixels(&frame) {
+                Ok(image) => {
+                    for path in paths {
+                        if let Err(error) = image.save_png(&path) {
+                            log::warn!("{}", error);
+                        }
+                    }
+                }
+                Err(error) => log::warn!("Failed to capture the frame, {}", error),
+            }
+            self.screenshot_requested = false;
+        }
+        self.text.atlas.dirty = false;
+        if let Some(limiter) = &mut self.limiter {
+            limiter.wait();
+        }
+    }
 
-        let small = [0.33, 0.33];
-        let triangles = &[
-            // Red triangle
-            PushConstants {
-                color: [1.0, 0.0, 0.0, 1.0],
-                pos: [world.player.0, world.player.1],
-                scale: small,
-            },
-        ];
-        unsafe {
-            command_buffer.begin_primary(CommandBufferFlags::ONE_TIME_SUBMIT);
-            command_buffer.set_viewports(0, &[viewport.clone()]);
-            command_buffer.set_scissors(0, &[viewport.rect]);
-            command_buffer.begin_render_pass(
-                &render_passes[0],
-                &framebuffer,
-                viewport.rect,
-                &[ClearValue {
-                    color: ClearColor {
-                        float32: [0.0, 0.0, 0.0, 1.0],
+    // turns commands and laid out glyphs into draws sorted back to front
+    fn prepare(&self, commands: &RenderCommands, glyphs: &[GlyphQuad]) -> (Color, Vec<Draw>) {
+        let extent = &self.surface_extent;
+        let view_projection = self.camera.view_projection();
+        let mut clear_color = [0.0, 0.0, 0.0, 1.0];
+        let mut draws = DrawQueue::default();
+        for command in commands.iter() {
+            match command {
+                RenderCommand::Clear(color) => clear_color = *color,
+                RenderCommand::Shape(shape) => match shape.kind {
+                    ShapeKind::Triangle => draws.push(
+                        shape.layer,
+                        shape.z,
+                        Draw::Triangle(PushConstants {
+                            view_projection,
+                            color: shape.color,
+                            pos: shape.position,
+                            scale: shape.scale,
+                        }),
+                    ),
+                },
+                RenderCommand::Sprite(sprite) => draws.push(
+                    sprite.layer,
+                    sprite.z,
+                    Draw::Sprite(sprite_push_constants(
+                        Quad {
+                            position: sprite.position,
+                            size: sprite.size,
+                            uv: self.text.atlas.white_uv(),
+                            color: sprite.color,
+                            space: sprite.space,
+                        },
+                        view_projection,
+                        extent,
+                    )),
+                ),
+                RenderCommand::Line(line) => draws.push(
+                    line.layer,
+                    line.z,
+                    Draw::Line(LinePushConstants {
+                        view_projection: projection(line.space, view_projection, extent),
+                        color: line.color,
+                        from: line.from,
+                        to: line.to,
+                    }),
+                ),
+                // already queued, their glyphs are below
+                RenderCommand::Camera(_) | RenderCommand::Text(_) => {}
+            }
+        }
+        for glyph in glyphs {
+            draws.push(
+                glyph.layer,
+                glyph.z,
+                Draw::Sprite(sprite_push_constants(
+                    Quad {
+                        position: glyph.position,
+                        size: glyph.size,
+                        uv: glyph.uv,
+                        color: glyph.color,
+                        space: glyph.space,
                     },
-                }],
-                SubpassContents::Inline,
+                    view_projection,
+                    extent,
+                )),
             );
-            command_buffer.bind_graphics_pipeline(&pipelines[0]);
-            for triangle in triangles {
-                command_buffer.push_graphics_constants(
-                    &pipeline_layouts[0],
-                    ShaderStageFlags::VERTEX,
-                    0,
-                    push_constant_bytes(triangle),
-                );
-                command_buffer.draw(0..3, 0..1);
-            }
-            command_buffer.finish();
         }
+        let draws = draws
+            .drain_sorted()
+            .into_iter()
+            .map(|(depth, draw)| match draw {
+                Draw::Triangle(mut triangle) => {
+                    triangle.view_projection = with_depth(triangle.view_projection, depth);
+                    Draw::Triangle(triangle)
+                }
+                Draw::Sprite(mut sprite) => {
+                    sprite.view_projection = with_depth(sprite.view_projection, depth);
+                    Draw::Sprite(sprite)
+                }
+                Draw::Line(mut line) => {
+                    line.view_projection = with_depth(line.view_projection, depth);
+                    Draw::Line(line)
+                }
+            })
+            .collect();
+        (clear_color, draws)
+    }
+}
 
-        let submission = Submission {
-            command_buffers: vec![&command_buffer],
-            wait_semaphores: None,
-            signal_semaphores: vec![&semaphore],
-        };
-        let queue = &mut queue_group.queues[0];
-        unsafe {
-            queue.submit(submission, Some(&fence));
-            queue
-                .present(surface, surface_image, Some(&semaphore))
-                .unwrap();
-            &device.destroy_framebuffer(framebuffer);
+// read through the manager, so that it's found in the archive too
+fn load_font(assets: &AssetManager) -> Option<Font> {
+    let font = assets
+        .read(FONT_PATH)
+        .and_then(|data| Font::from_data(data, FONT_SIZE).map_err(|error| format!("{:?}", error)));
+    match font {
+        Ok(font) => Some(font),
+        Err(error) => {
+            log::warn!("Failed to load font {}: {}", FONT_PATH, error);
+            None
         }
     }
 }
 
-unsafe fn push_constant_bytes<T>(push_constants: &T) -> &[u32] {
-    let size_in_bytes = std::mem::size_of::<T>();
-    let size_in_u32s = size_in_bytes / std::mem::size_of::<u32>();
-    let start_ptr = push_constants as *const T as *const u32;
-    std::slice::from_raw_parts(start_ptr, size_in_u32s)
+// only the gpu backend needs the config and shaders
+#[cfg_attr(not(feature = "gfx"), allow(unused_variables))]
+fn make_backend(
+    kind: BackendKind,
+    window: &Window,
+    config: &GraphicsConfig,
+    assets: &AssetManager,
+) -> Option<Box<dyn RenderBackend>> {
+    let extent = window.surface_extent;
+    match kind {
+        #[cfg(feature = "gfx")]
+        BackendKind::Gpu => match GfxBackend::new(&window.window, extent, config, assets) {
+            Ok(backend) => Some(Box::new(backend)),
+            Err(()) => {
+                log::warn!("GPU backend failed to initialize");
+                None
+            }
+        },
+        #[cfg(not(feature = "gfx"))]
+        BackendKind::Gpu => {
+            log::warn!("Built without a GPU backend");
+            None
+        }
+        BackendKind::Software => match SoftwareBackend::with_window(&window.window, extent) {
+            Ok(backend) => Some(Box::new(backend)),
+            Err(error) => {
+                log::warn!("Software backend failed to initialize: {}", error);
+                None
+            }
+        },
+    }
+}
+
+// shaders place geometry at z 0, so the translation's z becomes the depth
+fn with_depth(mut view_projection: Matrix, depth: f32) -> Matrix {
+    view_projection[3][2] = depth;
+    view_projection
+}
+
+fn projection(space: Space, view_projection: Matrix, extent: &Extent2D) -> Matrix {
+    match space {
+        Space::Screen => screen_projection([extent.width as f32, extent.height as f32]),
+        Space::World => view_projection,
+    }
+}
+
+struct Quad {
+    position: [f32; 2],
+    size: [f32; 2],
+    uv: [f32; 4],
+    color: Color,
+    space: Space,
+}
+
+fn sprite_push_constants(
+    quad: Quad,
+    view_projection: Matrix,
+    extent: &Extent2D,
+) -> SpritePushConstants {
+    SpritePushConstants {
+        view_projection: projection(quad.space, view_projection, extent),
+        color: quad.color,
+        pos: quad.position,
+        scale: quad.size,
+        uv: quad.uv,
+    }
 }
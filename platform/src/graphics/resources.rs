@@ -5,28 +5,129 @@ use gfx_backend_metal as back;
 #[cfg(feature = "vulkan")]
 use gfx_backend_vulkan as back;
 
+pub type Backend = back::Backend;
+
+#[cfg(feature = "dx12")]
+const DRIVER: &str = "dx12";
+#[cfg(feature = "metal")]
+const DRIVER: &str = "metal";
+#[cfg(feature = "vulkan")]
+const DRIVER: &str = "vulkan";
+
 use super::super::APP_NAME;
+use super::adapter::{report, AdapterSummary};
+use super::backend::{LinePushConstants, PushConstants, SpritePushConstants};
+use super::config::{GraphicsConfig, PresentMode};
+use super::shaders::reflect::Interface;
+use super::shaders::PIPELINES;
+use super::text::ATLAS_SIZE;
+use super::texture::{depth_format, DepthBuffer, Texture};
+use crate::assets::kinds::Shader;
+use common::event::GraphicsLoss;
 use gfx_hal::{
     adapter::{Adapter, PhysicalDevice},
     command::Level,
-    device::Device,
+    device::{Device, OomOrDeviceLost, OutOfMemory},
     format::{ChannelType, Format},
     image::Layout,
     pass::{Attachment, AttachmentLoadOp, AttachmentOps, AttachmentStoreOp, Subpass, SubpassDesc},
     pool::{CommandPool, CommandPoolCreateFlags},
     pso::{
-        BlendState, ColorBlendDesc, ColorMask, EntryPoint, Face, GraphicsPipelineDesc,
-        InputAssemblerDesc, Primitive, PrimitiveAssemblerDesc, Rasterizer, ShaderStageFlags,
-        Specialization,
+        BlendState, ColorBlendDesc, ColorMask, Comparison, DepthStencilDesc, DepthTest, Descriptor,
+        DescriptorPool, DescriptorPoolCreateFlags, DescriptorRangeDesc, DescriptorSetLayoutBinding,
+        DescriptorSetWrite, DescriptorType, EntryPoint, Face, GraphicsPipelineDesc,
+        ImageDescriptorType, InputAssemblerDesc, Primitive, PrimitiveAssemblerDesc, Rasterizer,
+        ShaderStageFlags, Specialization,
+    },
+    queue::{QueueFamily, QueueFamilyId, QueueGroup},
+    window::{
+        self, AcquireError, CreationError, Extent2D, PresentError, PresentationSurface, Surface,
+        SwapchainConfig,
     },
-    queue::{QueueFamily, QueueGroup},
-    window::{PresentationSurface, Surface},
     Features, Instance,
 };
-use shaderc::{Compiler, ShaderKind};
+use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+use std::fmt::Debug;
 use std::mem::ManuallyDrop;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::Arc;
 use winit::event::WindowEvent;
-use winit::window::Window;
+
+// the native window, kept so that a lost surface can be created again
+#[derive(Debug, Clone, Copy)]
+pub struct WindowHandle(pub RawWindowHandle);
+
+unsafe impl HasRawWindowHandle for WindowHandle {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        self.0
+    }
+}
+
+// why a frame couldn't be drawn
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrameError {
+    // the swapchain no longer matches the surface, configuring it again is enough
+    OutOfDate,
+    // nothing was drawn, the next frame may well succeed
+    Skipped(String),
+    // everything has to be created again
+    Lost(GraphicsLoss),
+}
+
+impl FrameError {
+    // for errors that can't tell a lost device from anything else
+    pub fn skipped(what: &str, error: impl Debug) -> Self {
+        FrameError::Skipped(format!("{}: {:?}", what, error))
+    }
+}
+
+impl From<OutOfMemory> for FrameError {
+    fn from(error: OutOfMemory) -> Self {
+        FrameError::Skipped(format!("{:?}", error))
+    }
+}
+
+impl From<OomOrDeviceLost> for FrameError {
+    fn from(error: OomOrDeviceLost) -> Self {
+        match error {
+            OomOrDeviceLost::DeviceLost(_) => FrameError::Lost(GraphicsLoss::Device),
+            error => FrameError::Skipped(format!("{:?}", error)),
+        }
+    }
+}
+
+impl From<AcquireError> for FrameError {
+    fn from(error: AcquireError) -> Self {
+        match error {
+            AcquireError::OutOfDate => FrameError::OutOfDate,
+            AcquireError::SurfaceLost(_) => FrameError::Lost(GraphicsLoss::Surface),
+            AcquireError::DeviceLost(_) => FrameError::Lost(GraphicsLoss::Device),
+            error => FrameError::Skipped(error.to_string()),
+        }
+    }
+}
+
+impl From<PresentError> for FrameError {
+    fn from(error: PresentError) -> Self {
+        match error {
+            PresentError::OutOfDate => FrameError::OutOfDate,
+            PresentError::SurfaceLost(_) => FrameError::Lost(GraphicsLoss::Surface),
+            PresentError::DeviceLost(_) => FrameError::Lost(GraphicsLoss::Device),
+            error => FrameError::Skipped(error.to_string()),
+        }
+    }
+}
+
+impl From<CreationError> for FrameError {
+    fn from(error: CreationError) -> Self {
+        match error {
+            CreationError::SurfaceLost(_) => FrameError::Lost(GraphicsLoss::Surface),
+            CreationError::DeviceLost(_) => FrameError::Lost(GraphicsLoss::Device),
+            error => FrameError::Skipped(format!("Failed to configure swapchain: {:?}", error)),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Resources<B: gfx_hal::Backend> {
@@ -38,136 +139,308 @@ pub struct Resources<B: gfx_hal::Backend> {
     pub render_passes: Vec<B::RenderPass>,
     pub pipeline_layouts: Vec<B::PipelineLayout>,
     pub pipelines: Vec<B::GraphicsPipeline>,
-    // pub command_buffers: Vec<B::CommandBuffer>,
-    // pub fences: Vec<B::Fence>,
-    // pub semaphores: Vec<B::Semaphore>,
+    pub pipeline_cache: B::PipelineCache,
+    pub pipeline_cache_path: Option<PathBuf>,
+    pub descriptor_set_layouts: Vec<B::DescriptorSetLayout>,
+    pub descriptor_pool: B::DescriptorPool,
+    pub descriptor_sets: Vec<B::DescriptorSet>,
+    pub textures: Vec<Texture<B>>,
     pub command_pool: B::CommandPool,
-
-    pub command_buffer: B::CommandBuffer,
-    pub submission_complete_fence: B::Fence,
-    pub rendering_complete_semaphore: B::Semaphore,
+    // one of each per frame in flight. gfx-hal's presentation surface synchronizes image
+    // acquisition on its own and takes no semaphore, so only rendering completion is signalled
+    pub command_buffers: Vec<B::CommandBuffer>,
+    pub fences: Vec<B::Fence>,
+    pub semaphores: Vec<B::Semaphore>,
+    pub framebuffers: Vec<Option<B::Framebuffer>>,
     pub surface_color_format: Format,
+    pub depth_format: Format,
+    // created by `configure_swapchain` once the extent is known
+    pub depth_buffer: Option<DepthBuffer<B>>,
+    pub present_mode: PresentMode,
     pub frame: u64,
     pub events: Vec<WindowEvent<'static>>,
 }
 
-impl Resources<back::Backend> {
-    pub fn new(window: &Window) -> Result<Self, ()> {
-        let (instance, surface, adapter) = {
-            let instance = back::Instance::create(APP_NAME, 1).expect("Backend not supported");
-            let surface = unsafe {
-                instance
-                    .create_surface(window)
-                    .expect("Failed to create surface for window")
-            };
-            let adapter = instance.enumerate_adapters().remove(0);
-            (instance, surface, adapter)
+impl<B: gfx_hal::Backend> Resources<B> {
+    // everything frames are drawn with, every object is pushed as soon as it's created
+    unsafe fn create_objects(
+        &mut self,
+        frames_in_flight: usize,
+        shaders: &PipelineSpirv,
+    ) -> Result<(), FrameError> {
+        for _ in 0..frames_in_flight {
+            let command_buffer = self.command_pool.allocate_one(Level::Primary);
+            self.command_buffers.push(command_buffer);
+            self.fences.push(self.device.create_fence(true)?);
+            self.semaphores.push(self.device.create_semaphore()?);
+            self.framebuffers.push(None);
+        }
+        // the same attachments, so the pipelines work in both, but the second leaves the color
+        // ready to be copied from
+        for &layout in &[Layout::Present, Layout::TransferSrcOptimal] {
+            let render_pass = make_render_pass::<B>(
+                &self.device,
+                self.surface_color_format,
+                self.depth_format,
+                layout,
+            )?;
+            self.render_passes.push(render_pass);
+        }
+        self.descriptor_set_layouts
+            .push(self.device.create_descriptor_set_layout(
+                &[DescriptorSetLayoutBinding {
+                    binding: 0,
+                    ty: SAMPLED_IMAGE,
+                    count: 1,
+                    stage_flags: ShaderStageFlags::FRAGMENT,
+                    immutable_samplers: false,
+                }],
+                &[],
+            )?);
+        self.pipeline_layouts.push(
+            self.device
+                .create_pipeline_layout(&[], &push_constants::<PushConstants>())?,
+        );
+        self.pipeline_layouts
+            .push(self.device.create_pipeline_layout(
+                vec![&self.descriptor_set_layouts[0]],
+                &push_constants::<SpritePushConstants>(),
+            )?);
+        self.pipeline_layouts.push(
+            self.device
+                .create_pipeline_layout(&[], &push_constants::<LinePushConstants>())?,
+        );
+        let atlas = Texture::<B>::new(
+            &self.device,
+            &self.adapter.physical_device,
+            ATLAS_SIZE,
+            ATLAS_SIZE,
+        )?;
+        self.textures.push(atlas);
+        let atlas_descriptor_set = self
+            .descriptor_pool
+            .allocate_set(&self.descriptor_set_layouts[0])
+            .map_err(|error| FrameError::skipped("Failed to allocate descriptor set", error))?;
+        self.device.write_descriptor_sets(vec![DescriptorSetWrite {
+            set: &atlas_descriptor_set,
+            binding: 0,
+            array_offset: 0,
+            descriptors: Some(Descriptor::CombinedImageSampler(
+                &self.textures[0].view,
+                Layout::ShaderReadOnlyOptimal,
+                &self.textures[0].sampler,
+            )),
+        }]);
+        self.descriptor_sets.push(atlas_descriptor_set);
+        for (index, (vertex, fragment)) in shaders.iter().enumerate() {
+            let inputs = self.pipeline_inputs(index);
+            let pipeline = make_pipeline(&self.device, &inputs, &vertex.spirv, &fragment.spirv)
+                .map_err(FrameError::Skipped)?;
+            self.pipelines.push(pipeline);
+        }
+        Ok(())
+    }
+
+    fn pipeline_inputs(&self, index: usize) -> PipelineInputs<'_, B> {
+        PipelineInputs {
+            render_pass: &self.render_passes[0],
+            layout: &self.pipeline_layouts[index],
+            cache: &self.pipeline_cache,
+            transparent: TRANSPARENT[index],
+            primitive: PRIMITIVES[index],
+        }
+    }
+
+    // returns the extent the surface actually got, which can differ from the requested one
+    pub fn configure_swapchain(&mut self, extent: Extent2D) -> Result<Extent2D, FrameError> {
+        let caps = self.surface.capabilities(&self.adapter.physical_device);
+        let mut swapchain_config =
+            SwapchainConfig::from_caps(&caps, self.surface_color_format, extent);
+        swapchain_config.present_mode = present_mode(self.present_mode, caps.present_modes);
+        // This seems to fix some fullscreen slowdown on macOS.
+        if caps.image_count.contains(&3) {
+            swapchain_config.image_count = 3;
+        }
+        let extent = swapchain_config.extent;
+        unsafe {
+            // images of the old swapchain may still be in use
+            self.device.wait_idle()?;
+            self.surface
+                .configure_swapchain(&self.device, swapchain_config)?;
+            if let Some(depth_buffer) = self.depth_buffer.take() {
+                depth_buffer.destroy(&self.device);
+            }
+            self.depth_buffer = Some(DepthBuffer::new(
+                &self.device,
+                &self.adapter.physical_device,
+                self.depth_format,
+                extent.width,
+                extent.height,
+            )?);
         };
-        let (device, queue_group) = {
-            let queue_family = adapter
-                .queue_families
-                .iter()
-                .find(|family| {
+        Ok(extent)
+    }
+
+    // rebuilds the pipeline at `index` from reloaded shaders, a broken shader is logged and
+    // the previous pipeline stays in use. True when the pipeline was replaced
+    pub fn reload_pipeline(
+        &mut self,
+        index: usize,
+        vertex: &Shader,
+        fragment: &Shader,
+    ) -> Result<bool, FrameError> {
+        let shaders = &PIPELINES[index];
+        let pipeline = shaders
+            .validate(
+                &vertex.spirv,
+                &fragment.spirv,
+                &pipeline_interfaces()[index],
+            )
+            .and_then(|()| unsafe {
+                let inputs = self.pipeline_inputs(index);
+                make_pipeline(&self.device, &inputs, &vertex.spirv, &fragment.spirv)
+            });
+        match pipeline {
+            Ok(pipeline) => unsafe {
+                // frames in flight may still be drawing with the old one
+                if let Err(error) = self.device.wait_idle() {
+                    self.device.destroy_graphics_pipeline(pipeline);
+                    return Err(error.into());
+                }
+                let old = std::mem::replace(&mut self.pipelines[index], pipeline);
+                self.device.destroy_graphics_pipeline(old);
+                log::info!("Reloaded {} and {}", shaders.vertex, shaders.fragment);
+                Ok(true)
+            },
+            Err(error) => {
+                log::error!("Keeping previous pipeline, {}", error);
+                Ok(false)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ResourceHolder(pub ManuallyDrop<Resources<back::Backend>>);
+
+impl ResourceHolder {
+    // the shaders have to be validated against `pipeline_interfaces` already. Objects are kept
+    // in the holder as soon as they're created, so a failure destroys the ones made before it
+    pub fn new(
+        window: &impl HasRawWindowHandle,
+        config: &GraphicsConfig,
+        shaders: &PipelineSpirv,
+    ) -> Result<Self, FrameError> {
+        let instance = back::Instance::create(APP_NAME, 1).map_err(|error| {
+            FrameError::skipped(&format!("Failed to create {} instance", DRIVER), error)
+        })?;
+        let surface = unsafe { instance.create_surface(window) }
+            .map_err(|error| FrameError::skipped("Failed to create surface for window", error))?;
+        let mut adapters: Vec<Option<Adapter<back::Backend>>> = instance
+            .enumerate_adapters()
+            .into_iter()
+            .map(Some)
+            .collect();
+        let summaries: Vec<AdapterSummary> = adapters
+            .iter()
+            .flatten()
+            .map(|adapter| AdapterSummary::new(adapter, DRIVER))
+            .collect();
+        log::info!("{}", report(&summaries));
+        // the first adapter in order of preference that can present and open a device
+        let (adapter, device, queue_group) = config
+            .adapter
+            .rank(&summaries)
+            .into_iter()
+            .find_map(|index| {
+                let adapter = adapters[index].take()?;
+                let queue_family = adapter.queue_families.iter().find(|family| {
                     surface.supports_queue_family(family) && family.queue_type().supports_graphics()
-                })
-                .expect("No compatible queue family found");
-            let mut gpu = unsafe {
-                adapter
-                    .physical_device
-                    .open(&[(queue_family, &[1.0])], Features::empty())
-                    .expect("Failed to open device")
-            };
-            (gpu.device, gpu.queue_groups.pop().unwrap())
-        };
-        let (command_pool, command_buffer) = unsafe {
-            let mut command_pool = device
-                .create_command_pool(queue_group.family, CommandPoolCreateFlags::empty())
-                .expect("Out of memory");
-            let command_buffer = command_pool.allocate_one(Level::Primary);
-            (command_pool, command_buffer)
-        };
+                });
+                let queue_family = match queue_family {
+                    Some(queue_family) => queue_family,
+                    None => {
+                        log::warn!("{} can't present to the window", adapter.info.name);
+                        return None;
+                    }
+                };
+                let gpu = unsafe {
+                    adapter
+                        .physical_device
+                        .open(&[(queue_family, &[1.0])], Features::empty())
+                };
+                match gpu {
+                    Ok(mut gpu) => {
+                        let queue_group = gpu.queue_groups.pop().unwrap();
+                        Some((adapter, gpu.device, queue_group))
+                    }
+                    Err(error) => {
+                        log::warn!("Failed to open {}: {:?}", adapter.info.name, error);
+                        None
+                    }
+                }
+            })
+            .ok_or_else(|| FrameError::Skipped(format!("No usable {} adapter", DRIVER)))?;
+        log::info!("Using {}", adapter.info.name);
         let surface_color_format = {
             let supported_formats = surface
                 .supported_formats(&adapter.physical_device)
                 .unwrap_or(vec![]);
-            let default_format = *supported_formats.get(0).unwrap_or(&Format::Rgba8Srgb);
+            let default_format = *supported_formats.first().unwrap_or(&Format::Rgba8Srgb);
             supported_formats
                 .into_iter()
                 .find(|format| format.base_format().1 == ChannelType::Srgb)
                 .unwrap_or(default_format)
         };
-        let render_pass = {
-            let color_attachment = Attachment {
-                format: Some(surface_color_format),
-                samples: 1,
-                ops: AttachmentOps::new(AttachmentLoadOp::Clear, AttachmentStoreOp::Store),
-                stencil_ops: AttachmentOps::DONT_CARE,
-                layouts: Layout::Undefined..Layout::Present,
-            };
-            let subpass = SubpassDesc {
-                colors: &[(0, Layout::ColorAttachmentOptimal)],
-                depth_stencil: None,
-                inputs: &[],
-                resolves: &[],
-                preserves: &[],
-            };
-            unsafe {
-                device
-                    .create_render_pass(&[color_attachment], &[subpass], &[])
-                    .expect("Out of memory")
-            }
-        };
-        let pipeline_layout = unsafe {
-            let push_constant_bytes = std::mem::size_of::<PushConstants>() as u32;
-            device
-                .create_pipeline_layout(&[], &[(ShaderStageFlags::VERTEX, 0..push_constant_bytes)])
-                .expect("Out of memory")
-        };
-        let vertex_shader = include_str!("./shaders/vertex/vs.vert");
-        let fragment_shader = include_str!("./shaders/fragment/fs.frag");
-        let pipeline = unsafe {
-            make_pipeline::<back::Backend>(
+        let depth_format = depth_format::<back::Backend>(&adapter.physical_device)?;
+        let (command_pool, descriptor_pool, pipeline_cache) = unsafe {
+            make_pools::<back::Backend>(
                 &device,
-                &render_pass,
-                &pipeline_layout,
-                vertex_shader,
-                fragment_shader,
-            )
+                queue_group.family,
+                config.pipeline_cache.as_ref(),
+            )?
         };
-        let submission_complete_fence = device.create_fence(true).expect("Out of memory");
-        let rendering_complete_semaphore = device.create_semaphore().expect("Out of memory");
-        Ok(Self {
+        let mut holder = Self(ManuallyDrop::new(Resources {
             instance,
             surface,
+            adapter,
             device,
-            render_passes: vec![render_pass],
-            pipeline_layouts: vec![pipeline_layout],
-            pipelines: vec![pipeline],
+            queue_group,
+            render_passes: vec![],
+            pipeline_layouts: vec![],
+            pipelines: vec![],
+            pipeline_cache,
+            // only set once everything was created, a cache that's barely started isn't saved
+            pipeline_cache_path: None,
+            descriptor_set_layouts: vec![],
+            descriptor_pool,
+            descriptor_sets: vec![],
+            textures: vec![],
             command_pool,
-            submission_complete_fence,
-            rendering_complete_semaphore,
-            adapter,
+            command_buffers: vec![],
+            fences: vec![],
+            semaphores: vec![],
+            framebuffers: vec![],
             surface_color_format,
-            command_buffer,
-            queue_group,
+            depth_format,
+            depth_buffer: None,
+            present_mode: config.present_mode,
             frame: u64::MIN,
             events: vec![],
-        })
-    }
-}
-
-#[derive(Debug)]
-pub struct ResourceHolder(pub ManuallyDrop<Resources<back::Backend>>);
-
-impl ResourceHolder {
-    pub fn new(window: &Window) -> Result<Self, ()> {
-        Ok(Self(ManuallyDrop::new(Resources::new(window)?)))
+        }));
+        unsafe { holder.0.create_objects(config.frames_in_flight, shaders)? };
+        holder.0.pipeline_cache_path = config.pipeline_cache.clone();
+        Ok(holder)
     }
 }
 
 impl Drop for ResourceHolder {
     fn drop(&mut self) {
         unsafe {
+            // a lost device has nothing left to wait for, everything is destroyed all the same
+            if let Err(error) = self.0.device.wait_idle() {
+                log::warn!("Failed to wait for the device: {:?}", error);
+            }
             let Resources {
                 instance,
                 mut surface,
@@ -176,23 +449,50 @@ impl Drop for ResourceHolder {
                 pipeline_layouts,
                 command_pool,
                 pipelines,
-                submission_complete_fence,
-                rendering_complete_semaphore,
-                // fences,
-                // semaphores,
+                pipeline_cache,
+                pipeline_cache_path,
+                descriptor_set_layouts,
+                descriptor_pool,
+                textures,
+                fences,
+                semaphores,
+                framebuffers,
+                depth_buffer,
                 ..
             } = ManuallyDrop::take(&mut self.0);
-            device.destroy_semaphore(rendering_complete_semaphore);
-            device.destroy_fence(submission_complete_fence);
-            // for semaphore in semaphores {
-            //     device.destroy_semaphore(semaphore);
-            // }
-            // for fence in fences {
-            //     device.destroy_fence(fence);
-            // }
+            for semaphore in semaphores {
+                device.destroy_semaphore(semaphore);
+            }
+            for fence in fences {
+                device.destroy_fence(fence);
+            }
+            for framebuffer in framebuffers.into_iter().flatten() {
+                device.destroy_framebuffer(framebuffer);
+            }
+            if let Some(depth_buffer) = depth_buffer {
+                depth_buffer.destroy(&device);
+            }
+            device.destroy_descriptor_pool(descriptor_pool);
+            for descriptor_set_layout in descriptor_set_layouts {
+                device.destroy_descriptor_set_layout(descriptor_set_layout);
+            }
+            for texture in textures {
+                texture.destroy(&device);
+            }
             for pipeline in pipelines {
                 device.destroy_graphics_pipeline(pipeline);
             }
+            if let Some(path) = pipeline_cache_path {
+                match device.get_pipeline_cache_data(&pipeline_cache) {
+                    Ok(data) => {
+                        if let Err(error) = std::fs::write(&path, data) {
+                            log::warn!("Failed to save pipeline cache {:?}: {}", path, error);
+                        }
+                    }
+                    Err(error) => log::warn!("Failed to read pipeline cache: {:?}", error),
+                }
+            }
+            device.destroy_pipeline_cache(pipeline_cache);
             for pipeline_layout in pipeline_layouts {
                 device.destroy_pipeline_layout(pipeline_layout);
             }
@@ -203,26 +503,138 @@ impl Drop for ResourceHolder {
             surface.unconfigure_swapchain(&device);
             instance.destroy_surface(surface);
         }
-        println!("resources cleared!");
+        log::debug!("Destroyed graphics resources");
+    }
+}
+
+// FIFO is the only mode every surface has to support
+fn present_mode(mode: PresentMode, supported: window::PresentMode) -> window::PresentMode {
+    let wanted = match mode {
+        PresentMode::Vsync => window::PresentMode::FIFO,
+        PresentMode::Immediate => window::PresentMode::IMMEDIATE,
+        PresentMode::Mailbox => window::PresentMode::MAILBOX,
+        PresentMode::Adaptive => window::PresentMode::RELAXED,
+    };
+    if supported.contains(wanted) {
+        wanted
+    } else {
+        log::warn!("{:?} presentation isn't supported, using vsync", mode);
+        window::PresentMode::FIFO
+    }
+}
+
+unsafe fn make_render_pass<B: gfx_hal::Backend>(
+    device: &B::Device,
+    color_format: Format,
+    depth_format: Format,
+    color_layout: Layout,
+) -> Result<B::RenderPass, FrameError> {
+    let color_attachment = Attachment {
+        format: Some(color_format),
+        samples: 1,
+        ops: AttachmentOps::new(AttachmentLoadOp::Clear, AttachmentStoreOp::Store),
+        stencil_ops: AttachmentOps::DONT_CARE,
+        layouts: Layout::Undefined..color_layout,
+    };
+    let depth_attachment = Attachment {
+        format: Some(depth_format),
+        samples: 1,
+        ops: AttachmentOps::new(AttachmentLoadOp::Clear, AttachmentStoreOp::DontCare),
+        stencil_ops: AttachmentOps::DONT_CARE,
+        layouts: Layout::Undefined..Layout::DepthStencilAttachmentOptimal,
+    };
+    let subpass = SubpassDesc {
+        colors: &[(0, Layout::ColorAttachmentOptimal)],
+        depth_stencil: Some(&(1, Layout::DepthStencilAttachmentOptimal)),
+        inputs: &[],
+        resolves: &[],
+        preserves: &[],
+    };
+    Ok(device.create_render_pass(&[color_attachment, depth_attachment], &[subpass], &[])?)
+}
+
+// what everything else is allocated from
+type Pools<B> = (
+    <B as gfx_hal::Backend>::CommandPool,
+    <B as gfx_hal::Backend>::DescriptorPool,
+    <B as gfx_hal::Backend>::PipelineCache,
+);
+
+// a failure destroys the pools made before it
+unsafe fn make_pools<B: gfx_hal::Backend>(
+    device: &B::Device,
+    family: QueueFamilyId,
+    cache_path: Option<&PathBuf>,
+) -> Result<Pools<B>, FrameError> {
+    let command_pool =
+        device.create_command_pool(family, CommandPoolCreateFlags::RESET_INDIVIDUAL)?;
+    let descriptor_pool = device.create_descriptor_pool(
+        1,
+        Some(DescriptorRangeDesc {
+            ty: SAMPLED_IMAGE,
+            count: 1,
+        }),
+        DescriptorPoolCreateFlags::empty(),
+    );
+    let descriptor_pool = match descriptor_pool {
+        Ok(descriptor_pool) => descriptor_pool,
+        Err(error) => {
+            device.destroy_command_pool(command_pool);
+            return Err(error.into());
+        }
+    };
+    // a missing or stale cache only costs compile time, the driver rejects foreign data
+    let cache_data = cache_path.and_then(|path| std::fs::read(path).ok());
+    let pipeline_cache = device
+        .create_pipeline_cache(cache_data.as_deref())
+        .or_else(|_| device.create_pipeline_cache(None));
+    match pipeline_cache {
+        Ok(pipeline_cache) => Ok((command_pool, descriptor_pool, pipeline_cache)),
+        Err(error) => {
+            device.destroy_descriptor_pool(descriptor_pool);
+            device.destroy_command_pool(command_pool);
+            Err(error.into())
+        }
     }
 }
 
+// the push constants of a pipeline, all of them read by its vertex shader
+fn push_constants<T>() -> [(ShaderStageFlags, Range<u32>); 1] {
+    [(ShaderStageFlags::VERTEX, 0..std::mem::size_of::<T>() as u32)]
+}
+
+// everything a pipeline is made with besides its shaders
+struct PipelineInputs<'a, B: gfx_hal::Backend> {
+    render_pass: &'a B::RenderPass,
+    layout: &'a B::PipelineLayout,
+    cache: &'a B::PipelineCache,
+    // blended with what is behind it
+    transparent: bool,
+    primitive: Primitive,
+}
+
 unsafe fn make_pipeline<B>(
     device: &B::Device,
-    render_pass: &B::RenderPass,
-    pipeline_layout: &B::PipelineLayout,
-    vertex_shader: &str,
-    fragment_shader: &str,
-) -> B::GraphicsPipeline
+    inputs: &PipelineInputs<B>,
+    vertex_shader: &[u32],
+    fragment_shader: &[u32],
+) -> Result<B::GraphicsPipeline, String>
 where
     B: gfx_hal::Backend,
 {
     let vertex_shader_module = device
-        .create_shader_module(&compile_shader(vertex_shader, ShaderKind::Vertex))
-        .expect("Failed to create vertex shader module");
-    let fragment_shader_module = device
-        .create_shader_module(&compile_shader(fragment_shader, ShaderKind::Fragment))
-        .expect("Failed to create fragment shader module");
+        .create_shader_module(vertex_shader)
+        .map_err(|error| format!("Failed to create vertex shader module: {:?}", error))?;
+    let fragment_shader_module = match device.create_shader_module(fragment_shader) {
+        Ok(module) => module,
+        Err(error) => {
+            device.destroy_shader_module(vertex_shader_module);
+            return Err(format!(
+                "Failed to create fragment shader module: {:?}",
+                error
+            ));
+        }
+    };
     let (vs_entry, fs_entry) = (
         EntryPoint {
             entry: "main",
@@ -238,7 +650,7 @@ where
     let primitive_assembler = PrimitiveAssemblerDesc::Vertex {
         buffers: &[],
         attributes: &[],
-        input_assembler: InputAssemblerDesc::new(Primitive::TriangleList),
+        input_assembler: InputAssemblerDesc::new(inputs.primitive),
         vertex: vs_entry,
         tessellation: None,
         geometry: None,
@@ -250,37 +662,98 @@ where
             ..Rasterizer::FILL
         },
         Some(fs_entry),
-        pipeline_layout,
+        inputs.layout,
         Subpass {
             index: 0,
-            main_pass: render_pass,
+            main_pass: inputs.render_pass,
         },
     );
+    // blended pipelines are drawn back to front, they test depth but leave it to what's behind
+    pipeline_desc.depth_stencil = DepthStencilDesc {
+        depth: Some(DepthTest {
+            fun: Comparison::LessEqual,
+            write: !inputs.transparent,
+        }),
+        depth_bounds: false,
+        stencil: None,
+    };
     pipeline_desc.blender.targets.push(ColorBlendDesc {
         mask: ColorMask::ALL,
         blend: Some(BlendState::ALPHA),
     });
     let pipeline = device
-        .create_graphics_pipeline(&pipeline_desc, None)
-        .expect("Failed to create graphics pipeline");
+        .create_graphics_pipeline(&pipeline_desc, Some(inputs.cache))
+        .map_err(|error| format!("Failed to create graphics pipeline: {:?}", error));
     device.destroy_shader_module(vertex_shader_module);
     device.destroy_shader_module(fragment_shader_module);
 
     pipeline
 }
 
-fn compile_shader(glsl: &str, shader_kind: ShaderKind) -> Vec<u32> {
-    let mut compiler = Compiler::new().unwrap();
-    let compiled_shader = compiler
-        .compile_into_spirv(glsl, shader_kind, "unnamed", "main", None)
-        .expect("Failed to compile shader");
-    compiled_shader.as_binary().to_vec()
+// the atlas, sampled by the sprite pipeline
+const SAMPLED_IMAGE: DescriptorType = DescriptorType::Image {
+    ty: ImageDescriptorType::Sampled { with_sampler: true },
+};
+
+// pipelines that blend with what is behind them, indexed like `PIPELINES`
+const TRANSPARENT: [bool; 3] = [false, true, true];
+
+// indexed like `PIPELINES`
+const PRIMITIVES: [Primitive; 3] = [
+    Primitive::TriangleList,
+    Primitive::TriangleList,
+    Primitive::LineList,
+];
+
+// the vertex and fragment shader of each pipeline, indexed like `PIPELINES`
+pub type PipelineSpirv = [(Arc<Shader>, Arc<Shader>)];
+
+// what each pipeline's shaders are checked against, indexed like `PIPELINES`
+pub fn pipeline_interfaces() -> [Interface; 3] {
+    [
+        Interface::default().with_push_constants::<PushConstants>(ShaderStageFlags::VERTEX),
+        Interface::default()
+            .with_push_constants::<SpritePushConstants>(ShaderStageFlags::VERTEX)
+            .with_descriptor(0, 0, SAMPLED_IMAGE),
+        Interface::default().with_push_constants::<LinePushConstants>(ShaderStageFlags::VERTEX),
+    ]
 }
 
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-pub struct PushConstants {
-    pub color: [f32; 4],
-    pub pos: [f32; 2],
-    pub scale: [f32; 2],
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gfx_hal::device::{DeviceLost, SurfaceLost};
+
+    #[test]
+    fn classifies_frame_errors() {
+        assert_eq!(
+            FrameError::from(AcquireError::OutOfDate),
+            FrameError::OutOfDate
+        );
+        assert_eq!(
+            FrameError::from(AcquireError::SurfaceLost(SurfaceLost)),
+            FrameError::Lost(GraphicsLoss::Surface)
+        );
+        assert_eq!(
+            FrameError::from(PresentError::DeviceLost(DeviceLost)),
+            FrameError::Lost(GraphicsLoss::Device)
+        );
+        assert_eq!(
+            FrameError::from(OomOrDeviceLost::DeviceLost(DeviceLost)),
+            FrameError::Lost(GraphicsLoss::Device)
+        );
+        assert_eq!(
+            FrameError::from(CreationError::SurfaceLost(SurfaceLost)),
+            FrameError::Lost(GraphicsLoss::Surface)
+        );
+        // worth retrying without rebuilding anything
+        assert!(matches!(
+            FrameError::from(AcquireError::Timeout),
+            FrameError::Skipped(_)
+        ));
+        assert!(matches!(
+            FrameError::from(OomOrDeviceLost::OutOfMemory(OutOfMemory::Device)),
+            FrameError::Skipped(_)
+        ));
+    }
 }
@@ -0,0 +1,16 @@
+use shaderc::ShaderKind;
+use std::path::Path;
+
+// the stage of a GLSL source, by its extension. build.rs and the packer include this file as a
+// module of their own, so it only uses what all three have
+pub fn shader_kind(path: &Path) -> Option<ShaderKind> {
+    match path.extension()?.to_str()? {
+        "vert" => Some(ShaderKind::Vertex),
+        "frag" => Some(ShaderKind::Fragment),
+        "comp" => Some(ShaderKind::Compute),
+        "geom" => Some(ShaderKind::Geometry),
+        "tesc" => Some(ShaderKind::TessControl),
+        "tese" => Some(ShaderKind::TessEvaluation),
+        _ => None,
+    }
+}
@@ -1,2 +1,155 @@
 // pub mod vertex;
 pub mod fragment;
+#[cfg(feature = "hot-reload")]
+mod kind;
+pub mod reflect;
+
+#[cfg(feature = "hot-reload")]
+pub use kind::shader_kind;
+
+use reflect::{reflect, Interface};
+#[cfg(feature = "hot-reload")]
+use shaderc::{Compiler, ShaderKind};
+
+// with `hot-reload` shaders are compiled from source instead of read as SPIR-V
+#[cfg(not(feature = "hot-reload"))]
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+// paths of the GLSL sources in the asset directory, loaded as `assets::kinds::Shader`
+#[derive(Debug, Clone, Copy)]
+pub struct PipelineShaders {
+    pub vertex: &'static str,
+    pub fragment: &'static str,
+}
+
+pub const TRIANGLE: PipelineShaders = PipelineShaders {
+    vertex: "shaders/vertex/vs.vert",
+    fragment: "shaders/fragment/fs.frag",
+};
+
+pub const SPRITE: PipelineShaders = PipelineShaders {
+    vertex: "shaders/vertex/sprite.vert",
+    fragment: "shaders/fragment/sprite.frag",
+};
+
+pub const LINE: PipelineShaders = PipelineShaders {
+    vertex: "shaders/vertex/line.vert",
+    fragment: "shaders/fragment/fs.frag",
+};
+
+// indexed like `Resources::pipelines` and `Resources::pipeline_layouts`
+pub const PIPELINES: [PipelineShaders; 3] = [TRIANGLE, SPRITE, LINE];
+
+impl PipelineShaders {
+    // checks both stages against what the pipeline provides
+    pub fn validate(
+        &self,
+        vertex: &[u32],
+        fragment: &[u32],
+        interface: &Interface,
+    ) -> Result<(), String> {
+        for (path, spirv) in &[(self.vertex, vertex), (self.fragment, fragment)] {
+            reflect(spirv)
+                .and_then(|reflection| interface.validate(&reflection))
+                .map_err(|error| format!("{}: {}", path, error))?;
+        }
+        Ok(())
+    }
+}
+
+// the checked-in SPIR-V, or with `compile-shaders` what the build script compiled, see build.rs
+#[cfg(not(feature = "hot-reload"))]
+macro_rules! embedded {
+    ($($path:literal),*) => {
+        // by the path of the GLSL source, like `assets::kinds::Shader`
+        pub fn embedded_spirv(path: &str) -> Option<&'static [u8]> {
+            match path {
+                $($path => Some(include_bytes!(concat!(env!("SPIRV_DIR"), "/", $path, ".spv"))),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "hot-reload"))]
+embedded!(
+    "shaders/vertex/vs.vert",
+    "shaders/vertex/sprite.vert",
+    "shaders/vertex/line.vert",
+    "shaders/fragment/fs.frag",
+    "shaders/fragment/sprite.frag"
+);
+
+#[cfg(not(feature = "hot-reload"))]
+pub fn spirv_words(bytes: &[u8]) -> Result<Vec<u32>, String> {
+    let chunks = bytes.chunks_exact(4);
+    if !chunks.remainder().is_empty() {
+        return Err(format!(
+            "SPIR-V size {} is not a multiple of 4",
+            bytes.len()
+        ));
+    }
+    let words: Vec<u32> = chunks
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect();
+    match words.first() {
+        Some(&SPIRV_MAGIC) => Ok(words),
+        _ => Err("missing SPIR-V magic number".to_string()),
+    }
+}
+
+// errors are reported as `file:line: error: message`, one per line
+#[cfg(feature = "hot-reload")]
+pub fn compile_shader(
+    glsl: &str,
+    shader_kind: ShaderKind,
+    file_name: &str,
+) -> Result<Vec<u32>, String> {
+    let mut compiler = Compiler::new().ok_or("Failed to initialize shader compiler")?;
+    let compiled_shader = compiler
+        .compile_into_spirv(glsl, shader_kind, file_name, "main", None)
+        .map_err(|error| error.to_string())?;
+    if compiled_shader.get_num_warnings() > 0 {
+        log::warn!("{}", compiled_shader.get_warning_messages());
+    }
+    Ok(compiled_shader.as_binary().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_sources_for_every_pipeline() {
+        let assets = std::path::Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/../assets"));
+        for shaders in &PIPELINES {
+            for path in &[shaders.vertex, shaders.fragment] {
+                assert!(assets.join(path).is_file(), "{}", path);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "hot-reload"))]
+    fn embeds_spirv_for_every_pipeline() {
+        for shaders in &PIPELINES {
+            for path in &[shaders.vertex, shaders.fragment] {
+                let spirv = embedded_spirv(path).unwrap_or_else(|| panic!("{}", path));
+                let words =
+                    spirv_words(spirv).unwrap_or_else(|error| panic!("{}: {}", path, error));
+                assert!(reflect(&words).is_ok(), "{}", path);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "hot-reload"))]
+    fn rejects_truncated_spirv() {
+        assert!(spirv_words(&[0x03, 0x02, 0x23]).is_err());
+        assert!(spirv_words(&[0, 0, 0, 0]).is_err());
+        assert_eq!(
+            spirv_words(&[0x03, 0x02, 0x23, 0x07]),
+            Ok(vec![SPIRV_MAGIC])
+        );
+    }
+}
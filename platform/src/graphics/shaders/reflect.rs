@@ -0,0 +1,948 @@
+use gfx_hal::{
+    format::{ChannelType, Format},
+    pso::{
+        AttributeDesc, BufferDescriptorFormat, BufferDescriptorType, DescriptorType,
+        ImageDescriptorType, ShaderStageFlags,
+    },
+};
+use std::collections::HashMap;
+use std::fmt;
+
+// the subset of SPIR-V needed to describe shader interfaces
+const OP_NAME: u32 = 5;
+const OP_MEMBER_NAME: u32 = 6;
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_BOOL: u32 = 20;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_BUILT_IN: u32 = 11;
+const DECORATION_LOCATION: u32 = 30;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_INPUT: u32 = 1;
+const STORAGE_UNIFORM: u32 = 2;
+const STORAGE_PUSH_CONSTANT: u32 = 9;
+const STORAGE_STORAGE_BUFFER: u32 = 12;
+
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Bool,
+    Int { signed: bool, width: u32 },
+    Float { width: u32 },
+    Vector(Box<Type>, u32),
+    // column type and column count
+    Matrix(Box<Type>, u32),
+    Array(Box<Type>, u32),
+    RuntimeArray(Box<Type>),
+    Struct(Vec<Member>),
+    Image { dim: u32, sampled: u32 },
+    Sampler,
+    SampledImage,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Member {
+    pub name: Option<String>,
+    pub offset: u32,
+    pub ty: Type,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub name: Option<String>,
+    pub members: Vec<Member>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DescriptorKind {
+    Sampler,
+    CombinedImageSampler,
+    SampledImage,
+    StorageImage,
+    UniformTexelBuffer,
+    StorageTexelBuffer,
+    UniformBuffer,
+    StorageBuffer,
+    InputAttachment,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Descriptor {
+    pub name: Option<String>,
+    pub set: u32,
+    pub binding: u32,
+    pub kind: DescriptorKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Input {
+    pub name: Option<String>,
+    pub location: u32,
+    pub ty: Type,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reflection {
+    pub stage: ShaderStageFlags,
+    pub push_constants: Option<Block>,
+    pub descriptors: Vec<Descriptor>,
+    pub inputs: Vec<Input>,
+}
+
+#[derive(Debug, Default)]
+struct Decorations {
+    names: HashMap<u32, String>,
+    member_names: HashMap<(u32, u32), String>,
+    member_offsets: HashMap<(u32, u32), u32>,
+    // id -> (decoration, first literal)
+    decorations: HashMap<u32, Vec<(u32, Option<u32>)>>,
+}
+
+impl Decorations {
+    fn get(&self, id: u32, decoration: u32) -> Option<Option<u32>> {
+        self.decorations
+            .get(&id)?
+            .iter()
+            .find(|(kind, _)| *kind == decoration)
+            .map(|(_, value)| *value)
+    }
+
+    fn value(&self, id: u32, decoration: u32) -> Option<u32> {
+        self.get(id, decoration).flatten()
+    }
+}
+
+pub fn reflect(words: &[u32]) -> Result<Reflection, String> {
+    if words.len() < 5 {
+        return Err("SPIR-V module is missing its header".to_string());
+    }
+    let instructions = instructions(&words[5..])?;
+
+    let mut decorations = Decorations::default();
+    let mut stage = None;
+    for (opcode, operands) in &instructions {
+        match (*opcode, operands.as_slice()) {
+            (OP_NAME, [target, name @ ..]) => {
+                decorations.names.insert(*target, string(name));
+            }
+            (OP_MEMBER_NAME, [target, member, name @ ..]) => {
+                decorations
+                    .member_names
+                    .insert((*target, *member), string(name));
+            }
+            (OP_ENTRY_POINT, [model, ..]) => stage = Some(stage_flags(*model)?),
+            (OP_DECORATE, [target, decoration, literals @ ..]) => decorations
+                .decorations
+                .entry(*target)
+                .or_default()
+                .push((*decoration, literals.first().cloned())),
+            (OP_MEMBER_DECORATE, [target, member, DECORATION_OFFSET, offset, ..]) => {
+                decorations
+                    .member_offsets
+                    .insert((*target, *member), *offset);
+            }
+            _ => {}
+        }
+    }
+    let stage = stage.ok_or("SPIR-V module has no entry point")?;
+
+    let mut types: HashMap<u32, Type> = HashMap::new();
+    let mut constants: HashMap<u32, u32> = HashMap::new();
+    let mut pointers: HashMap<u32, (u32, u32)> = HashMap::new();
+    let mut reflection = Reflection {
+        stage,
+        push_constants: None,
+        descriptors: vec![],
+        inputs: vec![],
+    };
+    for (opcode, operands) in &instructions {
+        let lookup = |id: &u32| {
+            types
+                .get(id)
+                .cloned()
+                .ok_or(format!("SPIR-V references unknown type %{}", id))
+        };
+        let ty = match (*opcode, operands.as_slice()) {
+            (OP_TYPE_BOOL, [id]) => Some((*id, Type::Bool)),
+            (OP_TYPE_INT, [id, width, signed]) => Some((
+                *id,
+                Type::Int {
+                    signed: *signed == 1,
+                    width: *width,
+                },
+            )),
+            (OP_TYPE_FLOAT, [id, width]) => Some((*id, Type::Float { width: *width })),
+            (OP_TYPE_VECTOR, [id, component, count]) => {
+                Some((*id, Type::Vector(Box::new(lookup(component)?), *count)))
+            }
+            (OP_TYPE_MATRIX, [id, column, count]) => {
+                Some((*id, Type::Matrix(Box::new(lookup(column)?), *count)))
+            }
+            (OP_TYPE_IMAGE, [id, _, dim, _, _, _, sampled, ..]) => Some((
+                *id,
+                Type::Image {
+                    dim: *dim,
+                    sampled: *sampled,
+                },
+            )),
+            (OP_TYPE_SAMPLER, [id]) => Some((*id, Type::Sampler)),
+            (OP_TYPE_SAMPLED_IMAGE, [id, _]) => Some((*id, Type::SampledImage)),
+            (OP_TYPE_ARRAY, [id, element, length]) => {
+                let length = constants
+                    .get(length)
+                    .ok_or(format!("array %{} has a specialized length", id))?;
+                Some((*id, Type::Array(Box::new(lookup(element)?), *length)))
+            }
+            (OP_TYPE_RUNTIME_ARRAY, [id, element]) => {
+                Some((*id, Type::RuntimeArray(Box::new(lookup(element)?))))
+            }
+            (OP_TYPE_STRUCT, [id, members @ ..]) => {
+                let members = members
+                    .iter()
+                    .enumerate()
+                    .map(|(index, member)| {
+                        let index = index as u32;
+                        Ok(Member {
+                            name: decorations.member_names.get(&(*id, index)).cloned(),
+                            offset: decorations
+                                .member_offsets
+                                .get(&(*id, index))
+                                .cloned()
+                                .unwrap_or(0),
+                            ty: lookup(member)?,
+                        })
+                    })
+                    .collect::<Result<_, String>>()?;
+                Some((*id, Type::Struct(members)))
+            }
+            (OP_TYPE_POINTER, [id, storage, pointee]) => {
+                pointers.insert(*id, (*storage, *pointee));
+                None
+            }
+            (OP_CONSTANT, [_, id, value, ..]) => {
+                constants.insert(*id, *value);
+                None
+            }
+            (OP_VARIABLE, [pointer, id, storage, ..]) => {
+                let (_, pointee) = pointers
+                    .get(pointer)
+                    .ok_or(format!("variable %{} is not a pointer", id))?;
+                let name = decorations.names.get(id).cloned();
+                match *storage {
+                    STORAGE_PUSH_CONSTANT => {
+                        let members = match lookup(pointee)? {
+                            Type::Struct(members) => members,
+                            _ => return Err("push constants are not a block".to_string()),
+                        };
+                        reflection.push_constants = Some(Block {
+                            name: decorations.names.get(pointee).cloned(),
+                            members,
+                        });
+                    }
+                    STORAGE_UNIFORM_CONSTANT | STORAGE_UNIFORM | STORAGE_STORAGE_BUFFER => {
+                        let buffer_block = *storage == STORAGE_STORAGE_BUFFER
+                            || decorations.get(*pointee, DECORATION_BUFFER_BLOCK).is_some();
+                        reflection.descriptors.push(Descriptor {
+                            kind: descriptor_kind(&lookup(pointee)?, buffer_block)
+                                .ok_or(format!("{:?} is not a descriptor", name))?,
+                            name,
+                            set: decorations
+                                .value(*id, DECORATION_DESCRIPTOR_SET)
+                                .unwrap_or(0),
+                            binding: decorations.value(*id, DECORATION_BINDING).unwrap_or(0),
+                        });
+                    }
+                    STORAGE_INPUT if decorations.get(*id, DECORATION_BUILT_IN).is_none() => {
+                        if let Some(location) = decorations.value(*id, DECORATION_LOCATION) {
+                            reflection.inputs.push(Input {
+                                name,
+                                location,
+                                ty: lookup(pointee)?,
+                            });
+                        }
+                    }
+                    _ => {}
+                }
+                None
+            }
+            _ => None,
+        };
+        if let Some((id, ty)) = ty {
+            types.insert(id, ty);
+        }
+    }
+    Ok(reflection)
+}
+
+fn instructions(mut words: &[u32]) -> Result<Vec<(u32, Vec<u32>)>, String> {
+    let mut instructions = vec![];
+    while let Some(&first) = words.first() {
+        let (count, opcode) = ((first >> 16) as usize, first & 0xffff);
+        if count == 0 || count > words.len() {
+            return Err(format!("malformed SPIR-V instruction {}", opcode));
+        }
+        instructions.push((opcode, words[1..count].to_vec()));
+        words = &words[count..];
+    }
+    Ok(instructions)
+}
+
+// nul terminated UTF-8 packed into little endian words
+fn string(words: &[u32]) -> String {
+    let bytes: Vec<u8> = words
+        .iter()
+        .flat_map(|word| word.to_le_bytes().to_vec())
+        .take_while(|&byte| byte != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn stage_flags(model: u32) -> Result<ShaderStageFlags, String> {
+    match model {
+        0 => Ok(ShaderStageFlags::VERTEX),
+        1 => Ok(ShaderStageFlags::HULL),
+        2 => Ok(ShaderStageFlags::DOMAIN),
+        3 => Ok(ShaderStageFlags::GEOMETRY),
+        4 => Ok(ShaderStageFlags::FRAGMENT),
+        5 => Ok(ShaderStageFlags::COMPUTE),
+        _ => Err(format!("unsupported execution model {}", model)),
+    }
+}
+
+fn descriptor_kind(ty: &Type, buffer_block: bool) -> Option<DescriptorKind> {
+    match ty {
+        Type::Array(element, _) | Type::RuntimeArray(element) => {
+            descriptor_kind(element, buffer_block)
+        }
+        Type::Sampler => Some(DescriptorKind::Sampler),
+        Type::SampledImage => Some(DescriptorKind::CombinedImageSampler),
+        Type::Image { dim, .. } if *dim == DIM_SUBPASS_DATA => {
+            Some(DescriptorKind::InputAttachment)
+        }
+        Type::Image { dim, sampled } if *dim == DIM_BUFFER && *sampled == 2 => {
+            Some(DescriptorKind::StorageTexelBuffer)
+        }
+        Type::Image { dim, .. } if *dim == DIM_BUFFER => Some(DescriptorKind::UniformTexelBuffer),
+        Type::Image { sampled: 2, .. } => Some(DescriptorKind::StorageImage),
+        Type::Image { .. } => Some(DescriptorKind::SampledImage),
+        Type::Struct(_) if buffer_block => Some(DescriptorKind::StorageBuffer),
+        Type::Struct(_) => Some(DescriptorKind::UniformBuffer),
+        _ => None,
+    }
+}
+
+impl From<&DescriptorType> for DescriptorKind {
+    fn from(ty: &DescriptorType) -> Self {
+        match ty {
+            DescriptorType::Sampler => DescriptorKind::Sampler,
+            DescriptorType::Image {
+                ty: ImageDescriptorType::Sampled { with_sampler: true },
+            } => DescriptorKind::CombinedImageSampler,
+            DescriptorType::Image {
+                ty: ImageDescriptorType::Sampled { .. },
+            } => DescriptorKind::SampledImage,
+            DescriptorType::Image {
+                ty: ImageDescriptorType::Storage { .. },
+            } => DescriptorKind::StorageImage,
+            DescriptorType::Buffer {
+                ty: BufferDescriptorType::Uniform,
+                format: BufferDescriptorFormat::Texel,
+            } => DescriptorKind::UniformTexelBuffer,
+            DescriptorType::Buffer {
+                ty: BufferDescriptorType::Storage { .. },
+                format: BufferDescriptorFormat::Texel,
+            } => DescriptorKind::StorageTexelBuffer,
+            DescriptorType::Buffer {
+                ty: BufferDescriptorType::Uniform,
+                ..
+            } => DescriptorKind::UniformBuffer,
+            DescriptorType::Buffer { .. } => DescriptorKind::StorageBuffer,
+            DescriptorType::InputAttachment => DescriptorKind::InputAttachment,
+        }
+    }
+}
+
+impl Type {
+    fn scalar(&self) -> &Type {
+        match self {
+            Type::Vector(component, _) => component.scalar(),
+            Type::Matrix(column, _) => column.scalar(),
+            Type::Array(element, _) | Type::RuntimeArray(element) => element.scalar(),
+            scalar => scalar,
+        }
+    }
+
+    // std430 base alignment and size in bytes
+    pub fn std430_alignment(&self) -> u32 {
+        match self {
+            Type::Bool => 4,
+            Type::Int { width, .. } | Type::Float { width } => width / 8,
+            Type::Vector(component, count) => {
+                component.std430_alignment() * if *count == 2 { 2 } else { 4 }
+            }
+            Type::Matrix(column, _) => column.std430_alignment(),
+            Type::Array(element, _) | Type::RuntimeArray(element) => element.std430_alignment(),
+            Type::Struct(members) => members
+                .iter()
+                .map(|member| member.ty.std430_alignment())
+                .max()
+                .unwrap_or(1),
+            _ => 1,
+        }
+    }
+
+    pub fn std430_size(&self) -> u32 {
+        match self {
+            Type::Vector(component, count) => component.std430_size() * count,
+            Type::Matrix(column, count) => {
+                align(column.std430_size(), column.std430_alignment()) * count
+            }
+            Type::Array(element, length) => {
+                align(element.std430_size(), element.std430_alignment()) * length
+            }
+            Type::RuntimeArray(_) => 0,
+            Type::Struct(members) => {
+                let end = std430_offsets(members)
+                    .last()
+                    .map(|(offset, member)| offset + member.ty.std430_size())
+                    .unwrap_or(0);
+                align(end, self.std430_alignment())
+            }
+            scalar => scalar.std430_alignment(),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Bool => write!(f, "bool"),
+            Type::Int { signed: true, .. } => write!(f, "int"),
+            Type::Int { signed: false, .. } => write!(f, "uint"),
+            Type::Float { width: 64 } => write!(f, "double"),
+            Type::Float { .. } => write!(f, "float"),
+            Type::Vector(component, count) => match **component {
+                Type::Int { signed: true, .. } => write!(f, "ivec{}", count),
+                Type::Int { signed: false, .. } => write!(f, "uvec{}", count),
+                Type::Bool => write!(f, "bvec{}", count),
+                _ => write!(f, "vec{}", count),
+            },
+            Type::Matrix(column, count) => match **column {
+                Type::Vector(_, rows) if rows == *count => write!(f, "mat{}", count),
+                Type::Vector(_, rows) => write!(f, "mat{}x{}", count, rows),
+                _ => write!(f, "mat{}", count),
+            },
+            Type::Array(element, length) => write!(f, "{}[{}]", element, length),
+            Type::RuntimeArray(element) => write!(f, "{}[]", element),
+            Type::Struct(_) => write!(f, "struct"),
+            Type::Image { .. } => write!(f, "image"),
+            Type::Sampler => write!(f, "sampler"),
+            Type::SampledImage => write!(f, "sampler2D"),
+        }
+    }
+}
+
+fn align(value: u32, alignment: u32) -> u32 {
+    match value % alignment {
+        0 => value,
+        rest => value + alignment - rest,
+    }
+}
+
+// where std430 places each member, ignoring the offsets the shader declares
+fn std430_offsets(members: &[Member]) -> Vec<(u32, &Member)> {
+    let mut offset = 0;
+    members
+        .iter()
+        .map(|member| {
+            let placed = align(offset, member.ty.std430_alignment());
+            offset = placed + member.ty.std430_size();
+            (placed, member)
+        })
+        .collect()
+}
+
+// Rust types that can be shared with shaders
+pub trait ShaderType {
+    fn shader_type() -> Type;
+}
+
+impl ShaderType for f32 {
+    fn shader_type() -> Type {
+        Type::Float { width: 32 }
+    }
+}
+
+impl ShaderType for i32 {
+    fn shader_type() -> Type {
+        Type::Int {
+            signed: true,
+            width: 32,
+        }
+    }
+}
+
+impl ShaderType for u32 {
+    fn shader_type() -> Type {
+        Type::Int {
+            signed: false,
+            width: 32,
+        }
+    }
+}
+
+macro_rules! vector {
+    ($($count:literal),*) => {
+        $(
+            impl<T: ShaderType> ShaderType for [T; $count] {
+                fn shader_type() -> Type {
+                    match T::shader_type() {
+                        // a vector of vectors is a column major matrix
+                        column @ Type::Vector(..) => Type::Matrix(Box::new(column), $count),
+                        component => Type::Vector(Box::new(component), $count),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+vector!(2, 3, 4);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: &'static str,
+    pub offset: u32,
+    pub ty: Type,
+}
+
+impl Field {
+    // `field` has to be borrowed from `value`
+    pub fn new<T, F: ShaderType>(name: &'static str, value: &T, field: &F) -> Self {
+        let offset = field as *const F as usize - value as *const T as usize;
+        Self {
+            name,
+            offset: offset as u32,
+            ty: F::shader_type(),
+        }
+    }
+}
+
+// a `#[repr(C)]` struct that is uploaded as push constants
+pub trait ShaderLayout: Sized {
+    fn fields() -> Vec<Field>;
+}
+
+#[derive(Debug, Clone)]
+pub struct PushConstantLayout {
+    pub name: &'static str,
+    pub stages: ShaderStageFlags,
+    pub size: u32,
+    pub fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DescriptorBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub ty: DescriptorType,
+}
+
+// what the Rust side of a pipeline provides to its shaders
+#[derive(Debug, Clone, Default)]
+pub struct Interface {
+    pub push_constants: Option<PushConstantLayout>,
+    pub descriptors: Vec<DescriptorBinding>,
+    pub attributes: Vec<AttributeDesc>,
+}
+
+impl Interface {
+    pub fn with_push_constants<T: ShaderLayout>(mut self, stages: ShaderStageFlags) -> Self {
+        let name = std::any::type_name::<T>();
+        self.push_constants = Some(PushConstantLayout {
+            name: name.rsplit("::").next().unwrap_or(name),
+            stages,
+            size: std::mem::size_of::<T>() as u32,
+            fields: T::fields(),
+        });
+        self
+    }
+
+    pub fn with_descriptor(mut self, set: u32, binding: u32, ty: DescriptorType) -> Self {
+        self.descriptors
+            .push(DescriptorBinding { set, binding, ty });
+        self
+    }
+
+    pub fn validate(&self, reflection: &Reflection) -> Result<(), String> {
+        if let Some(block) = &reflection.push_constants {
+            self.validate_push_constants(reflection.stage, block)?;
+        }
+        for descriptor in &reflection.descriptors {
+            self.validate_descriptor(descriptor)?;
+        }
+        if reflection.stage == ShaderStageFlags::VERTEX {
+            for input in &reflection.inputs {
+                self.validate_input(input)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_push_constants(
+        &self,
+        stage: ShaderStageFlags,
+        block: &Block,
+    ) -> Result<(), String> {
+        let block_name = block.name.as_deref().unwrap_or("push constant block");
+        let layout = match &self.push_constants {
+            Some(layout) => layout,
+            None => {
+                return Err(format!(
+                    "{} is used, but the pipeline has no push constants",
+                    block_name
+                ))
+            }
+        };
+        if !layout.stages.contains(stage) {
+            return Err(format!(
+                "{} is used in {:?}, but {} is only pushed to {:?}",
+                block_name, stage, layout.name, layout.stages
+            ));
+        }
+        for (expected, member) in std430_offsets(&block.members) {
+            if member.offset != expected {
+                return Err(format!(
+                    "{}.{} is at offset {}, std430 places it at {}",
+                    block_name,
+                    member_name(member),
+                    member.offset,
+                    expected
+                ));
+            }
+        }
+        if block.members.len() != layout.fields.len() {
+            return Err(format!(
+                "{} has {} members, {} has {} fields",
+                block_name,
+                block.members.len(),
+                layout.name,
+                layout.fields.len()
+            ));
+        }
+        for (member, field) in block.members.iter().zip(&layout.fields) {
+            let mismatch = match &member.name {
+                Some(name) if name != field.name => true,
+                _ => member.ty != field.ty || member.offset != field.offset,
+            };
+            if mismatch {
+                return Err(format!(
+                    "{}.{} is {} at offset {}, but {}::{} is {} at offset {}",
+                    block_name,
+                    member_name(member),
+                    member.ty,
+                    member.offset,
+                    layout.name,
+                    field.name,
+                    field.ty,
+                    field.offset
+                ));
+            }
+        }
+        let end = block
+            .members
+            .last()
+            .map(|member| member.offset + member.ty.std430_size())
+            .unwrap_or(0);
+        if layout.size < end {
+            return Err(format!(
+                "{} is {} bytes, but {} needs {}",
+                layout.name, layout.size, block_name, end
+            ));
+        }
+        Ok(())
+    }
+
+    fn validate_descriptor(&self, descriptor: &Descriptor) -> Result<(), String> {
+        let name = descriptor.name.as_deref().unwrap_or("descriptor");
+        let binding = self
+            .descriptors
+            .iter()
+            .find(|binding| binding.set == descriptor.set && binding.binding == descriptor.binding);
+        match binding {
+            None => Err(format!(
+                "{} (set {}, binding {}) is not in the pipeline layout",
+                name, descriptor.set, descriptor.binding
+            )),
+            Some(binding) if DescriptorKind::from(&binding.ty) != descriptor.kind => Err(format!(
+                "{} (set {}, binding {}) is a {:?}, but the pipeline layout binds a {:?}",
+                name,
+                descriptor.set,
+                descriptor.binding,
+                descriptor.kind,
+                DescriptorKind::from(&binding.ty)
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+
+    fn validate_input(&self, input: &Input) -> Result<(), String> {
+        let name = input.name.as_deref().unwrap_or("input");
+        let attribute = self
+            .attributes
+            .iter()
+            .find(|attribute| attribute.location == input.location);
+        let format = match attribute {
+            Some(attribute) => attribute.element.format,
+            None => {
+                return Err(format!(
+                    "{} (location {}) has no vertex attribute",
+                    name, input.location
+                ))
+            }
+        };
+        if !compatible(input.ty.scalar(), format) {
+            return Err(format!(
+                "{} (location {}) is {}, but the vertex attribute is {:?}",
+                name, input.location, input.ty, format
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn member_name(member: &Member) -> String {
+    match &member.name {
+        Some(name) => name.clone(),
+        None => format!("<offset {}>", member.offset),
+    }
+}
+
+// missing components are filled in by the input assembler, only the numeric type has to agree
+fn compatible(scalar: &Type, format: Format) -> bool {
+    let channel = format.base_format().1;
+    match scalar {
+        Type::Float { .. } => !matches!(channel, ChannelType::Uint | ChannelType::Sint),
+        Type::Int { signed: true, .. } => channel == ChannelType::Sint,
+        Type::Int { signed: false, .. } => channel == ChannelType::Uint,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLOAT: u32 = 1;
+    const VEC2: u32 = 2;
+    const VEC4: u32 = 3;
+    const BLOCK: u32 = 4;
+    const POINTER: u32 = 5;
+    const VARIABLE: u32 = 6;
+
+    fn op(opcode: u32, operands: &[u32]) -> Vec<u32> {
+        let mut words = vec![((operands.len() as u32 + 1) << 16) | opcode];
+        words.extend_from_slice(operands);
+        words
+    }
+
+    fn literal(value: &str) -> Vec<u32> {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.push(0);
+        bytes.resize(align(bytes.len() as u32, 4) as usize, 0);
+        bytes
+            .chunks(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect()
+    }
+
+    fn named(opcode: u32, ids: &[u32], name: &str) -> Vec<u32> {
+        let mut operands = ids.to_vec();
+        operands.extend(literal(name));
+        op(opcode, &operands)
+    }
+
+    // vertex shader with `PushConstants { vec4 color; vec2 pos; }` at the given offsets
+    fn module(offsets: [u32; 2]) -> Vec<u32> {
+        let mut words = vec![0x0723_0203, 0x0001_0000, 0, 100, 0];
+        words.extend(named(OP_ENTRY_POINT, &[0, 99], "main"));
+        words.extend(named(OP_NAME, &[BLOCK], "PushConstants"));
+        words.extend(named(OP_MEMBER_NAME, &[BLOCK, 0], "color"));
+        words.extend(named(OP_MEMBER_NAME, &[BLOCK, 1], "pos"));
+        words.extend(op(
+            OP_MEMBER_DECORATE,
+            &[BLOCK, 0, DECORATION_OFFSET, offsets[0]],
+        ));
+        words.extend(op(
+            OP_MEMBER_DECORATE,
+            &[BLOCK, 1, DECORATION_OFFSET, offsets[1]],
+        ));
+        words.extend(op(OP_TYPE_FLOAT, &[FLOAT, 32]));
+        words.extend(op(OP_TYPE_VECTOR, &[VEC2, FLOAT, 2]));
+        words.extend(op(OP_TYPE_VECTOR, &[VEC4, FLOAT, 4]));
+        words.extend(op(OP_TYPE_STRUCT, &[BLOCK, VEC4, VEC2]));
+        words.extend(op(
+            OP_TYPE_POINTER,
+            &[POINTER, STORAGE_PUSH_CONSTANT, BLOCK],
+        ));
+        words.extend(op(OP_VARIABLE, &[POINTER, VARIABLE, STORAGE_PUSH_CONSTANT]));
+        words
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct Constants {
+        color: [f32; 4],
+        pos: [f32; 2],
+    }
+
+    impl ShaderLayout for Constants {
+        fn fields() -> Vec<Field> {
+            let value = Self::default();
+            vec![
+                Field::new("color", &value, &value.color),
+                Field::new("pos", &value, &value.pos),
+            ]
+        }
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct Swapped {
+        pos: [f32; 2],
+        color: [f32; 4],
+    }
+
+    impl ShaderLayout for Swapped {
+        fn fields() -> Vec<Field> {
+            let value = Self::default();
+            vec![
+                Field::new("color", &value, &value.color),
+                Field::new("pos", &value, &value.pos),
+            ]
+        }
+    }
+
+    #[test]
+    fn reflects_push_constants() {
+        let reflection = reflect(&module([0, 16])).unwrap();
+        assert_eq!(reflection.stage, ShaderStageFlags::VERTEX);
+        let block = reflection.push_constants.unwrap();
+        assert_eq!(block.name.as_deref(), Some("PushConstants"));
+        let members: Vec<(Option<&str>, u32, String)> = block
+            .members
+            .iter()
+            .map(|member| (member.name.as_deref(), member.offset, member.ty.to_string()))
+            .collect();
+        assert_eq!(
+            members,
+            vec![
+                (Some("color"), 0, "vec4".to_string()),
+                (Some("pos"), 16, "vec2".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn accepts_matching_layout() {
+        let reflection = reflect(&module([0, 16])).unwrap();
+        let interface =
+            Interface::default().with_push_constants::<Constants>(ShaderStageFlags::VERTEX);
+        assert_eq!(interface.validate(&reflection), Ok(()));
+    }
+
+    #[test]
+    fn reports_mismatched_offsets() {
+        let reflection = reflect(&module([0, 16])).unwrap();
+        let interface =
+            Interface::default().with_push_constants::<Swapped>(ShaderStageFlags::VERTEX);
+        assert_eq!(
+            interface.validate(&reflection),
+            Err(
+                "PushConstants.color is vec4 at offset 0, but Swapped::color is vec4 at offset 8"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn reports_non_std430_offsets() {
+        let reflection = reflect(&module([0, 20])).unwrap();
+        let interface =
+            Interface::default().with_push_constants::<Constants>(ShaderStageFlags::VERTEX);
+        assert_eq!(
+            interface.validate(&reflection),
+            Err("PushConstants.pos is at offset 20, std430 places it at 16".to_string())
+        );
+    }
+
+    #[test]
+    fn reports_missing_stage() {
+        let reflection = reflect(&module([0, 16])).unwrap();
+        let interface =
+            Interface::default().with_push_constants::<Constants>(ShaderStageFlags::FRAGMENT);
+        assert!(interface.validate(&reflection).is_err());
+    }
+
+    #[test]
+    fn reflects_descriptors() {
+        let mut words = vec![0x0723_0203, 0x0001_0000, 0, 100, 0];
+        words.extend(named(OP_ENTRY_POINT, &[4, 99], "main"));
+        words.extend(named(OP_NAME, &[VARIABLE], "atlas"));
+        words.extend(op(OP_DECORATE, &[VARIABLE, DECORATION_DESCRIPTOR_SET, 1]));
+        words.extend(op(OP_DECORATE, &[VARIABLE, DECORATION_BINDING, 2]));
+        words.extend(op(OP_TYPE_FLOAT, &[FLOAT, 32]));
+        words.extend(op(OP_TYPE_IMAGE, &[VEC2, FLOAT, 1, 0, 0, 0, 1, 0]));
+        words.extend(op(OP_TYPE_SAMPLED_IMAGE, &[VEC4, VEC2]));
+        words.extend(op(
+            OP_TYPE_POINTER,
+            &[POINTER, STORAGE_UNIFORM_CONSTANT, VEC4],
+        ));
+        words.extend(op(
+            OP_VARIABLE,
+            &[POINTER, VARIABLE, STORAGE_UNIFORM_CONSTANT],
+        ));
+        let reflection = reflect(&words).unwrap();
+        assert_eq!(
+            reflection.descriptors,
+            vec![Descriptor {
+                name: Some("atlas".to_string()),
+                set: 1,
+                binding: 2,
+                kind: DescriptorKind::CombinedImageSampler,
+            }]
+        );
+
+        let sampled = DescriptorType::Image {
+            ty: ImageDescriptorType::Sampled { with_sampler: true },
+        };
+        let wrong_binding = Interface::default().with_descriptor(1, 0, sampled);
+        assert_eq!(
+            wrong_binding.validate(&reflection),
+            Err("atlas (set 1, binding 2) is not in the pipeline layout".to_string())
+        );
+        let interface = Interface::default().with_descriptor(1, 2, sampled);
+        assert_eq!(interface.validate(&reflection), Ok(()));
+    }
+}
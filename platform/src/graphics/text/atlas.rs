@@ -0,0 +1,127 @@
+use super::font::{Font, GlyphBitmap};
+use std::collections::HashMap;
+
+const PADDING: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasGlyph {
+    // top left and bottom right corners in texture coordinates
+    pub uv: [f32; 4],
+    pub size: [f32; 2],
+    pub offset: [f32; 2],
+}
+
+// RGBA glyph cache packed in rows ("shelves"), glyphs are rasterized on first use
+#[derive(Debug)]
+pub struct GlyphAtlas {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+    pub dirty: bool,
+    glyphs: HashMap<char, AtlasGlyph>,
+    cursor: [u32; 2],
+    shelf_height: u32,
+}
+
+impl GlyphAtlas {
+    pub fn new(width: u32, height: u32) -> Self {
+        let mut pixels = vec![0; (width * height * 4) as usize];
+        // the top left corner is never packed into, it keeps a white texel for solid quads
+        pixels[..4].copy_from_slice(&[255; 4]);
+        Self {
+            width,
+            height,
+            pixels,
+            dirty: true,
+            glyphs: HashMap::new(),
+            cursor: [PADDING, PADDING],
+            shelf_height: 0,
+        }
+    }
+
+    // samples only the white texel, even with linear filtering
+    pub fn white_uv(&self) -> [f32; 4] {
+        let (u, v) = (0.5 / self.width as f32, 0.5 / self.height as f32);
+        [u, v, u, v]
+    }
+
+    pub fn glyph(&mut self, font: &Font, c: char) -> Option<AtlasGlyph> {
+        if let Some(glyph) = self.glyphs.get(&c) {
+            return Some(*glyph);
+        }
+        let glyph = self.insert(&font.rasterize(c)?)?;
+        self.glyphs.insert(c, glyph);
+        Some(glyph)
+    }
+
+    pub fn insert(&mut self, bitmap: &GlyphBitmap) -> Option<AtlasGlyph> {
+        if self.cursor[0] + bitmap.width + PADDING > self.width {
+            self.cursor = [PADDING, self.cursor[1] + self.shelf_height + PADDING];
+            self.shelf_height = 0;
+        }
+        if self.cursor[0] + bitmap.width + PADDING > self.width
+            || self.cursor[1] + bitmap.height + PADDING > self.height
+        {
+            log::warn!("Glyph atlas is full");
+            return None;
+        }
+        let [x, y] = self.cursor;
+        for row in 0..bitmap.height {
+            let source = (row * bitmap.width * 4) as usize;
+            let target = (((y + row) * self.width + x) * 4) as usize;
+            let length = (bitmap.width * 4) as usize;
+            self.pixels[target..target + length]
+                .copy_from_slice(&bitmap.pixels[source..source + length]);
+        }
+        self.cursor[0] += bitmap.width + PADDING;
+        self.shelf_height = self.shelf_height.max(bitmap.height);
+        self.dirty = true;
+
+        let (width, height) = (self.width as f32, self.height as f32);
+        Some(AtlasGlyph {
+            uv: [
+                x as f32 / width,
+                y as f32 / height,
+                (x + bitmap.width) as f32 / width,
+                (y + bitmap.height) as f32 / height,
+            ],
+            size: [bitmap.width as f32, bitmap.height as f32],
+            offset: bitmap.offset,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bitmap(width: u32, height: u32) -> GlyphBitmap {
+        GlyphBitmap {
+            width,
+            height,
+            offset: [0.0, 0.0],
+            pixels: vec![255; (width * height * 4) as usize],
+        }
+    }
+
+    #[test]
+    fn packs_glyphs_in_rows() {
+        let mut atlas = GlyphAtlas::new(16, 16);
+        let first = atlas.insert(&bitmap(6, 4)).unwrap();
+        let second = atlas.insert(&bitmap(6, 6)).unwrap();
+        let third = atlas.insert(&bitmap(6, 2)).unwrap();
+        assert_eq!(first.uv, [1.0 / 16.0, 1.0 / 16.0, 7.0 / 16.0, 5.0 / 16.0]);
+        assert_eq!(second.uv[0], 8.0 / 16.0);
+        // the third glyph does not fit in the first shelf, which is as tall as its tallest glyph
+        assert_eq!(third.uv[0], 1.0 / 16.0);
+        assert_eq!(third.uv[1], 8.0 / 16.0);
+        assert_eq!(atlas.pixels[((16 + 1) * 4 + 3) as usize], 255);
+    }
+
+    #[test]
+    fn refuses_glyphs_when_full() {
+        let mut atlas = GlyphAtlas::new(8, 8);
+        assert!(atlas.insert(&bitmap(6, 6)).is_some());
+        assert!(atlas.insert(&bitmap(6, 6)).is_none());
+    }
+}
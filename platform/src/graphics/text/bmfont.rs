@@ -0,0 +1,246 @@
+use super::font::{FontError, GlyphBitmap};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+
+// AngelCode BMFont, text flavour of the .fnt descriptor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BmChar {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub offset: [f32; 2],
+    pub advance: f32,
+    pub page: usize,
+}
+
+#[derive(Debug)]
+pub struct Page {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+#[derive(Debug, Default)]
+pub struct BmFont {
+    pub size: f32,
+    pub line_height: f32,
+    pub base: f32,
+    pub chars: HashMap<char, BmChar>,
+    pub kernings: HashMap<(char, char), f32>,
+    pub pages: Vec<Page>,
+}
+
+impl BmFont {
+    pub fn from_file(path: &Path) -> Result<Self, FontError> {
+        let descriptor = std::fs::read_to_string(path)?;
+        let (mut font, page_files) = Self::parse(&descriptor)?;
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        for file in page_files {
+            font.pages.push(load_page(&directory.join(file))?);
+        }
+        Ok(font)
+    }
+
+    pub fn parse(descriptor: &str) -> Result<(Self, Vec<String>), FontError> {
+        let mut font = Self::default();
+        let mut page_files = vec![];
+        for (number, line) in descriptor.lines().enumerate() {
+            let mut tokens = tokenize(line).into_iter();
+            let tag = match tokens.next() {
+                Some((tag, _)) => tag,
+                None => continue,
+            };
+            let attributes: HashMap<String, String> = tokens.collect();
+            let attribute = |key: &str| -> Result<f32, FontError> {
+                attributes
+                    .get(key)
+                    .and_then(|value| value.parse().ok())
+                    .ok_or_else(|| {
+                        FontError::Bitmap(format!(
+                            "line {}: missing or invalid `{}`",
+                            number + 1,
+                            key
+                        ))
+                    })
+            };
+            match tag.as_str() {
+                "info" => font.size = attribute("size")?.abs(),
+                "common" => {
+                    font.line_height = attribute("lineHeight")?;
+                    font.base = attribute("base")?;
+                }
+                "page" => {
+                    let id = attribute("id")? as usize;
+                    let file = attributes.get("file").cloned().ok_or_else(|| {
+                        FontError::Bitmap(format!("line {}: missing `file`", number + 1))
+                    })?;
+                    if page_files.len() <= id {
+                        page_files.resize(id + 1, String::new());
+                    }
+                    page_files[id] = file;
+                }
+                "char" => {
+                    let c = match std::char::from_u32(attribute("id")? as u32) {
+                        Some(c) => c,
+                        None => continue,
+                    };
+                    font.chars.insert(
+                        c,
+                        BmChar {
+                            x: attribute("x")? as u32,
+                            y: attribute("y")? as u32,
+                            width: attribute("width")? as u32,
+                            height: attribute("height")? as u32,
+                            offset: [attribute("xoffset")?, attribute("yoffset")?],
+                            advance: attribute("xadvance")?,
+                            page: attribute("page")? as usize,
+                        },
+                    );
+                }
+                "kerning" => {
+                    let first = std::char::from_u32(attribute("first")? as u32);
+                    let second = std::char::from_u32(attribute("second")? as u32);
+                    if let (Some(first), Some(second)) = (first, second) {
+                        font.kernings.insert((first, second), attribute("amount")?);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok((font, page_files))
+    }
+
+    pub fn glyph(&self, c: char) -> Option<GlyphBitmap> {
+        let glyph = self.chars.get(&c)?;
+        let page = self.pages.get(glyph.page)?;
+        let mut pixels = Vec::with_capacity((glyph.width * glyph.height * 4) as usize);
+        for y in glyph.y..glyph.y + glyph.height {
+            let start = ((y * page.width + glyph.x) * 4) as usize;
+            let end = start + (glyph.width * 4) as usize;
+            pixels.extend_from_slice(page.pixels.get(start..end)?);
+        }
+        Some(GlyphBitmap {
+            width: glyph.width,
+            height: glyph.height,
+            // bmfont offsets are relative to the top of the line, not to the baseline
+            offset: [glyph.offset[0], glyph.offset[1] - self.base],
+            pixels,
+        })
+    }
+}
+
+// splits `tag key=value key="quoted value"` into (key, value) pairs, the tag has an empty value
+fn tokenize(line: &str) -> Vec<(String, String)> {
+    let mut tokens = vec![];
+    let mut chars = line.chars().peekable();
+    loop {
+        while matches!(chars.peek(), Some(c) if c.is_whitespace()) {
+            chars.next();
+        }
+        let mut key = String::new();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() || c == '=' {
+                break;
+            }
+            key.push(c);
+            chars.next();
+        }
+        if key.is_empty() {
+            break;
+        }
+        let mut value = String::new();
+        if chars.peek() == Some(&'=') {
+            chars.next();
+            if chars.peek() == Some(&'"') {
+                chars.next();
+                value = chars.by_ref().take_while(|c| *c != '"').collect();
+            } else {
+                value = chars.by_ref().take_while(|c| !c.is_whitespace()).collect();
+            }
+        }
+        tokens.push((key, value));
+    }
+    tokens
+}
+
+fn load_page(path: &Path) -> Result<Page, FontError> {
+    let mut decoder = png::Decoder::new(File::open(path)?);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let (info, mut reader) = decoder
+        .read_info()
+        .map_err(|error| FontError::Bitmap(format!("{}: {}", path.display(), error)))?;
+    let mut buffer = vec![0; info.buffer_size()];
+    reader
+        .next_frame(&mut buffer)
+        .map_err(|error| FontError::Bitmap(format!("{}: {}", path.display(), error)))?;
+    let pixels = match info.color_type {
+        png::ColorType::RGBA => buffer,
+        png::ColorType::RGB => buffer
+            .chunks(3)
+            .flat_map(|rgb| vec![rgb[0], rgb[1], rgb[2], 255])
+            .collect(),
+        // single channel pages store coverage, draw them as white glyphs
+        png::ColorType::Grayscale => buffer
+            .iter()
+            .flat_map(|&a| vec![255, 255, 255, a])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buffer
+            .chunks(2)
+            .flat_map(|la| vec![la[0], la[0], la[0], la[1]])
+            .collect(),
+        other => {
+            return Err(FontError::Bitmap(format!(
+                "{}: unsupported color type {:?}",
+                path.display(),
+                other
+            )))
+        }
+    };
+    Ok(Page {
+        width: info.width,
+        height: info.height,
+        pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESCRIPTOR: &str = r#"info face="Pixel Font" size=-16 bold=0
+common lineHeight=18 base=14 scaleW=64 scaleH=64 pages=1 packed=0
+page id=0 file="pixel font.png"
+chars count=2
+char id=65   x=0  y=0  width=8 height=10 xoffset=1 yoffset=4 xadvance=9 page=0 chnl=15
+char id=86   x=8  y=0  width=8 height=10 xoffset=0 yoffset=4 xadvance=9 page=0 chnl=15
+kernings count=1
+kerning first=65 second=86 amount=-1
+"#;
+
+    #[test]
+    fn tokenizes_quoted_values() {
+        let tokens = tokenize(r#"info face="Pixel Font" size=-16"#);
+        assert_eq!(tokens[0], ("info".to_string(), String::new()));
+        assert_eq!(tokens[1], ("face".to_string(), "Pixel Font".to_string()));
+        assert_eq!(tokens[2], ("size".to_string(), "-16".to_string()));
+    }
+
+    #[test]
+    fn parses_descriptor() {
+        let (font, pages) = BmFont::parse(DESCRIPTOR).unwrap();
+        assert_eq!(pages, vec!["pixel font.png".to_string()]);
+        assert_eq!(font.size, 16.0);
+        assert_eq!(font.line_height, 18.0);
+        assert_eq!(font.chars[&'V'].x, 8);
+        assert_eq!(font.chars[&'A'].offset, [1.0, 4.0]);
+        assert_eq!(font.kernings[&('A', 'V')], -1.0);
+    }
+
+    #[test]
+    fn reports_broken_lines() {
+        let error = BmFont::parse("common lineHeight=18").unwrap_err();
+        assert!(matches!(error, FontError::Bitmap(message) if message.contains("line 1")));
+    }
+}
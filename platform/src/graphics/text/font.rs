@@ -0,0 +1,138 @@
+use super::bmfont::BmFont;
+use super::layout::Metrics;
+use ab_glyph::{Font as _, FontVec, PxScale, ScaleFont};
+use std::ffi::OsStr;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum FontError {
+    Io(std::io::Error),
+    InvalidFont,
+    Bitmap(String),
+}
+
+impl From<std::io::Error> for FontError {
+    fn from(error: std::io::Error) -> Self {
+        FontError::Io(error)
+    }
+}
+
+// RGBA pixels of a single glyph, offset is from the pen position on the baseline
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlyphBitmap {
+    pub width: u32,
+    pub height: u32,
+    pub offset: [f32; 2],
+    pub pixels: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum Font {
+    Outline { font: FontVec, scale: PxScale },
+    Bitmap(BmFont),
+}
+
+impl Font {
+    // .fnt files are loaded as BMFont descriptors, anything else is parsed as TTF/OTF
+    // and rasterized at `size` pixels
+    pub fn from_file(path: &Path, size: f32) -> Result<Self, FontError> {
+        if path.extension() == Some(OsStr::new("fnt")) {
+            return Ok(Font::Bitmap(BmFont::from_file(path)?));
+        }
+        Self::from_data(std::fs::read(path)?, size)
+    }
+
+    // TTF/OTF only, BMFont pages are separate files
+    pub fn from_data(data: Vec<u8>, size: f32) -> Result<Self, FontError> {
+        let font = FontVec::try_from_vec(data).map_err(|_| FontError::InvalidFont)?;
+        Ok(Font::Outline {
+            font,
+            scale: PxScale::from(size),
+        })
+    }
+
+    pub fn size(&self) -> f32 {
+        match self {
+            Font::Outline { scale, .. } => scale.y,
+            Font::Bitmap(font) => font.size,
+        }
+    }
+
+    pub fn rasterize(&self, c: char) -> Option<GlyphBitmap> {
+        match self {
+            Font::Outline { font, scale } => {
+                let glyph = font.glyph_id(c).with_scale(*scale);
+                let outlined = match font.outline_glyph(glyph) {
+                    Some(outlined) => outlined,
+                    // whitespace has no outline but still needs an atlas entry
+                    None => {
+                        return Some(GlyphBitmap {
+                            width: 0,
+                            height: 0,
+                            offset: [0.0, 0.0],
+                            pixels: vec![],
+                        })
+                    }
+                };
+                let bounds = outlined.px_bounds();
+                let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+                let mut pixels: Vec<u8> = [255, 255, 255, 0]
+                    .iter()
+                    .cycle()
+                    .take((width * height * 4) as usize)
+                    .cloned()
+                    .collect();
+                outlined.draw(|x, y, coverage| {
+                    let alpha = (coverage.min(1.0) * 255.0) as u8;
+                    pixels[((y * width + x) * 4 + 3) as usize] = alpha;
+                });
+                Some(GlyphBitmap {
+                    width,
+                    height,
+                    offset: [bounds.min.x, bounds.min.y],
+                    pixels,
+                })
+            }
+            Font::Bitmap(font) => font.glyph(c),
+        }
+    }
+}
+
+impl Metrics for Font {
+    fn line_height(&self) -> f32 {
+        match self {
+            Font::Outline { font, scale } => {
+                let font = font.as_scaled(*scale);
+                font.height() + font.line_gap()
+            }
+            Font::Bitmap(font) => font.line_height,
+        }
+    }
+
+    fn ascent(&self) -> f32 {
+        match self {
+            Font::Outline { font, scale } => font.as_scaled(*scale).ascent(),
+            Font::Bitmap(font) => font.base,
+        }
+    }
+
+    fn advance(&self, c: char) -> f32 {
+        match self {
+            Font::Outline { font, scale } => {
+                let font = font.as_scaled(*scale);
+                font.h_advance(font.glyph_id(c))
+            }
+            Font::Bitmap(font) => font.chars.get(&c).map_or(0.0, |glyph| glyph.advance),
+        }
+    }
+
+    fn kerning(&self, left: char, right: char) -> f32 {
+        match self {
+            Font::Outline { font, scale } => {
+                let font = font.as_scaled(*scale);
+                font.kern(font.glyph_id(left), font.glyph_id(right))
+            }
+            Font::Bitmap(font) => font.kernings.get(&(left, right)).cloned().unwrap_or(0.0),
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use super::Align;
+
+// metrics are in font pixels, the renderer scales them to the requested text size
+pub trait Metrics {
+    fn line_height(&self) -> f32;
+    fn ascent(&self) -> f32;
+    fn advance(&self, c: char) -> f32;
+    fn kerning(&self, left: char, right: char) -> f32;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionedGlyph {
+    pub c: char,
+    // pen position on the baseline, relative to the top left corner of the text box
+    pub position: [f32; 2],
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Layout {
+    pub glyphs: Vec<PositionedGlyph>,
+    pub size: [f32; 2],
+}
+
+pub fn layout<M: Metrics>(
+    metrics: &M,
+    content: &str,
+    align: Align,
+    max_width: Option<f32>,
+) -> Layout {
+    let lines: Vec<Vec<char>> = content
+        .split('\n')
+        .flat_map(|paragraph| wrap(metrics, paragraph, max_width))
+        .collect();
+    let widths: Vec<f32> = lines.iter().map(|line| measure(metrics, line)).collect();
+    let box_width = max_width.unwrap_or_else(|| widths.iter().cloned().fold(0.0, f32::max));
+
+    let mut glyphs = vec![];
+    for (index, (line, width)) in lines.iter().zip(&widths).enumerate() {
+        let mut x = match align {
+            Align::Left => 0.0,
+            Align::Center => (box_width - width) / 2.0,
+            Align::Right => box_width - width,
+        };
+        let y = metrics.ascent() + index as f32 * metrics.line_height();
+        let mut previous = None;
+        for &c in line {
+            if let Some(previous) = previous {
+                x += metrics.kerning(previous, c);
+            }
+            glyphs.push(PositionedGlyph {
+                c,
+                position: [x, y],
+            });
+            x += metrics.advance(c);
+            previous = Some(c);
+        }
+    }
+
+    Layout {
+        glyphs,
+        size: [box_width, lines.len() as f32 * metrics.line_height()],
+    }
+}
+
+fn measure<M: Metrics>(metrics: &M, line: &[char]) -> f32 {
+    let advances: f32 = line.iter().map(|&c| metrics.advance(c)).sum();
+    let kerning: f32 = line
+        .windows(2)
+        .map(|pair| metrics.kerning(pair[0], pair[1]))
+        .sum();
+    advances + kerning
+}
+
+// greedy word wrap, words wider than the box are kept on a line of their own
+fn wrap<M: Metrics>(metrics: &M, paragraph: &str, max_width: Option<f32>) -> Vec<Vec<char>> {
+    let max_width = match max_width {
+        Some(max_width) => max_width,
+        None => return vec![paragraph.chars().collect()],
+    };
+    let mut lines = vec![];
+    let mut line: Vec<char> = vec![];
+    for word in paragraph.split(' ') {
+        let mut candidate = line.clone();
+        if !candidate.is_empty() {
+            candidate.push(' ');
+        }
+        candidate.extend(word.chars());
+        if !line.is_empty() && measure(metrics, &candidate) > max_width {
+            lines.push(line);
+            line = word.chars().collect();
+        } else {
+            line = candidate;
+        }
+    }
+    lines.push(line);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Monospace;
+    impl Metrics for Monospace {
+        fn line_height(&self) -> f32 {
+            20.0
+        }
+        fn ascent(&self) -> f32 {
+            15.0
+        }
+        fn advance(&self, _c: char) -> f32 {
+            10.0
+        }
+        fn kerning(&self, left: char, right: char) -> f32 {
+            if (left, right) == ('A', 'V') {
+                -2.0
+            } else {
+                0.0
+            }
+        }
+    }
+
+    fn xs(layout: &Layout) -> Vec<f32> {
+        layout
+            .glyphs
+            .iter()
+            .map(|glyph| glyph.position[0])
+            .collect()
+    }
+
+    #[test]
+    fn applies_kerning() {
+        let layout = layout(&Monospace, "AVA", Align::Left, None);
+        assert_eq!(xs(&layout), vec![0.0, 8.0, 18.0]);
+        assert_eq!(layout.size, [28.0, 20.0]);
+    }
+
+    #[test]
+    fn breaks_on_newlines() {
+        let layout = layout(&Monospace, "ab\ncd", Align::Left, None);
+        let ys: Vec<f32> = layout
+            .glyphs
+            .iter()
+            .map(|glyph| glyph.position[1])
+            .collect();
+        assert_eq!(ys, vec![15.0, 15.0, 35.0, 35.0]);
+        assert_eq!(layout.size, [20.0, 40.0]);
+    }
+
+    #[test]
+    fn wraps_words() {
+        let layout = layout(&Monospace, "aa bb cc", Align::Left, Some(50.0));
+        let lines: Vec<char> = layout.glyphs.iter().map(|glyph| glyph.c).collect();
+        assert_eq!(lines, "aa bbcc".chars().collect::<Vec<_>>());
+        assert_eq!(layout.glyphs[5].position, [0.0, 35.0]);
+        assert_eq!(layout.size, [50.0, 40.0]);
+    }
+
+    #[test]
+    fn keeps_long_words_whole() {
+        let layout = layout(&Monospace, "abcdefgh", Align::Left, Some(30.0));
+        assert_eq!(layout.glyphs.len(), 8);
+        assert_eq!(layout.size[1], 20.0);
+    }
+
+    #[test]
+    fn aligns_lines() {
+        let centered = layout(&Monospace, "a\nabc", Align::Center, None);
+        assert_eq!(xs(&centered), vec![10.0, 0.0, 10.0, 20.0]);
+        let right = layout(&Monospace, "a\nabc", Align::Right, Some(40.0));
+        assert_eq!(xs(&right), vec![30.0, 10.0, 20.0, 30.0]);
+    }
+}
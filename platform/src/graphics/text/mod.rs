@@ -0,0 +1,114 @@
+mod atlas;
+mod bmfont;
+mod font;
+mod layout;
+
+pub use atlas::{AtlasGlyph, GlyphAtlas};
+pub use font::{Font, FontError, GlyphBitmap};
+pub use layout::{layout, Layout, Metrics, PositionedGlyph};
+
+pub use common::render::{Align, Space, Text};
+
+use super::layer::Layer;
+use std::path::Path;
+
+pub const ATLAS_SIZE: u32 = 512;
+
+// a textured quad in the same space as the text it belongs to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlyphQuad {
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    pub uv: [f32; 4],
+    pub color: [f32; 4],
+    pub space: Space,
+    pub layer: Layer,
+    pub z: i16,
+}
+
+#[derive(Debug)]
+pub struct TextRenderer {
+    pub font: Option<Font>,
+    pub atlas: GlyphAtlas,
+    queue: Vec<Text>,
+}
+
+impl TextRenderer {
+    pub fn new(font_path: &Path, font_size: f32) -> Self {
+        let font = match Font::from_file(font_path, font_size) {
+            Ok(font) => Some(font),
+            Err(error) => {
+                log::warn!("Failed to load font {}: {:?}", font_path.display(), error);
+                None
+            }
+        };
+        Self::with_font(font)
+    }
+
+    pub fn with_font(font: Option<Font>) -> Self {
+        Self {
+            font,
+            atlas: GlyphAtlas::new(ATLAS_SIZE, ATLAS_SIZE),
+            queue: vec![],
+        }
+    }
+
+    pub fn queue(&mut self, text: Text) {
+        self.queue.push(text);
+    }
+
+    // lays out everything queued since the last call, rasterizing missing glyphs into the atlas
+    pub fn flush(&mut self) -> Vec<GlyphQuad> {
+        let font = match &self.font {
+            Some(font) => font,
+            None => {
+                self.queue.clear();
+                return vec![];
+            }
+        };
+        let mut quads = vec![];
+        for text in self.queue.drain(..) {
+            let scale = text.size / font.line_height();
+            let max_width = text.max_width.map(|width| width / scale);
+            let Layout { glyphs, .. } = layout(font, &text.content, text.align, max_width);
+            for PositionedGlyph { c, position } in glyphs {
+                let glyph = match self.atlas.glyph(font, c) {
+                    Some(glyph) if glyph.size[0] > 0.0 => glyph,
+                    _ => continue,
+                };
+                quads.push(GlyphQuad {
+                    position: [
+                        text.position[0] + (position[0] + glyph.offset[0]) * scale,
+                        text.position[1] + (position[1] + glyph.offset[1]) * scale,
+                    ],
+                    size: [glyph.size[0] * scale, glyph.size[1] * scale],
+                    uv: glyph.uv,
+                    color: text.color,
+                    space: text.space,
+                    layer: text.layer,
+                    z: text.z,
+                });
+            }
+        }
+        quads
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flushes_queued_text_into_quads() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets/fonts/DejaVuSansMono.ttf");
+        let mut text = TextRenderer::new(&path, 32.0);
+        text.queue(Text::new("a b", [10.0, 20.0], 16.0));
+        let quads = text.flush();
+        // the space has no outline and produces no quad
+        assert_eq!(quads.len(), 2);
+        assert!(quads[0].position[0] >= 10.0 && quads[0].position[1] > 20.0);
+        assert!(quads[1].position[0] > quads[0].position[0]);
+        assert!(text.atlas.dirty);
+        assert!(text.flush().is_empty());
+    }
+}
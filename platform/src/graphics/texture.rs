@@ -0,0 +1,481 @@
+use gfx_hal::{
+    adapter::PhysicalDevice,
+    buffer,
+    command::{BufferImageCopy, CommandBuffer, CommandBufferFlags, Level},
+    device::Device,
+    format::{Aspects, Format, ImageFeature, Swizzle},
+    image::{
+        Access, Extent, Filter, Kind, Layout, Offset, SamplerDesc, SubresourceLayers,
+        SubresourceRange, Tiling, Usage, ViewCapabilities, ViewKind, WrapMode,
+    },
+    memory::{Barrier, Dependencies, Properties, Segment},
+    pool::CommandPool,
+    pso::PipelineStage,
+    queue::CommandQueue,
+    MemoryTypeId,
+};
+
+use super::resources::FrameError;
+
+const COLOR_RANGE: SubresourceRange = SubresourceRange {
+    aspects: Aspects::COLOR,
+    level_start: 0,
+    level_count: Some(1),
+    layer_start: 0,
+    layer_count: Some(1),
+};
+
+// sampled RGBA8 image, pixels are uploaded through a staging buffer
+#[derive(Debug)]
+pub struct Texture<B: gfx_hal::Backend> {
+    pub image: B::Image,
+    pub memory: B::Memory,
+    pub view: B::ImageView,
+    pub sampler: B::Sampler,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl<B: gfx_hal::Backend> Texture<B> {
+    pub unsafe fn new(
+        device: &B::Device,
+        physical_device: &B::PhysicalDevice,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, FrameError> {
+        let (image, memory, view) = make_image::<B>(
+            device,
+            physical_device,
+            width,
+            height,
+            Format::Rgba8Unorm,
+            Usage::TRANSFER_DST | Usage::SAMPLED,
+            Aspects::COLOR,
+        )?;
+        let sampler =
+            match device.create_sampler(&SamplerDesc::new(Filter::Linear, WrapMode::Clamp)) {
+                Ok(sampler) => sampler,
+                Err(error) => {
+                    destroy_image::<B>(device, image, memory, view);
+                    return Err(FrameError::skipped("Failed to create sampler", error));
+                }
+            };
+
+        Ok(Self {
+            image,
+            memory,
+            view,
+            sampler,
+            width,
+            height,
+        })
+    }
+
+    // blocks until the copy has finished, the whole image is overwritten
+    pub unsafe fn upload(
+        &self,
+        device: &B::Device,
+        physical_device: &B::PhysicalDevice,
+        command_pool: &mut B::CommandPool,
+        queue: &mut B::CommandQueue,
+        pixels: &[u8],
+    ) -> Result<(), FrameError> {
+        let (staging_memory, staging_buffer) = make_buffer::<B>(
+            device,
+            physical_device,
+            pixels.len() as u64,
+            buffer::Usage::TRANSFER_SRC,
+            Properties::CPU_VISIBLE | Properties::COHERENT,
+        )?;
+        let mut command_buffer = command_pool.allocate_one(Level::Primary);
+        let uploaded = (|| {
+            let mapped = device
+                .map_memory(&staging_memory, Segment::ALL)
+                .map_err(|error| FrameError::skipped("Failed to map staging memory", error))?;
+            std::ptr::copy_nonoverlapping(pixels.as_ptr(), mapped, pixels.len());
+            device.unmap_memory(&staging_memory);
+            command_buffer.begin_primary(CommandBufferFlags::ONE_TIME_SUBMIT);
+            self.record_upload(&mut command_buffer, &staging_buffer);
+            command_buffer.finish();
+            submit_and_wait::<B>(device, queue, &command_buffer)
+        })();
+        command_pool.free(Some(command_buffer));
+        device.destroy_buffer(staging_buffer);
+        device.free_memory(staging_memory);
+        uploaded
+    }
+
+    unsafe fn record_upload(&self, command_buffer: &mut B::CommandBuffer, staging: &B::Buffer) {
+        command_buffer.pipeline_barrier(
+            PipelineStage::TOP_OF_PIPE..PipelineStage::TRANSFER,
+            Dependencies::empty(),
+            &[Barrier::Image {
+                states: (Access::empty(), Layout::Undefined)
+                    ..(Access::TRANSFER_WRITE, Layout::TransferDstOptimal),
+                target: &self.image,
+                families: None,
+                range: COLOR_RANGE,
+            }],
+        );
+        command_buffer.copy_buffer_to_image(
+            staging,
+            &self.image,
+            Layout::TransferDstOptimal,
+            &[BufferImageCopy {
+                buffer_offset: 0,
+                buffer_width: self.width,
+                buffer_height: self.height,
+                image_layers: SubresourceLayers {
+                    aspects: Aspects::COLOR,
+                    level: 0,
+                    layers: 0..1,
+                },
+                image_offset: Offset { x: 0, y: 0, z: 0 },
+                image_extent: Extent {
+                    width: self.width,
+                    height: self.height,
+                    depth: 1,
+                },
+            }],
+        );
+        command_buffer.pipeline_barrier(
+            PipelineStage::TRANSFER..PipelineStage::FRAGMENT_SHADER,
+            Dependencies::empty(),
+            &[Barrier::Image {
+                states: (Access::TRANSFER_WRITE, Layout::TransferDstOptimal)
+                    ..(Access::SHADER_READ, Layout::ShaderReadOnlyOptimal),
+                target: &self.image,
+                families: None,
+                range: COLOR_RANGE,
+            }],
+        );
+    }
+
+    pub unsafe fn destroy(self, device: &B::Device) {
+        device.destroy_sampler(self.sampler);
+        destroy_image::<B>(device, self.image, self.memory, self.view);
+    }
+}
+
+// offscreen color attachment that can be copied from, frames are read back through it
+#[derive(Debug)]
+pub struct ColorTarget<B: gfx_hal::Backend> {
+    pub image: B::Image,
+    pub memory: B::Memory,
+    pub view: B::ImageView,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl<B: gfx_hal::Backend> ColorTarget<B> {
+    pub unsafe fn new(
+        device: &B::Device,
+        physical_device: &B::PhysicalDevice,
+        format: Format,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, FrameError> {
+        let (image, memory, view) = make_image::<B>(
+            device,
+            physical_device,
+            width,
+            height,
+            format,
+            Usage::COLOR_ATTACHMENT | Usage::TRANSFER_SRC,
+            Aspects::COLOR,
+        )?;
+
+        Ok(Self {
+            image,
+            memory,
+            view,
+            width,
+            height,
+        })
+    }
+
+    // blocks until `record` has drawn into the target and its pixels are copied out
+    pub unsafe fn read<F>(
+        &self,
+        device: &B::Device,
+        physical_device: &B::PhysicalDevice,
+        command_pool: &mut B::CommandPool,
+        queue: &mut B::CommandQueue,
+        record: F,
+    ) -> Result<Vec<u8>, FrameError>
+    where
+        F: FnOnce(&mut B::CommandBuffer),
+    {
+        let size = (self.width * self.height * 4) as usize;
+        let (readback_memory, readback_buffer) = make_buffer::<B>(
+            device,
+            physical_device,
+            size as u64,
+            buffer::Usage::TRANSFER_DST,
+            Properties::CPU_VISIBLE | Properties::COHERENT,
+        )?;
+        let mut command_buffer = command_pool.allocate_one(Level::Primary);
+        let pixels = (|| {
+            command_buffer.begin_primary(CommandBufferFlags::ONE_TIME_SUBMIT);
+            record(&mut command_buffer);
+            self.record_copy(&mut command_buffer, &readback_buffer);
+            command_buffer.finish();
+            submit_and_wait::<B>(device, queue, &command_buffer)?;
+            let mapped = device
+                .map_memory(&readback_memory, Segment::ALL)
+                .map_err(|error| FrameError::skipped("Failed to map readback memory", error))?;
+            let mut pixels = vec![0; size];
+            std::ptr::copy_nonoverlapping(mapped, pixels.as_mut_ptr(), size);
+            device.unmap_memory(&readback_memory);
+            Ok(pixels)
+        })();
+        command_pool.free(Some(command_buffer));
+        device.destroy_buffer(readback_buffer);
+        device.free_memory(readback_memory);
+        pixels
+    }
+
+    // after whatever was drawn into the target
+    unsafe fn record_copy(&self, command_buffer: &mut B::CommandBuffer, readback: &B::Buffer) {
+        let (width, height) = (self.width, self.height);
+        command_buffer.pipeline_barrier(
+            PipelineStage::COLOR_ATTACHMENT_OUTPUT..PipelineStage::TRANSFER,
+            Dependencies::empty(),
+            &[Barrier::Image {
+                states: (Access::COLOR_ATTACHMENT_WRITE, Layout::TransferSrcOptimal)
+                    ..(Access::TRANSFER_READ, Layout::TransferSrcOptimal),
+                target: &self.image,
+                families: None,
+                range: COLOR_RANGE,
+            }],
+        );
+        command_buffer.copy_image_to_buffer(
+            &self.image,
+            Layout::TransferSrcOptimal,
+            readback,
+            &[BufferImageCopy {
+                buffer_offset: 0,
+                buffer_width: width,
+                buffer_height: height,
+                image_layers: SubresourceLayers {
+                    aspects: Aspects::COLOR,
+                    level: 0,
+                    layers: 0..1,
+                },
+                image_offset: Offset { x: 0, y: 0, z: 0 },
+                image_extent: Extent {
+                    width,
+                    height,
+                    depth: 1,
+                },
+            }],
+        );
+    }
+
+    pub unsafe fn destroy(self, device: &B::Device) {
+        destroy_image::<B>(device, self.image, self.memory, self.view);
+    }
+}
+
+// depth attachment matching the swapchain extent, recreated together with the swapchain
+#[derive(Debug)]
+pub struct DepthBuffer<B: gfx_hal::Backend> {
+    pub image: B::Image,
+    pub memory: B::Memory,
+    pub view: B::ImageView,
+}
+
+impl<B: gfx_hal::Backend> DepthBuffer<B> {
+    pub unsafe fn new(
+        device: &B::Device,
+        physical_device: &B::PhysicalDevice,
+        format: Format,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, FrameError> {
+        let (image, memory, view) = make_image::<B>(
+            device,
+            physical_device,
+            width,
+            height,
+            format,
+            Usage::DEPTH_STENCIL_ATTACHMENT,
+            Aspects::DEPTH,
+        )?;
+
+        Ok(Self {
+            image,
+            memory,
+            view,
+        })
+    }
+
+    pub unsafe fn destroy(self, device: &B::Device) {
+        destroy_image::<B>(device, self.image, self.memory, self.view);
+    }
+}
+
+// a 2D image, the memory bound to it and a view of it
+type ImageParts<B> = (
+    <B as gfx_hal::Backend>::Image,
+    <B as gfx_hal::Backend>::Memory,
+    <B as gfx_hal::Backend>::ImageView,
+);
+
+// an image bound to memory of its own, a step that fails destroys what the ones before it
+// created
+unsafe fn make_image<B: gfx_hal::Backend>(
+    device: &B::Device,
+    physical_device: &B::PhysicalDevice,
+    width: u32,
+    height: u32,
+    format: Format,
+    usage: Usage,
+    aspects: Aspects,
+) -> Result<ImageParts<B>, FrameError> {
+    let mut image = device
+        .create_image(
+            Kind::D2(width, height, 1, 1),
+            1,
+            format,
+            Tiling::Optimal,
+            usage,
+            ViewCapabilities::empty(),
+        )
+        .map_err(|error| FrameError::skipped("Failed to create image", error))?;
+    let requirements = device.get_image_requirements(&image);
+    let memory = find_memory_type::<B>(
+        physical_device,
+        requirements.type_mask,
+        Properties::DEVICE_LOCAL,
+    )
+    .and_then(|memory_type| {
+        device
+            .allocate_memory(memory_type, requirements.size)
+            .map_err(|error| FrameError::skipped("Failed to allocate image memory", error))
+    });
+    let memory = match memory {
+        Ok(memory) => memory,
+        Err(error) => {
+            device.destroy_image(image);
+            return Err(error);
+        }
+    };
+    let range = SubresourceRange {
+        aspects,
+        ..COLOR_RANGE
+    };
+    let view = device
+        .bind_image_memory(&memory, 0, &mut image)
+        .map_err(|error| FrameError::skipped("Failed to bind image memory", error))
+        .and_then(|()| {
+            device
+                .create_image_view(&image, ViewKind::D2, format, Swizzle::NO, range)
+                .map_err(|error| FrameError::skipped("Failed to create image view", error))
+        });
+    match view {
+        Ok(view) => Ok((image, memory, view)),
+        Err(error) => {
+            device.destroy_image(image);
+            device.free_memory(memory);
+            Err(error)
+        }
+    }
+}
+
+unsafe fn destroy_image<B: gfx_hal::Backend>(
+    device: &B::Device,
+    image: B::Image,
+    memory: B::Memory,
+    view: B::ImageView,
+) {
+    device.destroy_image_view(view);
+    device.destroy_image(image);
+    device.free_memory(memory);
+}
+
+// submits `command_buffer` on its own and blocks until it has run
+unsafe fn submit_and_wait<B: gfx_hal::Backend>(
+    device: &B::Device,
+    queue: &mut B::CommandQueue,
+    command_buffer: &B::CommandBuffer,
+) -> Result<(), FrameError> {
+    let fence = device.create_fence(false)?;
+    queue.submit_without_semaphores(Some(command_buffer), Some(&fence));
+    let finished = device.wait_for_fence(&fence, !0);
+    device.destroy_fence(fence);
+    finished?;
+    Ok(())
+}
+
+pub fn depth_format<B: gfx_hal::Backend>(
+    physical_device: &B::PhysicalDevice,
+) -> Result<Format, FrameError> {
+    [
+        Format::D32Sfloat,
+        Format::D32SfloatS8Uint,
+        Format::D24UnormS8Uint,
+    ]
+    .iter()
+    .cloned()
+    .find(|&format| {
+        physical_device
+            .format_properties(Some(format))
+            .optimal_tiling
+            .contains(ImageFeature::DEPTH_STENCIL_ATTACHMENT)
+    })
+    .ok_or_else(|| FrameError::Skipped("No supported depth format".to_string()))
+}
+
+pub unsafe fn make_buffer<B: gfx_hal::Backend>(
+    device: &B::Device,
+    physical_device: &B::PhysicalDevice,
+    size: u64,
+    usage: buffer::Usage,
+    properties: Properties,
+) -> Result<(B::Memory, B::Buffer), FrameError> {
+    let mut buffer = device
+        .create_buffer(size, usage)
+        .map_err(|error| FrameError::skipped("Failed to create buffer", error))?;
+    let requirements = device.get_buffer_requirements(&buffer);
+    let memory = find_memory_type::<B>(physical_device, requirements.type_mask, properties)
+        .and_then(|memory_type| {
+            device
+                .allocate_memory(memory_type, requirements.size)
+                .map_err(|error| FrameError::skipped("Failed to allocate buffer memory", error))
+        });
+    let bound =
+        memory.and_then(
+            |memory| match device.bind_buffer_memory(&memory, 0, &mut buffer) {
+                Ok(()) => Ok(memory),
+                Err(error) => {
+                    device.free_memory(memory);
+                    Err(FrameError::skipped("Failed to bind buffer memory", error))
+                }
+            },
+        );
+    match bound {
+        Ok(memory) => Ok((memory, buffer)),
+        Err(error) => {
+            device.destroy_buffer(buffer);
+            Err(error)
+        }
+    }
+}
+
+pub fn find_memory_type<B: gfx_hal::Backend>(
+    physical_device: &B::PhysicalDevice,
+    type_mask: u32,
+    properties: Properties,
+) -> Result<MemoryTypeId, FrameError> {
+    physical_device
+        .memory_properties()
+        .memory_types
+        .iter()
+        .enumerate()
+        .find(|(id, memory_type)| {
+            type_mask & (1 << id) != 0 && memory_type.properties.contains(properties)
+        })
+        .map(|(id, _)| MemoryTypeId(id))
+        .ok_or_else(|| FrameError::Skipped("No compatible memory type available".to_string()))
+}
@@ -0,0 +1,149 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// how many recent frames the statistics cover
+const WINDOW: usize = 240;
+// sleeping is only trusted up to this close to the deadline, the rest is spent spinning
+const SPIN: Duration = Duration::from_millis(1);
+// frames taking this many times the average count as hitches
+const HITCH_FACTOR: f32 = 2.0;
+
+// keeps frames at least `1 / fps` apart
+#[derive(Debug)]
+pub struct FrameLimiter {
+    pub frame_time: Duration,
+    deadline: Option<Instant>,
+}
+
+impl FrameLimiter {
+    pub fn new(fps: f32) -> Self {
+        Self {
+            frame_time: Duration::from_secs_f32(1.0 / fps),
+            deadline: None,
+        }
+    }
+
+    // blocks until the next frame is due. A frame that ran late moves the schedule instead of
+    // letting the following ones catch up
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        let deadline = match self.deadline {
+            Some(deadline) if deadline > now => deadline,
+            _ => now,
+        };
+        if let Some(sleep) = deadline.checked_duration_since(now + SPIN) {
+            std::thread::sleep(sleep);
+        }
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+        self.deadline = Some(deadline + self.frame_time);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameSummary {
+    // milliseconds
+    pub average: f32,
+    pub p99: f32,
+    pub worst: f32,
+    pub fps: f32,
+    pub hitches: usize,
+}
+
+// frame times of the last `WINDOW` frames
+#[derive(Debug, Default)]
+pub struct FrameStats {
+    frames: VecDeque<Duration>,
+    last: Option<Instant>,
+}
+
+impl FrameStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // call once per presented frame
+    pub fn tick(&mut self, now: Instant) {
+        if let Some(last) = self.last.replace(now) {
+            self.record(now - last);
+        }
+    }
+
+    pub fn record(&mut self, frame_time: Duration) {
+        if self.frames.len() == WINDOW {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(frame_time);
+    }
+
+    pub fn summary(&self) -> FrameSummary {
+        if self.frames.is_empty() {
+            return FrameSummary::default();
+        }
+        let mut milliseconds: Vec<f32> = self
+            .frames
+            .iter()
+            .map(|frame| frame.as_secs_f32() * 1000.0)
+            .collect();
+        let average = milliseconds.iter().sum::<f32>() / milliseconds.len() as f32;
+        let hitches = milliseconds
+            .iter()
+            .filter(|&&frame| frame > average * HITCH_FACTOR)
+            .count();
+        milliseconds.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        // nearest rank
+        let rank = (milliseconds.len() as f32 * 0.99).ceil() as usize;
+        FrameSummary {
+            average,
+            p99: milliseconds[rank.max(1) - 1],
+            worst: milliseconds[milliseconds.len() - 1],
+            fps: 1000.0 / average,
+            hitches,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarizes_frame_times() {
+        let mut stats = FrameStats::new();
+        assert_eq!(stats.summary(), FrameSummary::default());
+        for _ in 0..98 {
+            stats.record(Duration::from_millis(10));
+        }
+        stats.record(Duration::from_millis(50));
+        stats.record(Duration::from_millis(110));
+        let summary = stats.summary();
+        assert!((summary.average - 11.4).abs() < 1e-3);
+        assert!((summary.p99 - 50.0).abs() < 1e-3);
+        assert!((summary.worst - 110.0).abs() < 1e-3);
+        assert_eq!(summary.hitches, 2);
+    }
+
+    #[test]
+    fn forgets_old_frames() {
+        let mut stats = FrameStats::new();
+        stats.record(Duration::from_millis(100));
+        for _ in 0..WINDOW {
+            stats.record(Duration::from_millis(4));
+        }
+        let summary = stats.summary();
+        assert!((summary.worst - 4.0).abs() < 1e-3);
+        assert!((summary.fps - 250.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn limits_frame_rate() {
+        let mut limiter = FrameLimiter::new(200.0);
+        let start = Instant::now();
+        for _ in 0..5 {
+            limiter.wait();
+        }
+        // the first frame is due immediately
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+}
@@ -0,0 +1,277 @@
+use common::event::PlatformEvent;
+use common::ui::UiInput;
+
+// TODO: remove winit dependency
+use winit::event::{ElementState, Event as WEvent, MouseButton, VirtualKeyCode, WindowEvent};
+
+// pointer state for the ui, fed the same events as the world
+#[derive(Debug, Default)]
+pub struct PointerInput {
+    state: UiInput,
+}
+
+impl PointerInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn handle_event(&mut self, event: &WEvent<PlatformEvent>) {
+        if let WEvent::WindowEvent { event, .. } = event {
+            apply(&mut self.state, event);
+        }
+    }
+
+    // the state for this frame, presses and releases are only reported once
+    pub fn take(&mut self) -> UiInput {
+        let state = self.state;
+        self.state.pressed = false;
+        self.state.released = false;
+        state
+    }
+}
+
+fn apply(state: &mut UiInput, event: &WindowEvent) {
+    match event {
+        WindowEvent::CursorMoved { position, .. } => {
+            state.cursor = Some([position.x as f32, position.y as f32]);
+        }
+        WindowEvent::CursorLeft { .. } => state.cursor = None,
+        WindowEvent::MouseInput {
+            state: button_state,
+            button: MouseButton::Left,
+            ..
+        } => {
+            let down = *button_state == ElementState::Pressed;
+            state.pressed |= down && !state.down;
+            state.released |= !down && state.down;
+            state.down = down;
+        }
+        _ => {}
+    }
+}
+
+// by the name it's debug printed as, `Key1` or `Escape`
+pub fn key_from_name(name: &str) -> Option<VirtualKeyCode> {
+    all_keys()
+        .iter()
+        .find(|key| format!("{:?}", key) == name)
+        .copied()
+}
+
+pub fn key_name(key: VirtualKeyCode) -> String {
+    format!("{:?}", key)
+}
+
+fn all_keys() -> &'static [VirtualKeyCode] {
+    use VirtualKeyCode::*;
+    &[
+        Key1,
+        Key2,
+        Key3,
+        Key4,
+        Key5,
+        Key6,
+        Key7,
+        Key8,
+        Key9,
+        Key0,
+        A,
+        B,
+        C,
+        D,
+        E,
+        F,
+        G,
+        H,
+        I,
+        J,
+        K,
+        L,
+        M,
+        N,
+        O,
+        P,
+        Q,
+        R,
+        S,
+        T,
+        U,
+        V,
+        W,
+        X,
+        Y,
+        Z,
+        Escape,
+        F1,
+        F2,
+        F3,
+        F4,
+        F5,
+        F6,
+        F7,
+        F8,
+        F9,
+        F10,
+        F11,
+        F12,
+        F13,
+        F14,
+        F15,
+        F16,
+        F17,
+        F18,
+        F19,
+        F20,
+        F21,
+        F22,
+        F23,
+        F24,
+        Snapshot,
+        Scroll,
+        Pause,
+        Insert,
+        Home,
+        Delete,
+        End,
+        PageDown,
+        PageUp,
+        Left,
+        Up,
+        Right,
+        Down,
+        Back,
+        Return,
+        Space,
+        Compose,
+        Caret,
+        Numlock,
+        Numpad0,
+        Numpad1,
+        Numpad2,
+        Numpad3,
+        Numpad4,
+        Numpad5,
+        Numpad6,
+        Numpad7,
+        Numpad8,
+        Numpad9,
+        NumpadAdd,
+        NumpadDivide,
+        NumpadDecimal,
+        NumpadComma,
+        NumpadEnter,
+        NumpadEquals,
+        NumpadMultiply,
+        NumpadSubtract,
+        AbntC1,
+        AbntC2,
+        Apostrophe,
+        Apps,
+        Asterisk,
+        At,
+        Ax,
+        Backslash,
+        Calculator,
+        Capital,
+        Colon,
+        Comma,
+        Convert,
+        Equals,
+        Grave,
+        Kana,
+        Kanji,
+        LAlt,
+        LBracket,
+        LControl,
+        LShift,
+        LWin,
+        Mail,
+        MediaSelect,
+        MediaStop,
+        Minus,
+        Mute,
+        MyComputer,
+        NavigateForward,
+        NavigateBackward,
+        NextTrack,
+        NoConvert,
+        OEM102,
+        Period,
+        PlayPause,
+        Plus,
+        Power,
+        PrevTrack,
+        RAlt,
+        RBracket,
+        RControl,
+        RShift,
+        RWin,
+        Semicolon,
+        Slash,
+        Sleep,
+        Stop,
+        Sysrq,
+        Tab,
+        Underline,
+        Unlabeled,
+        VolumeDown,
+        VolumeUp,
+        Wake,
+        WebBack,
+        WebFavorites,
+        WebForward,
+        WebHome,
+        WebRefresh,
+        WebSearch,
+        WebStop,
+        Yen,
+        Copy,
+        Paste,
+        Cut,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::dpi::PhysicalPosition;
+    use winit::event::{DeviceId, ModifiersState};
+
+    #[allow(deprecated)]
+    fn click(state: ElementState) -> WindowEvent<'static> {
+        WindowEvent::MouseInput {
+            device_id: unsafe { DeviceId::dummy() },
+            state,
+            button: MouseButton::Left,
+            modifiers: ModifiersState::empty(),
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn tracks_cursor_and_clicks() {
+        let mut state = UiInput::default();
+        apply(
+            &mut state,
+            &WindowEvent::CursorMoved {
+                device_id: unsafe { DeviceId::dummy() },
+                position: PhysicalPosition::new(12.0, 34.0),
+                modifiers: ModifiersState::empty(),
+            },
+        );
+        apply(&mut state, &click(ElementState::Pressed));
+        assert_eq!(state.cursor, Some([12.0, 34.0]));
+        assert!(state.down && state.pressed && !state.released);
+        apply(&mut state, &click(ElementState::Released));
+        assert!(!state.down && state.released);
+    }
+
+    #[test]
+    fn names_keys_both_ways() {
+        for key in all_keys() {
+            assert_eq!(key_from_name(&key_name(*key)), Some(*key));
+        }
+        assert_eq!(key_from_name("W"), Some(VirtualKeyCode::W));
+        assert_eq!(key_from_name("w"), None);
+    }
+}
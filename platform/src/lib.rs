@@ -1,34 +1,55 @@
+pub mod assets;
+pub mod audio;
 pub mod graphics;
-use graphics::renderer::Renderer;
+use assets::AssetManager;
+use common::event::PlatformEvent;
+use common::render::RenderCommands;
+use graphics::{config::GraphicsConfig, renderer::Renderer};
+pub mod input;
+pub mod replay;
 pub mod window;
 use queue::{event::Event, receiver::Receiver};
 use window::Window;
 use winit::event::Event as WEvent;
-use world::WorldState;
 
 pub const APP_NAME: &'static str = "Gamey";
 
 #[derive(Debug)]
 pub struct Platform<'a> {
+    pub window: Window,
     pub graphics: Renderer<'a>,
+    pub events: Receiver<Event<WEvent<'a, PlatformEvent>>>,
 }
 
 impl<'a> Platform<'a> {
-    pub fn start(window: &Window, events: Receiver<Event<WEvent<'a, ()>>>) -> Result<Self, ()> {
-        let graphics = Renderer::new(&window, events)?;
+    pub fn start(
+        window: Window,
+        config: GraphicsConfig,
+        events: Receiver<Event<WEvent<'a, PlatformEvent>>>,
+        assets: &AssetManager,
+    ) -> Result<Self, ()> {
+        let graphics = Renderer::new(&window, config, events.clone(), assets)?;
 
-        Ok(Self { graphics })
+        Ok(Self {
+            window,
+            graphics,
+            events,
+        })
     }
 
-    pub fn proccess_events(&mut self, world: &WorldState) {
-        &self.graphics.update(&world);
+    pub fn proccess_events(&mut self, commands: &RenderCommands) {
+        let event = self.events.try_recv().unwrap();
+        self.window.update(&event.payload);
+        &self.graphics.update(commands);
     }
 }
 
 #[no_mangle]
 pub fn build_platform<'a>(
-    window: &Window,
-    events: Receiver<Event<WEvent<'a, ()>>>,
+    window: Window,
+    config: GraphicsConfig,
+    events: Receiver<Event<WEvent<'a, PlatformEvent>>>,
+    assets: &AssetManager,
 ) -> Platform<'a> {
-    Platform::start(window, events).unwrap()
+    Platform::start(window, config, events, assets).unwrap()
 }
@@ -0,0 +1,310 @@
+use crate::input::{key_from_name, key_name};
+use common::event::PlatformEvent;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+// TODO: remove winit dependency
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::{
+    DeviceId, ElementState, Event as WEvent, KeyboardInput, ModifiersState, MouseButton,
+    MouseScrollDelta, TouchPhase, VirtualKeyCode, WindowEvent,
+};
+use winit::window::WindowId;
+
+// the input of a run, tick by tick, as text with one line per tick or event:
+//   seed <seed>
+//   tick <seconds since the last tick>
+//   key <scancode> <key name or -> pressed|released [synthetic]
+//   char <code point>
+//   cursor <x> <y>
+//   cursor-left
+//   button left|right|middle|<number> pressed|released
+//   wheel lines|pixels <x> <y>
+//   resized <width> <height>
+//   focused true|false
+// everything else the window reports isn't recorded, the world doesn't look at it
+
+// what the simulation was fed in one tick
+#[derive(Debug, Clone)]
+pub struct Tick {
+    pub dt: f32,
+    pub events: Vec<WEvent<'static, PlatformEvent>>,
+}
+
+// writes a recording as the game runs
+#[derive(Debug)]
+pub struct Recorder {
+    out: BufWriter<File>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path, seed: u64) -> Result<Self, String> {
+        let file = File::create(path)
+            .map_err(|error| format!("Failed to create {:?}: {}", path, error))?;
+        let mut recorder = Self {
+            out: BufWriter::new(file),
+        };
+        recorder.line(format!("seed {}", seed))?;
+        Ok(recorder)
+    }
+
+    pub fn tick(
+        &mut self,
+        dt: f32,
+        events: &[WEvent<'static, PlatformEvent>],
+    ) -> Result<(), String> {
+        self.line(format!("tick {}", dt))?;
+        for event in events {
+            if let WEvent::WindowEvent { event, .. } = event {
+                if let Some(line) = encode(event) {
+                    self.line(line)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn line(&mut self, line: String) -> Result<(), String> {
+        writeln!(self.out, "{}", line).map_err(|error| error.to_string())
+    }
+}
+
+// a recording being played back, one tick at a time
+#[derive(Debug, Clone)]
+pub struct Replay {
+    pub seed: u64,
+    ticks: VecDeque<Tick>,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| format!("Failed to read {:?}: {}", path, error))?;
+        Self::parse(&text).map_err(|error| format!("{:?}: {}", path, error))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut seed = None;
+        let mut ticks = VecDeque::new();
+        for (number, line) in text.lines().enumerate() {
+            let at = |error: String| format!("line {}: {}", number + 1, error);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                [] => {}
+                ["seed", value] if seed.is_none() => {
+                    seed = Some(parse(value).map_err(at)?);
+                }
+                ["tick", dt] => ticks.push_back(Tick {
+                    dt: parse(dt).map_err(at)?,
+                    events: vec![],
+                }),
+                words => {
+                    let tick = ticks
+                        .back_mut()
+                        .ok_or_else(|| at("event before the first tick".to_string()))?;
+                    let event = decode(words).map_err(at)?;
+                    tick.events.push(WEvent::WindowEvent {
+                        window_id: unsafe { WindowId::dummy() },
+                        event,
+                    });
+                }
+            }
+        }
+        Ok(Self {
+            seed: seed.ok_or("no seed")?,
+            ticks,
+        })
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.ticks.len()
+    }
+}
+
+impl Iterator for Replay {
+    type Item = Tick;
+
+    fn next(&mut self) -> Option<Tick> {
+        self.ticks.pop_front()
+    }
+}
+
+fn parse<T: std::str::FromStr>(word: &str) -> Result<T, String> {
+    word.parse().map_err(|_| format!("`{}` is invalid", word))
+}
+
+fn state_name(state: ElementState) -> &'static str {
+    match state {
+        ElementState::Pressed => "pressed",
+        ElementState::Released => "released",
+    }
+}
+
+fn parse_state(word: &str) -> Result<ElementState, String> {
+    match word {
+        "pressed" => Ok(ElementState::Pressed),
+        "released" => Ok(ElementState::Released),
+        _ => Err(format!("`{}` is neither pressed nor released", word)),
+    }
+}
+
+fn parse_key(word: &str) -> Result<Option<VirtualKeyCode>, String> {
+    match word {
+        "-" => Ok(None),
+        name => key_from_name(name)
+            .map(Some)
+            .ok_or_else(|| format!("no key is called `{}`", name)),
+    }
+}
+
+fn encode(event: &WindowEvent) -> Option<String> {
+    let line = match event {
+        WindowEvent::KeyboardInput {
+            input,
+            is_synthetic,
+            ..
+        } => format!(
+            "key {} {} {}{}",
+            input.scancode,
+            input.virtual_keycode.map_or("-".to_string(), key_name),
+            state_name(input.state),
+            if *is_synthetic { " synthetic" } else { "" }
+        ),
+        WindowEvent::ReceivedCharacter(c) => format!("char {}", *c as u32),
+        WindowEvent::CursorMoved { position, .. } => {
+            format!("cursor {} {}", position.x, position.y)
+        }
+        WindowEvent::CursorLeft { .. } => "cursor-left".to_string(),
+        WindowEvent::MouseInput { state, button, .. } => {
+            let button = match button {
+                MouseButton::Left => "left".to_string(),
+                MouseButton::Right => "right".to_string(),
+                MouseButton::Middle => "middle".to_string(),
+                MouseButton::Other(button) => button.to_string(),
+            };
+            format!("button {} {}", button, state_name(*state))
+        }
+        WindowEvent::MouseWheel { delta, .. } => match delta {
+            MouseScrollDelta::LineDelta(x, y) => format!("wheel lines {} {}", x, y),
+            MouseScrollDelta::PixelDelta(delta) => {
+                format!("wheel pixels {} {}", delta.x, delta.y)
+            }
+        },
+        WindowEvent::Resized(size) => format!("resized {} {}", size.width, size.height),
+        WindowEvent::Focused(focused) => format!("focused {}", focused),
+        _ => return None,
+    };
+    Some(line)
+}
+
+// the device and modifiers aren't recorded, nothing reads them
+#[allow(deprecated)]
+fn decode(words: &[&str]) -> Result<WindowEvent<'static>, String> {
+    let device_id = unsafe { DeviceId::dummy() };
+    let modifiers = ModifiersState::empty();
+    let event = match words {
+        ["key", scancode, key, state, rest @ ..] => WindowEvent::KeyboardInput {
+            device_id,
+            input: KeyboardInput {
+                scancode: parse(scancode)?,
+                state: parse_state(state)?,
+                virtual_keycode: parse_key(key)?,
+                modifiers,
+            },
+            is_synthetic: rest == ["synthetic"],
+        },
+        ["char", code] => {
+            let c = std::char::from_u32(parse(code)?).ok_or("not a character")?;
+            WindowEvent::ReceivedCharacter(c)
+        }
+        ["cursor", x, y] => WindowEvent::CursorMoved {
+            device_id,
+            position: PhysicalPosition::new(parse(x)?, parse(y)?),
+            modifiers,
+        },
+        ["cursor-left"] => WindowEvent::CursorLeft { device_id },
+        ["button", button, state] => WindowEvent::MouseInput {
+            device_id,
+            state: parse_state(state)?,
+            button: match *button {
+                "left" => MouseButton::Left,
+                "right" => MouseButton::Right,
+                "middle" => MouseButton::Middle,
+                button => MouseButton::Other(parse(button)?),
+            },
+            modifiers,
+        },
+        ["wheel", kind, x, y] => WindowEvent::MouseWheel {
+            device_id,
+            delta: match *kind {
+                "lines" => MouseScrollDelta::LineDelta(parse(x)?, parse(y)?),
+                "pixels" => {
+                    MouseScrollDelta::PixelDelta(PhysicalPosition::new(parse(x)?, parse(y)?))
+                }
+                kind => return Err(format!("`{}` is neither lines nor pixels", kind)),
+            },
+            phase: TouchPhase::Moved,
+            modifiers,
+        },
+        ["resized", width, height] => {
+            WindowEvent::Resized(PhysicalSize::new(parse(width)?, parse(height)?))
+        }
+        ["focused", focused] => WindowEvent::Focused(parse(focused)?),
+        words => return Err(format!("unknown event `{}`", words.join(" "))),
+    };
+    Ok(event)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plays_back_what_was_recorded() {
+        let events = Replay::parse(
+            "seed 0\n\
+             tick 0\n\
+             key 17 W pressed\n\
+             key 15 Tab released synthetic\n\
+             key 99 - pressed\n\
+             char 119\n\
+             cursor 12.5 -3\n\
+             cursor-left\n\
+             button left pressed\n\
+             button 8 released\n\
+             wheel lines 0 -1\n\
+             wheel pixels 1.5 2\n\
+             resized 800 600\n\
+             focused false\n",
+        )
+        .unwrap()
+        .next()
+        .unwrap()
+        .events;
+        let path = std::env::temp_dir().join(format!("replay-{}.txt", std::process::id()));
+        let mut recorder = Recorder::create(&path, 42).unwrap();
+        recorder.tick(0.016, &[]).unwrap();
+        recorder.tick(0.25, &events).unwrap();
+        drop(recorder);
+        let mut replay = Replay::load(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(replay.seed, 42);
+        assert_eq!(replay.remaining(), 2);
+        assert!(replay.next().unwrap().events.is_empty());
+        let tick = replay.next().unwrap();
+        assert_eq!(tick.dt, 0.25);
+        assert_eq!(tick.events, events);
+        assert!(replay.next().is_none());
+    }
+
+    #[test]
+    fn names_the_broken_line() {
+        let error = Replay::parse("seed 1\ntick 0.1\nkey 17 Www pressed\n").unwrap_err();
+        assert_eq!(error, "line 3: no key is called `Www`");
+        let error = Replay::parse("seed 1\ncursor 1 2\n").unwrap_err();
+        assert_eq!(error, "line 2: event before the first tick");
+        assert_eq!(Replay::parse("tick 1\n").unwrap_err(), "no seed");
+    }
+}
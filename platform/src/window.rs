@@ -1,44 +1,453 @@
 use super::APP_NAME;
+use common::event::{FullscreenMode, PlatformEvent, WindowChange};
+use common::settings::{boolean, invalid, string};
 use gfx_hal::window::Extent2D;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use toml::value::{Table, Value};
 use winit::{
-    dpi::{LogicalSize, PhysicalSize},
-    event_loop::EventLoop,
-    window,
-    window::WindowBuilder,
+    dpi::{LogicalSize, PhysicalPosition, PhysicalSize},
+    event::{ElementState, Event as WEvent, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event_loop::{EventLoop, EventLoopProxy},
+    monitor::MonitorHandle,
+    window::{self, Fullscreen, Icon, WindowBuilder},
 };
 
-const WINDOW_SIZE: [u16; 2] = [512, 512];
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowConfig {
+    pub title: String,
+    // logical pixels
+    pub size: [u32; 2],
+    // physical pixels, of the outer top left corner. Left to the window manager when None
+    pub position: Option<[i32; 2]>,
+    pub resizable: bool,
+    pub min_size: Option<[u32; 2]>,
+    pub max_size: Option<[u32; 2]>,
+    // F11 toggles borderless at runtime
+    pub fullscreen: FullscreenMode,
+    // by name, the primary monitor when None or not connected
+    pub monitor: Option<String>,
+    // a png
+    pub icon: Option<PathBuf>,
+    pub cursor_visible: bool,
+    // keeps the cursor inside of the window
+    pub cursor_grab: bool,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: APP_NAME.to_string(),
+            size: [512, 512],
+            position: None,
+            resizable: true,
+            min_size: None,
+            max_size: None,
+            fullscreen: FullscreenMode::Windowed,
+            monitor: None,
+            icon: None,
+            cursor_visible: true,
+            cursor_grab: false,
+        }
+    }
+}
+
+impl WindowConfig {
+    // one key of the `window` section, the error says what's wrong with the value
+    pub fn set(&mut self, key: &str, value: &Value) -> Result<(), String> {
+        let size = || {
+            pair(value)
+                .filter(|size: &[u32; 2]| size[0] > 0 && size[1] > 0)
+                .ok_or_else(|| invalid(value))
+        };
+        match key {
+            "title" => self.title = string(value)?,
+            "size" => self.size = size()?,
+            "position" => self.position = Some(pair(value).ok_or_else(|| invalid(value))?),
+            "resizable" => self.resizable = boolean(value)?,
+            "min_size" => self.min_size = Some(size()?),
+            "max_size" => self.max_size = Some(size()?),
+            "fullscreen" => {
+                self.fullscreen = match value.as_str() {
+                    Some("windowed") => FullscreenMode::Windowed,
+                    Some("borderless") => FullscreenMode::Borderless,
+                    Some("exclusive") => FullscreenMode::Exclusive,
+                    _ => {
+                        return Err(format!(
+                            "{}, it's windowed, borderless or exclusive",
+                            invalid(value)
+                        ))
+                    }
+                }
+            }
+            "monitor" => self.monitor = Some(string(value)?),
+            "icon" => self.icon = Some(string(value)?.into()),
+            "cursor_visible" => self.cursor_visible = boolean(value)?,
+            "cursor_grab" => self.cursor_grab = boolean(value)?,
+            _ => return Err("isn't a setting".to_string()),
+        }
+        Ok(())
+    }
+
+    // settings that are None are left out
+    pub fn to_table(&self) -> Table {
+        let mut table = Table::new();
+        let pair = |pair: [i64; 2]| Value::Array(vec![pair[0].into(), pair[1].into()]);
+        let size = |size: [u32; 2]| pair([size[0] as i64, size[1] as i64]);
+        table.insert("title".to_string(), self.title.clone().into());
+        table.insert("size".to_string(), size(self.size));
+        if let Some(position) = self.position {
+            table.insert(
+                "position".to_string(),
+                pair([position[0] as i64, position[1] as i64]),
+            );
+        }
+        table.insert("resizable".to_string(), self.resizable.into());
+        if let Some(min_size) = self.min_size {
+            table.insert("min_size".to_string(), size(min_size));
+        }
+        if let Some(max_size) = self.max_size {
+            table.insert("max_size".to_string(), size(max_size));
+        }
+        let fullscreen = match self.fullscreen {
+            FullscreenMode::Windowed => "windowed",
+            FullscreenMode::Borderless => "borderless",
+            FullscreenMode::Exclusive => "exclusive",
+        };
+        table.insert("fullscreen".to_string(), fullscreen.into());
+        if let Some(monitor) = &self.monitor {
+            table.insert("monitor".to_string(), monitor.clone().into());
+        }
+        if let Some(icon) = &self.icon {
+            table.insert(
+                "icon".to_string(),
+                icon.to_string_lossy().into_owned().into(),
+            );
+        }
+        table.insert("cursor_visible".to_string(), self.cursor_visible.into());
+        table.insert("cursor_grab".to_string(), self.cursor_grab.into());
+        table
+    }
+
+    pub fn change(&mut self, change: &WindowChange) {
+        match change {
+            WindowChange::Title(title) => self.title = title.clone(),
+            WindowChange::Size(size) => self.size = *size,
+            WindowChange::Position(position) => self.position = Some(*position),
+            WindowChange::Resizable(resizable) => self.resizable = *resizable,
+            WindowChange::MinSize(min_size) => self.min_size = *min_size,
+            WindowChange::MaxSize(max_size) => self.max_size = *max_size,
+            WindowChange::Fullscreen(fullscreen) => self.fullscreen = *fullscreen,
+            WindowChange::Monitor(monitor) => self.monitor = monitor.clone(),
+            WindowChange::CursorVisible(visible) => self.cursor_visible = *visible,
+            WindowChange::CursorGrab(grab) => self.cursor_grab = *grab,
+        }
+    }
+}
+
+fn pair<T: std::convert::TryFrom<i64>>(value: &Value) -> Option<[T; 2]> {
+    match value.as_array()?.as_slice() {
+        [x, y] => {
+            use std::convert::TryInto;
+            Some([
+                x.as_integer()?.try_into().ok()?,
+                y.as_integer()?.try_into().ok()?,
+            ])
+        }
+        _ => None,
+    }
+}
 
 #[derive(Debug)]
 pub struct Window {
-    pub event_loop: EventLoop<()>,
     pub window: window::Window,
     pub surface_extent: Extent2D,
+    // follows the window as it's changed, so saving it restores the window next time
+    pub config: WindowConfig,
+    // events sent through it come back from the event loop and reach the queue
+    pub proxy: EventLoopProxy<PlatformEvent>,
 }
 
 impl Window {
-    pub fn new() -> Result<Self, ()> {
-        let event_loop = EventLoop::new();
-        let (logical_window_size, physical_window_size) = {
-            let dpi = event_loop.primary_monitor().unwrap().scale_factor();
-            let logical: LogicalSize<u32> = WINDOW_SIZE.into();
-            let physical: PhysicalSize<u32> = logical.to_physical(dpi);
-            (logical, physical)
+    pub fn new(config: WindowConfig) -> Result<(Self, EventLoop<PlatformEvent>), ()> {
+        let event_loop = EventLoop::with_user_event();
+        let icon = config.icon.as_ref().and_then(|path| {
+            load_icon(path)
+                .map_err(|error| log::warn!("{}", error))
+                .ok()
+        });
+        let mut builder = WindowBuilder::new()
+            .with_title(config.title.clone())
+            .with_inner_size(logical(config.size))
+            .with_resizable(config.resizable)
+            .with_window_icon(icon);
+        if let Some(min_size) = config.min_size {
+            builder = builder.with_min_inner_size(logical(min_size));
+        }
+        if let Some(max_size) = config.max_size {
+            builder = builder.with_max_inner_size(logical(max_size));
+        }
+        let window = builder
+            .build(&event_loop)
+            .map_err(|error| log::error!("Failed to create window: {}", error))?;
+        let size = window.inner_size();
+        let window = Self {
+            window,
+            surface_extent: Extent2D {
+                width: size.width,
+                height: size.height,
+            },
+            config,
+            proxy: event_loop.create_proxy(),
+        };
+        window.place();
+        window.apply_fullscreen();
+        window.apply_cursor();
+
+        Ok((window, event_loop))
+    }
+
+    // the change is applied once it comes back through the queue
+    pub fn request(&self, change: WindowChange) {
+        if self
+            .proxy
+            .send_event(PlatformEvent::ChangeWindow(change))
+            .is_err()
+        {
+            log::warn!("Event loop closed, window change dropped");
+        }
+    }
+
+    pub fn update(&mut self, event: &WEvent<PlatformEvent>) {
+        match event {
+            WEvent::UserEvent(PlatformEvent::ChangeWindow(change)) => self.apply(change.clone()),
+            WEvent::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                self.surface_extent = Extent2D {
+                    width: size.width,
+                    height: size.height,
+                };
+                // fullscreen and minimized sizes aren't the window's own
+                if self.config.fullscreen == FullscreenMode::Windowed
+                    && size.width * size.height > 0
+                {
+                    let size: LogicalSize<u32> = size.to_logical(self.window.scale_factor());
+                    self.config.size = [size.width, size.height];
+                }
+            }
+            WEvent::WindowEvent {
+                event: WindowEvent::Moved(position),
+                ..
+            } if self.config.fullscreen == FullscreenMode::Windowed => {
+                self.config.position = Some([position.x, position.y]);
+            }
+            WEvent::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::F11),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => {
+                let fullscreen = match self.config.fullscreen {
+                    FullscreenMode::Windowed => FullscreenMode::Borderless,
+                    _ => FullscreenMode::Windowed,
+                };
+                self.apply(WindowChange::Fullscreen(fullscreen));
+            }
+            _ => {}
+        }
+    }
+
+    pub fn apply(&mut self, change: WindowChange) {
+        self.config.change(&change);
+        let window = &self.window;
+        match change {
+            WindowChange::Title(title) => window.set_title(&title),
+            WindowChange::Size(size) => window.set_inner_size(logical(size)),
+            WindowChange::Position(_) => self.place(),
+            WindowChange::Resizable(resizable) => window.set_resizable(resizable),
+            WindowChange::MinSize(min_size) => window.set_min_inner_size(min_size.map(logical)),
+            WindowChange::MaxSize(max_size) => window.set_max_inner_size(max_size.map(logical)),
+            WindowChange::Fullscreen(_) => self.apply_fullscreen(),
+            WindowChange::Monitor(_) => {
+                // the window's position was on the previous monitor
+                self.config.position = None;
+                self.place();
+                self.apply_fullscreen();
+            }
+            WindowChange::CursorVisible(_) | WindowChange::CursorGrab(_) => self.apply_cursor(),
+        }
+    }
+
+    // the named monitor, the primary one or any
+    fn monitor(&self) -> Option<MonitorHandle> {
+        let named = self.config.monitor.as_ref().and_then(|name| {
+            let monitor = self
+                .window
+                .available_monitors()
+                .find(|monitor| monitor.name().as_ref() == Some(name));
+            if monitor.is_none() {
+                log::warn!("Monitor {} is not connected", name);
+            }
+            monitor
+        });
+        named
+            .or_else(|| self.window.primary_monitor())
+            .or_else(|| self.window.available_monitors().next())
+    }
+
+    // a window without a position goes to the top left corner of its monitor, when one was named
+    fn place(&self) {
+        let position = match (self.config.position, &self.config.monitor) {
+            (Some(position), _) => PhysicalPosition::new(position[0], position[1]),
+            (None, Some(_)) => match self.monitor() {
+                Some(monitor) => monitor.position(),
+                None => return,
+            },
+            (None, None) => return,
         };
-        let surface_extent = Extent2D {
-            width: physical_window_size.width,
-            height: physical_window_size.height,
+        self.window.set_outer_position(position);
+    }
+
+    fn apply_fullscreen(&self) {
+        let fullscreen = match self.config.fullscreen {
+            FullscreenMode::Windowed => None,
+            FullscreenMode::Borderless => Some(Fullscreen::Borderless(self.monitor())),
+            FullscreenMode::Exclusive => {
+                let monitor = self.monitor();
+                let size: PhysicalSize<u32> = logical(self.config.size)
+                    .to_physical(monitor.as_ref().map_or(1.0, MonitorHandle::scale_factor));
+                let mode = monitor.and_then(|monitor| {
+                    let modes: Vec<_> = monitor.video_modes().collect();
+                    let sizes: Vec<([u32; 2], u16)> = modes
+                        .iter()
+                        .map(|mode| ([mode.size().width, mode.size().height], mode.refresh_rate()))
+                        .collect();
+                    closest_mode(&sizes, [size.width, size.height])
+                        .map(|index| modes[index].clone())
+                });
+                match mode {
+                    Some(mode) => Some(Fullscreen::Exclusive(mode)),
+                    None => {
+                        log::warn!("No video mode for exclusive fullscreen, using borderless");
+                        Some(Fullscreen::Borderless(self.monitor()))
+                    }
+                }
+            }
         };
-        let window = WindowBuilder::new()
-            .with_title(APP_NAME)
-            .with_inner_size(logical_window_size)
-            .build(&event_loop)
-            .expect("Failed to create window");
+        self.window.set_fullscreen(fullscreen);
+    }
 
-        Ok(Self {
-            window,
-            event_loop,
-            surface_extent,
-        })
+    fn apply_cursor(&self) {
+        self.window.set_cursor_visible(self.config.cursor_visible);
+        if let Err(error) = self.window.set_cursor_grab(self.config.cursor_grab) {
+            log::warn!("Failed to grab the cursor: {}", error);
+        }
+    }
+}
+
+fn logical(size: [u32; 2]) -> LogicalSize<u32> {
+    size.into()
+}
+
+// the mode nearest in size, the fastest of those
+fn closest_mode(modes: &[([u32; 2], u16)], size: [u32; 2]) -> Option<usize> {
+    let distance = |mode: [u32; 2]| {
+        (mode[0] as i64 - size[0] as i64).abs() + (mode[1] as i64 - size[1] as i64).abs()
+    };
+    (0..modes.len()).min_by_key(|&index| {
+        let (mode, refresh_rate) = modes[index];
+        (distance(mode), std::cmp::Reverse(refresh_rate))
+    })
+}
+
+fn load_icon(path: &Path) -> Result<Icon, String> {
+    let file = File::open(path).map_err(|error| format!("Failed to open {:?}: {}", path, error))?;
+    let mut decoder = png::Decoder::new(file);
+    // palettes to rgb
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let (info, mut reader) = decoder
+        .read_info()
+        .map_err(|error| format!("Failed to read {:?}: {}", path, error))?;
+    let mut pixels = vec![0; info.buffer_size()];
+    reader
+        .next_frame(&mut pixels)
+        .map_err(|error| format!("Failed to read {:?}: {}", path, error))?;
+    let rgba = match info.color_type {
+        png::ColorType::RGBA => pixels,
+        png::ColorType::RGB => pixels
+            .chunks_exact(3)
+            .flat_map(|pixel| vec![pixel[0], pixel[1], pixel[2], 255])
+            .collect(),
+        color_type => return Err(format!("{:?} is {:?}, not rgb", path, color_type)),
+    };
+    Icon::from_rgba(rgba, info.width, info.height)
+        .map_err(|error| format!("Invalid icon {:?}: {:?}", path, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<WindowConfig, String> {
+        let table: Table = toml::from_str(text).unwrap();
+        let mut config = WindowConfig::default();
+        for (key, value) in &table {
+            config
+                .set(key, value)
+                .map_err(|reason| format!("`{}` {}", key, reason))?;
+        }
+        Ok(config)
+    }
+
+    #[test]
+    fn saves_and_loads_settings() {
+        let config = WindowConfig {
+            size: [1280, 720],
+            position: Some([-1920, 40]),
+            min_size: Some([320, 240]),
+            fullscreen: FullscreenMode::Exclusive,
+            monitor: Some("DP-1".to_string()),
+            cursor_grab: true,
+            ..WindowConfig::default()
+        };
+        let text = toml::to_string(&config.to_table()).unwrap();
+        assert_eq!(parse(&text), Ok(config));
+        assert_eq!(parse(""), Ok(WindowConfig::default()));
+    }
+
+    #[test]
+    fn names_invalid_settings() {
+        let error = parse("size = [640]").unwrap_err();
+        assert!(error.contains("`size`"), "{}", error);
+        let error = parse("fullscreen = \"sometimes\"").unwrap_err();
+        assert!(error.contains("`fullscreen`"), "{}", error);
+        assert!(parse("min_size = [-1, 2]").is_err());
+        assert!(parse("size = [0, 2]").is_err());
+        assert_eq!(
+            parse("sise = [1, 2]").unwrap_err(),
+            "`sise` isn't a setting"
+        );
+    }
+
+    #[test]
+    fn picks_closest_video_mode() {
+        let modes = [
+            ([1920, 1080], 60),
+            ([1280, 720], 60),
+            ([1280, 720], 144),
+            ([800, 600], 75),
+        ];
+        assert_eq!(closest_mode(&modes, [1280, 700]), Some(2));
+        assert_eq!(closest_mode(&modes, [640, 480]), Some(3));
+        assert_eq!(closest_mode(&[], [640, 480]), None);
     }
 }
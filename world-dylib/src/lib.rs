@@ -0,0 +1,15 @@
+use log::{LevelFilter, Log};
+use world::api::GameApi;
+
+// the library has a `log` of its own, nothing logs until it's given main's logger. Setting it
+// only fails if this copy of the library was handed it before
+#[no_mangle]
+#[allow(improper_ctypes_definitions)]
+pub extern "C" fn game_api(logger: &'static dyn Log, level: LevelFilter) -> GameApi {
+    let _ = log::set_logger(logger);
+    log::set_max_level(level);
+    world::api::game_api()
+}
+
+// main looks it up as this type, a changed signature fails here instead of when it's loaded
+const _: world::api::GameApiEntry = game_api;
@@ -0,0 +1,124 @@
+use super::settings::GameSettings;
+use super::WorldState;
+use common::event::PlatformEvent;
+use common::jobs::JobSystem;
+use common::plugin::PluginHost;
+use common::render::RenderCommands;
+use common::ui::Ui;
+use log::{LevelFilter, Log};
+use std::ffi::c_void;
+use winit::event::Event as WEvent;
+
+// the entry point world-dylib exports, it's handed main's logger and returns the table below.
+// Neither is FFI-safe, but both sides are built by the same compiler from this workspace
+pub const GAME_API_SYMBOL: &[u8] = b"game_api\0";
+#[allow(improper_ctypes_definitions)]
+pub type GameApiEntry = extern "C" fn(&'static dyn Log, LevelFilter) -> GameApi;
+
+// everything main calls the game through. A hot reloaded library is only reached through this
+// table, and the state passes through as a pointer so that its layout may change between builds
+#[derive(Debug, Clone, Copy)]
+pub struct GameApi {
+    // a new game, everything random in it follows from the seed
+    pub start: unsafe fn(u64) -> *mut c_void,
+    // restores what `save` returned, possibly in an older build. An empty slice starts a new game
+    pub load: unsafe fn(&[u8]) -> *mut c_void,
+    pub save: unsafe fn(*const c_void) -> Vec<u8>,
+    pub destroy: unsafe fn(*mut c_void),
+    // the settings aren't part of the state, they're passed again after loading
+    pub configure: unsafe fn(*mut c_void, &GameSettings),
+    pub handle_event: unsafe fn(*mut c_void, &WEvent<'static, PlatformEvent>, bool),
+    // runs the world's systems and the plugins' on the job system
+    pub update: unsafe fn(*mut c_void, &PluginHost, &JobSystem, f32),
+    pub render: unsafe fn(*mut c_void, &mut RenderCommands),
+    pub ui: unsafe fn(*mut c_void, &mut Ui),
+}
+
+pub fn game_api() -> GameApi {
+    GameApi {
+        start,
+        load,
+        save,
+        destroy,
+        configure,
+        handle_event,
+        update,
+        render,
+        ui,
+    }
+}
+
+unsafe fn start(seed: u64) -> *mut c_void {
+    Box::into_raw(Box::new(WorldState::with_seed(seed))) as *mut c_void
+}
+
+unsafe fn load(saved: &[u8]) -> *mut c_void {
+    let state = if saved.is_empty() {
+        WorldState::new()
+    } else {
+        WorldState::load(saved).unwrap_or_else(|error| {
+            log::warn!("Starting over, failed to load world state: {}", error);
+            WorldState::new()
+        })
+    };
+    Box::into_raw(Box::new(state)) as *mut c_void
+}
+
+unsafe fn state<'a>(state: *mut c_void) -> &'a mut WorldState {
+    &mut *(state as *mut WorldState)
+}
+
+unsafe fn save(state: *const c_void) -> Vec<u8> {
+    (*(state as *const WorldState)).save()
+}
+
+unsafe fn destroy(state: *mut c_void) {
+    drop(Box::from_raw(state as *mut WorldState));
+}
+
+unsafe fn configure(world: *mut c_void, settings: &GameSettings) {
+    state(world).settings = settings.clone();
+}
+
+unsafe fn handle_event(
+    world: *mut c_void,
+    event: &WEvent<'static, PlatformEvent>,
+    pointer_captured: bool,
+) {
+    state(world).handle_event(event, pointer_captured);
+}
+
+unsafe fn update(world: *mut c_void, plugins: &PluginHost, jobs: &JobSystem, dt: f32) {
+    state(world).update(plugins, jobs, dt);
+}
+
+unsafe fn render(world: *mut c_void, commands: &mut RenderCommands) {
+    state(world).render(commands);
+}
+
+unsafe fn ui(world: *mut c_void, ui: &mut Ui) {
+    state(world).ui(ui);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn carries_state_across_the_api() {
+        let api = game_api();
+        unsafe {
+            let world = (api.start)(3);
+            state(world).player = (0.25, 2.0);
+            state(world).show_ui = false;
+            state(world).random = common::random::Random::new(u64::MAX - 1);
+            let saved = (api.save)(world);
+            (api.destroy)(world);
+            let world = (api.load)(&saved);
+            assert_eq!(state(world).player, (0.25, 2.0));
+            assert!(!state(world).show_ui);
+            assert_eq!(state(world).random.state(), u64::MAX - 1);
+            (api.destroy)(world);
+        }
+    }
+}
@@ -1,65 +1,381 @@
+pub mod api;
+pub mod settings;
+
+use common::debug::DebugDraw;
+use common::event::PlatformEvent;
+use common::jobs::{JobSystem, Schedule};
+use common::plugin::{PluginHost, PluginWorld};
+use common::random::Random;
+use common::render::{CameraView, Layer, RenderCommand, RenderCommands, Shape, ShapeKind, Text};
+use common::ui::Ui;
 use queue::{event::Event, receiver::Receiver};
+use settings::GameSettings;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use toml::value::{Table, Value};
 
 // TODO: remove winit dependency
-use winit::event::{Event as WEvent, VirtualKeyCode, WindowEvent};
+use winit::event::{ElementState, Event as WEvent, VirtualKeyCode, WindowEvent};
 
 #[derive(Debug)]
 pub struct WorldState {
     pub player: (f32, f32),
+    // toggled with F3
+    pub debug: DebugDraw,
+    // toggled with F1
+    pub show_ui: bool,
+    // everything random comes from here, so that a seed repeats a game
+    pub random: Random,
+    // not saved, the settings are handed to every build again
+    pub settings: GameSettings,
+}
+impl Default for WorldState {
+    fn default() -> Self {
+        Self::with_seed(0)
+    }
 }
 impl WorldState {
     pub fn new() -> Self {
+        Self::with_seed(0)
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
         Self {
             player: (-0.5, -0.5),
+            debug: DebugDraw::new(),
+            show_ui: true,
+            random: Random::new(seed),
+            settings: GameSettings::default(),
+        }
+    }
+
+    // a toml table, so that a build with different fields can still read what this one saved
+    pub fn save(&self) -> Vec<u8> {
+        let mut table = Table::new();
+        let player = vec![
+            Value::Float(self.player.0 as f64),
+            Value::Float(self.player.1 as f64),
+        ];
+        table.insert("player".to_string(), Value::Array(player));
+        table.insert("debug_draw".to_string(), self.debug.enabled.into());
+        table.insert("show_ui".to_string(), self.show_ui.into());
+        // toml integers are signed, the bits are kept as they are
+        table.insert("random".to_string(), (self.random.state() as i64).into());
+        toml::to_string(&table)
+            .expect("World state is always valid toml")
+            .into_bytes()
+    }
+
+    // fields missing from `saved` keep their initial values
+    pub fn load(saved: &[u8]) -> Result<Self, String> {
+        let text = std::str::from_utf8(saved).map_err(|error| error.to_string())?;
+        let table: Table = toml::from_str(text).map_err(|error| error.to_string())?;
+        let mut state = Self::new();
+        for (key, value) in &table {
+            let invalid = || format!("`{}` can't be {}", key, value);
+            match key.as_str() {
+                "player" => {
+                    let player = value.as_array().ok_or_else(invalid)?;
+                    match player.as_slice() {
+                        [Value::Float(x), Value::Float(y)] => state.player = (*x as f32, *y as f32),
+                        _ => return Err(invalid()),
+                    }
+                }
+                "debug_draw" => state.debug.enabled = value.as_bool().ok_or_else(invalid)?,
+                "show_ui" => state.show_ui = value.as_bool().ok_or_else(invalid)?,
+                "random" => {
+                    state.random = Random::new(value.as_integer().ok_or_else(invalid)? as u64)
+                }
+                _ => log::warn!("Dropping unknown world state `{}`", key),
+            }
+        }
+        Ok(state)
+    }
+
+    // everything the renderer needs to draw this state, replacing what was in `commands`
+    pub fn render(&mut self, commands: &mut RenderCommands) {
+        let player = [self.player.0, self.player.1];
+        commands.clear();
+        commands.push(RenderCommand::Clear([0.0, 0.0, 0.0, 1.0]));
+        commands.push(RenderCommand::Camera(CameraView {
+            focus: player,
+            zoom: 1.0,
+            rotation: 0.0,
+        }));
+        commands.push(RenderCommand::Shape(Shape {
+            kind: ShapeKind::Triangle,
+            position: player,
+            scale: [0.33, 0.33],
+            color: [1.0, 0.0, 0.0, 1.0],
+            layer: Layer::Entities,
+            z: 0,
+        }));
+        commands.push(RenderCommand::Text(Text::new(
+            format!("player {:.2} {:.2}", self.player.0, self.player.1),
+            [8.0, 8.0],
+            16.0,
+        )));
+        self.debug.flush(commands);
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        if !self.show_ui {
+            return;
+        }
+        let (x, y) = self.player;
+        let debug = &mut self.debug;
+        let mut reset = false;
+        ui.panel("debug", [8.0, 32.0], 200.0, |ui| {
+            ui.label(&format!("player {:.2} {:.2}", x, y));
+            ui.checkbox("debug draw", &mut debug.enabled);
+            reset = ui.button("reset player");
+        });
+        if reset {
+            self.player = Self::new().player;
+        }
+    }
+
+    // pointer events are dropped while the ui captures the pointer
+    pub fn handle_event(&mut self, event: &WEvent<PlatformEvent>, pointer_captured: bool) {
+        if let WEvent::WindowEvent { event, .. } = event {
+            if pointer_captured && is_pointer_event(event) {
+                return;
+            }
+        }
+        if let WEvent::WindowEvent {
+            event:
+                WindowEvent::KeyboardInput {
+                    input,
+                    is_synthetic,
+                    ..
+                },
+            ..
+        } = event
+        {
+            // ignore synthetic tab presses so that we don't get tabs when alt-tabbing back into the window
+            if matches!(input.virtual_keycode, Some(VirtualKeyCode::Tab)) && *is_synthetic {
+                return;
+            }
+            if let Some(key) = input.virtual_keycode {
+                log::trace!("pressed {:?}", input);
+                let pressed = input.state == ElementState::Pressed;
+                let controls = &self.settings.controls;
+                let step = self.settings.player_step;
+                if key == controls.toggle_ui && pressed {
+                    self.show_ui = !self.show_ui
+                } else if key == controls.toggle_debug && pressed {
+                    self.debug.toggle()
+                } else if key == controls.up {
+                    self.player.1 -= step
+                } else if key == controls.left {
+                    self.player.0 -= step
+                } else if key == controls.down {
+                    self.player.1 += step
+                } else if key == controls.right {
+                    self.player.0 += step
+                }
+            }
         }
     }
+
+    // one tick of the world, its systems and those of the plugins run on the job system and
+    // apart from each other only where they touch the same resources
+    pub fn update(&mut self, plugins: &PluginHost, jobs: &JobSystem, dt: f32) {
+        let mut schedule = Schedule::new();
+        for system in plugins.systems() {
+            // plugins see and change the world through a copy of the parts they may touch
+            schedule.add(
+                "plugin",
+                &[],
+                &["player"],
+                move |resources: &Resources, dt| {
+                    let mut player = lock(&resources.player);
+                    let mut world = PluginWorld {
+                        player: [player.0, player.1],
+                    };
+                    system.run(&mut world, dt);
+                    *player = (world.player[0], world.player[1]);
+                },
+            );
+        }
+        schedule.add("debug draw", &["player"], &["debug"], |resources, _| {
+            debug_draw(&mut lock(&resources.debug), *lock(&resources.player))
+        });
+        let resources = Arc::new(Resources {
+            player: Mutex::new(self.player),
+            debug: Mutex::new(std::mem::take(&mut self.debug)),
+        });
+        schedule.run(jobs, &resources, dt);
+        let resources = Arc::try_unwrap(resources).expect("a system outlived the schedule");
+        self.player = resources
+            .player
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        self.debug = resources
+            .debug
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+    }
+}
+
+// the parts of the world systems share during an update, each behind a lock of its own
+#[derive(Debug)]
+struct Resources {
+    player: Mutex<(f32, f32)>,
+    debug: Mutex<DebugDraw>,
+}
+
+// a system that panicked doesn't stop the others
+fn lock<T>(resource: &Mutex<T>) -> MutexGuard<'_, T> {
+    resource.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn debug_draw(debug: &mut DebugDraw, (x, y): (f32, f32)) {
+    let green = [0.0, 1.0, 0.0, 1.0];
+    // the player triangle's bounds
+    debug.rect([x - 0.165, y - 0.165], [0.33, 0.33], green);
+    debug.arrow([0.0, 0.0], [0.25, 0.0], [1.0, 0.0, 0.0, 1.0]);
+    debug.arrow([0.0, 0.0], [0.0, 0.25], [0.0, 0.0, 1.0, 1.0]);
+    debug.text("origin", [0.0, 0.0], green);
 }
 
 #[derive(Debug)]
 pub struct World<'a> {
-    pub events: Receiver<Event<WEvent<'a, ()>>>,
+    pub events: Receiver<Event<WEvent<'a, PlatformEvent>>>,
 }
 impl<'a> World<'a> {
-    pub fn start(events: Receiver<Event<WEvent<'a, ()>>>) -> Self {
+    pub fn start(events: Receiver<Event<WEvent<'a, PlatformEvent>>>) -> Self {
         Self { events }
     }
 
-    pub fn proccess_events(&self, world: &mut WorldState) {
+    pub fn proccess_events(&self, world: &mut WorldState, pointer_captured: bool) {
         let event = self.events.try_recv().unwrap();
-        match event.payload {
-            WEvent::WindowEvent {
-                event:
-                    WindowEvent::KeyboardInput {
-                        input,
-                        is_synthetic,
-                        ..
-                    },
-                ..
-            } => {
-                // ignore synthetic tab presses so that we don't get tabs when alt-tabbing back into the window
-                if matches!(input.virtual_keycode, Some(VirtualKeyCode::Tab)) && is_synthetic {
-                    return;
-                }
-                if let Some(key) = input.virtual_keycode {
-                    println!("PRESSED ${:?}", input);
-                    match key {
-                        VirtualKeyCode::W => world.player = (world.player.0, world.player.1 - 0.01),
-                        VirtualKeyCode::A => world.player = (world.player.0 - 0.01, world.player.1),
-                        VirtualKeyCode::S => world.player = (world.player.0, world.player.1 + 0.01),
-                        VirtualKeyCode::D => world.player = (world.player.0 + 0.01, world.player.1),
-                        _ => {}
-                    }
-                }
-            }
-            _ => {}
-        }
+        world.handle_event(&event.payload, pointer_captured);
     }
 }
 
+fn is_pointer_event(event: &WindowEvent) -> bool {
+    matches!(
+        event,
+        WindowEvent::CursorMoved { .. }
+            | WindowEvent::CursorEntered { .. }
+            | WindowEvent::CursorLeft { .. }
+            | WindowEvent::MouseInput { .. }
+            | WindowEvent::MouseWheel { .. }
+    )
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn renders_player() {
+        let mut commands = RenderCommands::new();
+        WorldState::new().render(&mut commands);
+        assert_eq!(commands.commands.len(), 4);
+        let dump = commands.dump();
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(
+            lines[..3],
+            [
+                "Clear([0.0, 0.0, 0.0, 1.0])",
+                "Camera(CameraView { focus: [-0.5, -0.5], zoom: 1.0, rotation: 0.0 })",
+                "Shape(Shape { kind: Triangle, position: [-0.5, -0.5], scale: [0.33, 0.33], \
+                 color: [1.0, 0.0, 0.0, 1.0], layer: Entities, z: 0 })",
+            ]
+        );
+        assert!(lines[3].contains("content: \"player -0.50 -0.50\""));
+    }
+
+    #[test]
+    fn renders_debug_shapes_when_enabled() {
+        let mut commands = RenderCommands::new();
+        let mut world = WorldState::new();
+        world.debug.toggle();
+        world.update(&PluginHost::new(), &JobSystem::new(1), 0.0);
+        world.render(&mut commands);
+        let debug = &commands.commands[4..];
+        if cfg!(debug_assertions) {
+            assert!(matches!(debug[0], RenderCommand::Line(_)));
+            assert!(matches!(debug.last(), Some(RenderCommand::Text(_))));
+        } else {
+            assert!(debug.is_empty());
+        }
+    }
+
+    #[test]
+    fn resets_player_from_ui() {
+        use common::ui::UiInput;
+        let mut world = WorldState::new();
+        world.player = (1.0, 1.0);
+        let mut ui = Ui::new();
+        // below the title, label and checkbox rows
+        let button = Some([50.0, 120.0]);
+        let inputs = [
+            UiInput {
+                cursor: button,
+                down: true,
+                pressed: true,
+                released: false,
+            },
+            UiInput {
+                cursor: button,
+                down: false,
+                pressed: false,
+                released: true,
+            },
+        ];
+        for input in inputs.iter() {
+            ui.begin(*input);
+            world.ui(&mut ui);
+            ui.finish(&mut RenderCommands::new());
+        }
+        assert_eq!(world.player, (-0.5, -0.5));
+        assert!(ui.wants_pointer());
+    }
+
+    #[test]
+    fn loads_older_saves() {
+        let state = WorldState::load(b"player = [1.5, -2.0]\nscore = 3\n").unwrap();
+        assert_eq!(state.player, (1.5, -2.0));
+        assert!(state.show_ui);
+        let error = WorldState::load(b"show_ui = 1").unwrap_err();
+        assert!(error.contains("`show_ui`"), "{}", error);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn moves_with_the_configured_keys() {
+        use winit::event::{DeviceId, KeyboardInput, ModifiersState};
+        use winit::window::WindowId;
+        let press = |key| WEvent::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event: WindowEvent::KeyboardInput {
+                device_id: unsafe { DeviceId::dummy() },
+                input: KeyboardInput {
+                    scancode: 0,
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(key),
+                    modifiers: ModifiersState::empty(),
+                },
+                is_synthetic: false,
+            },
+        };
+        let mut world = WorldState::new();
+        world.settings.player_step = 0.5;
+        world
+            .settings
+            .controls
+            .bind("right", VirtualKeyCode::Right)
+            .unwrap();
+        world.handle_event(&press(VirtualKeyCode::Right), false);
+        world.handle_event(&press(VirtualKeyCode::D), false);
+        world.handle_event(&press(VirtualKeyCode::W), false);
+        assert_eq!(world.player, (0.0, -1.0));
+    }
 }
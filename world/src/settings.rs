@@ -0,0 +1,87 @@
+use common::settings::{float, positive};
+use toml::value::{Table, Value};
+use winit::event::VirtualKeyCode;
+
+// the key for each action, the `input` section of the settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct Controls {
+    pub up: VirtualKeyCode,
+    pub down: VirtualKeyCode,
+    pub left: VirtualKeyCode,
+    pub right: VirtualKeyCode,
+    pub toggle_ui: VirtualKeyCode,
+    pub toggle_debug: VirtualKeyCode,
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Self {
+            up: VirtualKeyCode::W,
+            down: VirtualKeyCode::S,
+            left: VirtualKeyCode::A,
+            right: VirtualKeyCode::D,
+            toggle_ui: VirtualKeyCode::F1,
+            toggle_debug: VirtualKeyCode::F3,
+        }
+    }
+}
+
+impl Controls {
+    pub fn bind(&mut self, action: &str, key: VirtualKeyCode) -> Result<(), String> {
+        match action {
+            "up" => self.up = key,
+            "down" => self.down = key,
+            "left" => self.left = key,
+            "right" => self.right = key,
+            "toggle_ui" => self.toggle_ui = key,
+            "toggle_debug" => self.toggle_debug = key,
+            _ => return Err("isn't a setting".to_string()),
+        }
+        Ok(())
+    }
+
+    pub fn bindings(&self) -> Vec<(&'static str, VirtualKeyCode)> {
+        vec![
+            ("up", self.up),
+            ("down", self.down),
+            ("left", self.left),
+            ("right", self.right),
+            ("toggle_ui", self.toggle_ui),
+            ("toggle_debug", self.toggle_debug),
+        ]
+    }
+}
+
+// what the game logic is tuned with, the `gameplay` section of the settings, and its controls
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameSettings {
+    // how far the player moves per key press or repeat
+    pub player_step: f32,
+    pub controls: Controls,
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        Self {
+            player_step: 0.01,
+            controls: Controls::default(),
+        }
+    }
+}
+
+impl GameSettings {
+    // one key of the `gameplay` section, the error says what's wrong with the value
+    pub fn set(&mut self, key: &str, value: &Value) -> Result<(), String> {
+        match key {
+            "player_step" => self.player_step = positive(value)?,
+            _ => return Err("isn't a setting".to_string()),
+        }
+        Ok(())
+    }
+
+    pub fn to_table(&self) -> Table {
+        let mut table = Table::new();
+        table.insert("player_step".to_string(), float(self.player_step));
+        table
+    }
+}